@@ -0,0 +1,333 @@
+//! Hand analysis: shanten numbers and tenpai waits.
+//!
+//! Standard shanten is computed per suit from a table of "tiles needed to
+//! form `m` melds (with or without a pair)" and the per-suit vectors are
+//! combined with a min-plus convolution. Suit vectors are cached per thread,
+//! so repeated analysis of slowly changing hands mostly hits the cache.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::observe::Hand;
+use crate::piece::{Piece, TILE_KINDS};
+use crate::view::PlayerView;
+
+/// Number of copies of each tile kind, indexed by [`Piece::index`]
+pub type TileCounts = [u8; TILE_KINDS];
+
+/// Count pieces by kind, treating red fives as plain fives
+pub fn tile_counts(pieces: &[Piece]) -> TileCounts {
+    let mut counts = [0; TILE_KINDS];
+    for piece in pieces {
+        counts[piece.index()] += 1;
+    }
+    counts
+}
+
+/// Shanten numbers for each hand shape. `-1` means the hand is complete and
+/// `0` means it is tenpai.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shanten {
+    pub standard: i8,
+    /// Only available for fully concealed hands without melds
+    pub chiitoitsu: Option<i8>,
+    /// Only available for fully concealed hands without melds
+    pub kokushi: Option<i8>,
+}
+
+impl Shanten {
+    /// Get the lowest shanten across all hand shapes
+    pub fn min(&self) -> i8 {
+        [Some(self.standard), self.chiitoitsu, self.kokushi]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(self.standard)
+    }
+
+    /// Check if the hand is one tile away from winning
+    pub fn is_tenpai(&self) -> bool {
+        self.min() == 0
+    }
+
+    /// Check if the hand is a complete winning shape
+    pub fn is_complete(&self) -> bool {
+        self.min() < 0
+    }
+}
+
+/// A winning tile and how many copies are still unseen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wait {
+    pub piece: Piece,
+    pub remaining: u8,
+}
+
+const INF: u8 = 32;
+
+/// Tiles needed per meld count: `[0..5]` without a pair, `[5..10]` with one
+type Distances = [u8; 10];
+
+fn combine(a: &Distances, b: &Distances) -> Distances {
+    let mut out = [INF; 10];
+    for i in 0..5 {
+        for j in 0..5 - i {
+            out[i + j] = out[i + j].min(a[i] + b[j]);
+            out[5 + i + j] = out[5 + i + j].min(a[5 + i] + b[j]).min(a[i] + b[5 + j]);
+        }
+    }
+    out
+}
+
+/// Every meld/pair arrangement that fits in a single numbered suit
+fn suit_targets() -> &'static [([u8; 9], usize, bool)] {
+    static TARGETS: OnceLock<Vec<([u8; 9], usize, bool)>> = OnceLock::new();
+    TARGETS.get_or_init(|| {
+        let mut shapes: Vec<[u8; 9]> = Vec::with_capacity(16);
+        for start in 0..7 {
+            let mut shape = [0; 9];
+            shape[start..start + 3].fill(1);
+            shapes.push(shape);
+        }
+        for rank in 0..9 {
+            let mut shape = [0; 9];
+            shape[rank] = 3;
+            shapes.push(shape);
+        }
+
+        fn collect(
+            shapes: &[[u8; 9]],
+            from: usize,
+            current: [u8; 9],
+            melds: usize,
+            out: &mut Vec<([u8; 9], usize)>,
+        ) {
+            out.push((current, melds));
+            if melds == 4 {
+                return;
+            }
+            for (i, shape) in shapes.iter().enumerate().skip(from) {
+                let mut next = current;
+                let mut fits = true;
+                for rank in 0..9 {
+                    next[rank] += shape[rank];
+                    fits &= next[rank] <= 4;
+                }
+                if fits {
+                    collect(shapes, i, next, melds + 1, out);
+                }
+            }
+        }
+
+        let mut meld_sets = Vec::new();
+        collect(&shapes, 0, [0; 9], 0, &mut meld_sets);
+
+        let mut targets = Vec::new();
+        for (counts, melds) in meld_sets {
+            targets.push((counts, melds, false));
+            for rank in 0..9 {
+                if counts[rank] <= 2 {
+                    let mut with_pair = counts;
+                    with_pair[rank] += 2;
+                    targets.push((with_pair, melds, true));
+                }
+            }
+        }
+        targets
+    })
+}
+
+fn numbered_suit_distances(counts: &[u8]) -> Distances {
+    thread_local! {
+        static CACHE: RefCell<HashMap<u32, Distances>> = RefCell::new(HashMap::new());
+    }
+
+    let key = counts.iter().fold(0u32, |key, &c| key * 5 + c as u32);
+    if let Some(hit) = CACHE.with(|cache| cache.borrow().get(&key).copied()) {
+        return hit;
+    }
+
+    let mut out = [INF; 10];
+    for (target, melds, pair) in suit_targets() {
+        let needed: u8 = target
+            .iter()
+            .zip(counts)
+            .map(|(&want, &have)| want.saturating_sub(have))
+            .sum();
+        let slot = melds + if *pair { 5 } else { 0 };
+        out[slot] = out[slot].min(needed);
+    }
+
+    CACHE.with(|cache| cache.borrow_mut().insert(key, out));
+    out
+}
+
+fn honor_distances(count: u8) -> Distances {
+    let mut out = [INF; 10];
+    out[0] = 0;
+    out[1] = 3u8.saturating_sub(count);
+    out[5] = 2u8.saturating_sub(count);
+    out
+}
+
+/// Standard (four melds and a pair) shanten for concealed counts, given the
+/// number of melds already called
+pub fn standard_shanten(counts: &TileCounts, called_melds: usize) -> i8 {
+    let mut total = numbered_suit_distances(&counts[0..9]);
+    for suit in 1..3 {
+        total = combine(
+            &total,
+            &numbered_suit_distances(&counts[suit * 9..suit * 9 + 9]),
+        );
+    }
+    for &count in &counts[27..] {
+        total = combine(&total, &honor_distances(count));
+    }
+
+    let required = 4usize.saturating_sub(called_melds);
+    total[5 + required] as i8 - 1
+}
+
+/// Seven pairs shanten for concealed counts
+pub fn chiitoitsu_shanten(counts: &TileCounts) -> i8 {
+    let pairs = counts.iter().filter(|&&c| c >= 2).count() as i8;
+    let kinds = counts.iter().filter(|&&c| c >= 1).count() as i8;
+    6 - pairs + (7 - kinds).max(0)
+}
+
+/// Thirteen orphans shanten for concealed counts
+pub fn kokushi_shanten(counts: &TileCounts) -> i8 {
+    let orphans =
+        (0..TILE_KINDS).filter(|&i| Piece::from_index(i).is_some_and(Piece::is_terminal_or_honor));
+    let (kinds, has_pair) = orphans.fold((0i8, false), |(kinds, pair), i| {
+        (kinds + (counts[i] > 0) as i8, pair || counts[i] >= 2)
+    });
+    13 - kinds - has_pair as i8
+}
+
+fn shanten_for_counts(counts: &TileCounts, called_melds: usize) -> Shanten {
+    let concealed = called_melds == 0;
+    Shanten {
+        standard: standard_shanten(counts, called_melds),
+        chiitoitsu: concealed.then(|| chiitoitsu_shanten(counts)),
+        kokushi: concealed.then(|| kokushi_shanten(counts)),
+    }
+}
+
+/// Compute the shanten numbers of a hand's live pieces
+pub fn shanten(hand: &Hand) -> Shanten {
+    shanten_for_counts(&tile_counts(&hand.live_pieces), hand.meld_count())
+}
+
+/// Get every tile kind that would complete the hand. Empty unless the hand
+/// is tenpai and waiting for a tile (`3n + 1` live pieces).
+pub fn winning_pieces(hand: &Hand) -> Vec<Piece> {
+    if hand.live_piece_count() % 3 != 1 {
+        return Vec::new();
+    }
+
+    let mut counts = tile_counts(&hand.live_pieces);
+    let melds = hand.meld_count();
+    if shanten_for_counts(&counts, melds).min() != 0 {
+        return Vec::new();
+    }
+
+    (0..TILE_KINDS)
+        .filter(|&i| {
+            if counts[i] >= 4 {
+                return false;
+            }
+            counts[i] += 1;
+            let complete = shanten_for_counts(&counts, melds).is_complete();
+            counts[i] -= 1;
+            complete
+        })
+        .filter_map(Piece::from_index)
+        .collect()
+}
+
+/// Count the tiles a seat can see outside its own hand: every discard,
+/// every visible called meld and its own concealed pieces
+pub fn visible_counts(view: &PlayerView) -> TileCounts {
+    let mut counts = tile_counts(&view.hand.live_pieces);
+    for seat in &view.seats {
+        for piece in &seat.discards {
+            counts[piece.index()] += 1;
+        }
+        for meld in &seat.melds {
+            for piece in meld.pieces() {
+                counts[piece.index()] += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Get the winning tiles for the viewing seat together with how many copies
+/// remain unseen from its point of view
+pub fn waits(view: &PlayerView) -> Vec<Wait> {
+    let visible = visible_counts(view);
+
+    winning_pieces(&view.hand)
+        .into_iter()
+        .map(|piece| Wait {
+            piece,
+            remaining: 4u8.saturating_sub(visible[piece.index()]),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{format_pieces, parse_hand};
+
+    fn shanten_of(notation: &str) -> Shanten {
+        shanten(&parse_hand(notation).unwrap())
+    }
+
+    #[test]
+    fn detects_complete_and_tenpai_hands() {
+        assert_eq!(shanten_of("123m456p789s11z222z").standard, -1);
+        assert_eq!(shanten_of("123m456p789s11z22z").standard, 0);
+        assert_eq!(shanten_of("123m456p789s1z2z3z4z").standard, 2);
+        assert_eq!(shanten_of("19m19p19s1234567z").kokushi, Some(0));
+        assert_eq!(shanten_of("1133557799m113p").chiitoitsu, Some(0));
+        assert_eq!(shanten_of("147m258p369s1234z").min(), 6);
+    }
+
+    #[test]
+    fn accounts_for_called_melds() {
+        let hand = parse_hand("456p1z (123m) (789s) (2222z)").unwrap();
+        let result = shanten(&hand);
+        assert_eq!(result.standard, 0);
+        assert_eq!(result.chiitoitsu, None);
+        assert_eq!(format_pieces(&winning_pieces(&hand)), "1z");
+    }
+
+    #[test]
+    fn finds_all_winning_pieces() {
+        let nine_gates = parse_hand("1112345678999m").unwrap();
+        assert_eq!(format_pieces(&winning_pieces(&nine_gates)), "123456789m");
+
+        let kokushi = parse_hand("19m19p19s1234567z").unwrap();
+        assert_eq!(
+            format_pieces(&winning_pieces(&kokushi)),
+            "19m19p19s1234567z"
+        );
+
+        let not_tenpai = parse_hand("147m258p369s1234z").unwrap();
+        assert!(winning_pieces(&not_tenpai).is_empty());
+    }
+
+    #[test]
+    fn ignores_waits_on_tiles_held_four_times() {
+        // Only a fifth 1m would complete this hand
+        let hand = parse_hand("1111m234p567s789s").unwrap();
+        assert!(winning_pieces(&hand).is_empty());
+
+        let hand = parse_hand("1112m456p789s111z").unwrap();
+        assert_eq!(format_pieces(&winning_pieces(&hand)), "23m");
+    }
+}
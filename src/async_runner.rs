@@ -0,0 +1,517 @@
+//! Tokio driver for games.
+//!
+//! Native calls block, so [`AsyncGameState`] runs every advance on tokio's
+//! blocking pool and keeps the runtime's worker threads free. Games can be
+//! consumed as a [`GameStream`] of observed states, cancelled through a
+//! [`CancelHandle`] and bounded with per-step timeouts. Controllers that
+//! need to await (network bots, model servers) implement [`AsyncController`]
+//! and are bridged onto [`Controller`] with [`AsyncControllerBridge`]. Like
+//! every Rust controller they are only seated by [`Backend::Engine`], so
+//! games using them are created with [`AsyncGameState::with_backend`].
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::{spawn_blocking, JoinError, JoinHandle};
+
+use crate::controller::{register_controller, CallOption, Controller, Decision, Event};
+use crate::ffi::error::MahjongFFIError;
+use crate::ffi::gamesettings::OwnedCGameSettings;
+use crate::ffi::gamestate::{Backend, GameState};
+use crate::observe::{ObservedGameState, StateFunctionType};
+use crate::piece::{Honor, Piece};
+use crate::runner::DEFAULT_STEP_LIMIT;
+use crate::settings::GameSettings;
+use crate::view::PlayerView;
+
+fn join_error(error: JoinError) -> MahjongFFIError {
+    MahjongFFIError::BlockingTaskFailed(error.to_string())
+}
+
+/// Cancels every advance of the game it was taken from
+#[derive(Debug, Clone)]
+pub struct CancelHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for CancelHandle {
+    fn default() -> Self {
+        Self {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+}
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    async fn cancelled(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives in `self`, so the channel cannot close here
+        let _ = receiver.wait_for(|&cancelled| cancelled).await;
+    }
+}
+
+/// A [`GameState`] whose native calls run on the blocking pool
+pub struct AsyncGameState {
+    state: Option<GameState>,
+    pending: Option<JoinHandle<Result<GameState, MahjongFFIError>>>,
+    cancel: CancelHandle,
+}
+
+impl AsyncGameState {
+    pub fn new(state: GameState) -> Self {
+        Self {
+            state: Some(state),
+            pending: None,
+            cancel: CancelHandle::default(),
+        }
+    }
+
+    /// Create a new game from settings on the blocking pool
+    pub async fn from_settings<S>(settings: S) -> Result<Self, MahjongFFIError>
+    where
+        S: TryInto<OwnedCGameSettings, Error = MahjongFFIError> + Send + 'static,
+    {
+        let state = spawn_blocking(move || GameState::new(settings))
+            .await
+            .map_err(join_error)??;
+        Ok(Self::new(state))
+    }
+
+    /// Create a new game played by `backend` on the blocking pool
+    pub async fn with_backend(
+        settings: GameSettings,
+        backend: Backend,
+    ) -> Result<Self, MahjongFFIError> {
+        let state = spawn_blocking(move || GameState::with_backend(settings, backend))
+            .await
+            .map_err(join_error)??;
+        Ok(Self::new(state))
+    }
+
+    /// Get a handle that cancels pending and future advances
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Observe the current state. Fails with `GameStateConsumed` while an
+    /// advance is still running.
+    pub fn observe(&self) -> Result<ObservedGameState, MahjongFFIError> {
+        self.state
+            .as_ref()
+            .ok_or(MahjongFFIError::GameStateConsumed)?
+            .try_observe()
+    }
+
+    /// Advance the game on the blocking pool.
+    ///
+    /// Cancel safe: if the future is dropped or times out, the advance keeps
+    /// running and the next call picks up its result.
+    pub async fn advance(&mut self) -> Result<(), MahjongFFIError> {
+        if self.cancel.is_cancelled() {
+            return Err(MahjongFFIError::Cancelled);
+        }
+
+        let pending = match &mut self.pending {
+            Some(pending) => pending,
+            None => {
+                let state = self
+                    .state
+                    .take()
+                    .ok_or(MahjongFFIError::GameStateConsumed)?;
+                self.pending.insert(spawn_blocking(move || state.advance()))
+            }
+        };
+
+        let result = tokio::select! {
+            result = pending => result,
+            _ = self.cancel.cancelled() => return Err(MahjongFFIError::Cancelled),
+        };
+        self.pending = None;
+
+        self.state = Some(result.map_err(join_error)??);
+        Ok(())
+    }
+
+    /// Advance the game, giving up after `timeout`
+    pub async fn advance_timeout(&mut self, timeout: Duration) -> Result<(), MahjongFFIError> {
+        tokio::time::timeout(timeout, self.advance())
+            .await
+            .map_err(|_| MahjongFFIError::TimedOut(timeout))?
+    }
+
+    /// Consume the game as a stream of observed states
+    pub fn into_stream(self) -> GameStream {
+        GameStream::new(self)
+    }
+}
+
+struct StreamState {
+    game: AsyncGameState,
+    started: bool,
+    steps: usize,
+    step_limit: usize,
+    step_timeout: Option<Duration>,
+}
+
+type StepOutput = (
+    StreamState,
+    Option<Result<ObservedGameState, MahjongFFIError>>,
+);
+
+async fn step(mut state: StreamState) -> StepOutput {
+    if state.started {
+        if state.steps >= state.step_limit {
+            let limit = state.step_limit;
+            return (state, Some(Err(MahjongFFIError::StepLimitExceeded(limit))));
+        }
+
+        let advanced = match state.step_timeout {
+            Some(timeout) => state.game.advance_timeout(timeout).await,
+            None => state.game.advance().await,
+        };
+        match advanced {
+            Ok(()) => state.steps += 1,
+            Err(MahjongFFIError::GameEnded | MahjongFFIError::Cancelled) => return (state, None),
+            Err(error) => return (state, Some(Err(error))),
+        }
+    }
+    state.started = true;
+
+    let observed = state.game.observe();
+    (state, Some(observed))
+}
+
+/// Stream of observed states, starting with the current one. Ends after the
+/// `GameEnd` state, when the game ends or is cancelled, or after yielding an
+/// error.
+pub struct GameStream {
+    state: Option<StreamState>,
+    future: Option<Pin<Box<dyn Future<Output = StepOutput> + Send>>>,
+    cancel: CancelHandle,
+    finished: bool,
+}
+
+impl GameStream {
+    fn new(game: AsyncGameState) -> Self {
+        Self {
+            cancel: game.cancel_handle(),
+            state: Some(StreamState {
+                game,
+                started: false,
+                steps: 0,
+                step_limit: DEFAULT_STEP_LIMIT,
+                step_timeout: None,
+            }),
+            future: None,
+            finished: false,
+        }
+    }
+
+    /// Set the maximum number of advances before the stream gives up
+    pub fn with_step_limit(mut self, step_limit: usize) -> Self {
+        if let Some(state) = &mut self.state {
+            state.step_limit = step_limit;
+        }
+        self
+    }
+
+    /// Fail with `TimedOut` if a single advance takes longer than `timeout`
+    pub fn with_step_timeout(mut self, timeout: Duration) -> Self {
+        if let Some(state) = &mut self.state {
+            state.step_timeout = Some(timeout);
+        }
+        self
+    }
+
+    /// Get a handle that ends the stream
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+}
+
+impl Stream for GameStream {
+    type Item = Result<ObservedGameState, MahjongFFIError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.finished {
+            return Poll::Ready(None);
+        }
+
+        if self.future.is_none() {
+            let Some(state) = self.state.take() else {
+                return Poll::Ready(None);
+            };
+            self.future = Some(Box::pin(step(state)));
+        }
+
+        let Some(future) = self.future.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let (state, item) = match future.as_mut().poll(cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.future = None;
+        self.state = Some(state);
+
+        self.finished = match &item {
+            Some(Ok(observed)) => observed.curr_state == StateFunctionType::GameEnd,
+            _ => true,
+        };
+        Poll::Ready(item)
+    }
+}
+
+/// Future returned by [`AsyncController::decide`]
+pub type DecisionFuture<'a> = Pin<Box<dyn Future<Output = Decision> + Send + 'a>>;
+
+/// A seat controller that can await while deciding
+pub trait AsyncController: Send + 'static {
+    /// Called once when the game starts with the controller's seat
+    fn game_start(&mut self, _seat: usize) {}
+
+    /// Called at the start of every round with the starting hand
+    fn round_start(
+        &mut self,
+        _hand: &[Piece],
+        _seat_wind: Honor,
+        _round_wind: Honor,
+        _view: &PlayerView,
+    ) {
+    }
+
+    /// Called for every event the controller can see
+    fn receive_event(&mut self, _event: &Event, _view: &PlayerView) {}
+
+    /// Decide between the offered calls and discards
+    fn decide<'a>(
+        &'a mut self,
+        calls: &'a [CallOption],
+        discards: &'a [Piece],
+        view: &'a PlayerView,
+    ) -> DecisionFuture<'a>;
+}
+
+enum Request {
+    GameStart(usize),
+    RoundStart(Vec<Piece>, Honor, Honor, Box<PlayerView>),
+    Event(Event, Box<PlayerView>),
+    Decide(
+        Vec<CallOption>,
+        Vec<Piece>,
+        Box<PlayerView>,
+        oneshot::Sender<Decision>,
+    ),
+}
+
+/// Runs an [`AsyncController`] on a tokio runtime and exposes it as a
+/// blocking [`Controller`].
+///
+/// Decisions block the thread advancing the game until the async controller
+/// answers, so games using it must be advanced off the runtime's worker
+/// threads, e.g. through [`AsyncGameState`].
+pub struct AsyncControllerBridge {
+    requests: mpsc::UnboundedSender<Request>,
+}
+
+impl AsyncControllerBridge {
+    /// Spawn the controller's task on `runtime`
+    pub fn spawn(controller: impl AsyncController, runtime: &Handle) -> Self {
+        Self::spawn_boxed(Box::new(controller), runtime)
+    }
+
+    fn spawn_boxed(mut controller: Box<dyn AsyncController>, runtime: &Handle) -> Self {
+        let (requests, mut receiver) = mpsc::unbounded_channel();
+        runtime.spawn(async move {
+            while let Some(request) = receiver.recv().await {
+                match request {
+                    Request::GameStart(seat) => controller.game_start(seat),
+                    Request::RoundStart(hand, seat_wind, round_wind, view) => {
+                        controller.round_start(&hand, seat_wind, round_wind, &view)
+                    }
+                    Request::Event(event, view) => controller.receive_event(&event, &view),
+                    Request::Decide(calls, discards, view, reply) => {
+                        let _ = reply.send(controller.decide(&calls, &discards, &view).await);
+                    }
+                }
+            }
+        });
+        Self { requests }
+    }
+}
+
+impl Controller for AsyncControllerBridge {
+    fn game_start(&mut self, seat: usize) {
+        let _ = self.requests.send(Request::GameStart(seat));
+    }
+
+    fn round_start(
+        &mut self,
+        hand: &[Piece],
+        seat_wind: Honor,
+        round_wind: Honor,
+        view: &PlayerView,
+    ) {
+        let _ = self.requests.send(Request::RoundStart(
+            hand.to_vec(),
+            seat_wind,
+            round_wind,
+            Box::new(view.clone()),
+        ));
+    }
+
+    fn receive_event(&mut self, event: &Event, view: &PlayerView) {
+        let _ = self
+            .requests
+            .send(Request::Event(*event, Box::new(view.clone())));
+    }
+
+    fn choose_discard(&mut self, options: &[Piece], view: &PlayerView) -> Piece {
+        match self.decide(&[], options, view) {
+            Decision::Discard(piece) => piece,
+            _ => options[0],
+        }
+    }
+
+    fn decide(&mut self, calls: &[CallOption], discards: &[Piece], view: &PlayerView) -> Decision {
+        let fallback = discards
+            .first()
+            .copied()
+            .map_or(Decision::Decline, Decision::Discard);
+
+        let (reply, answer) = oneshot::channel();
+        let request = Request::Decide(
+            calls.to_vec(),
+            discards.to_vec(),
+            Box::new(view.clone()),
+            reply,
+        );
+        if self.requests.send(request).is_err() {
+            return fallback;
+        }
+        answer.blocking_recv().unwrap_or(fallback)
+    }
+}
+
+/// Register an async controller; every instance runs its task on `runtime`.
+/// Like [`register_controller`], the name can only be seated by
+/// [`Backend::Engine`].
+pub fn register_async_controller<F>(
+    name: &str,
+    runtime: Handle,
+    factory: F,
+) -> Result<(), MahjongFFIError>
+where
+    F: Fn() -> Box<dyn AsyncController> + Send + Sync + 'static,
+{
+    register_controller(name, move || {
+        Box::new(AsyncControllerBridge::spawn_boxed(factory(), &runtime))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::notation::parse_pieces;
+
+    struct SlowLast;
+
+    impl AsyncController for SlowLast {
+        fn decide<'a>(
+            &'a mut self,
+            _calls: &'a [CallOption],
+            discards: &'a [Piece],
+            _view: &'a PlayerView,
+        ) -> DecisionFuture<'a> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                Decision::Discard(discards[discards.len() - 1])
+            })
+        }
+    }
+
+    #[test]
+    fn bridge_awaits_async_decisions() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut bridge = AsyncControllerBridge::spawn(SlowLast, runtime.handle());
+
+        let discards = parse_pieces("1m7z").unwrap();
+        let view = ObservedGameState::default().view_for(0);
+        assert_eq!(
+            bridge.decide(&[], &discards, &view),
+            Decision::Discard(discards[1])
+        );
+        assert_eq!(bridge.choose_discard(&discards, &view), discards[1]);
+
+        drop(runtime);
+        assert_eq!(bridge.choose_discard(&discards, &view), discards[0]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn async_controllers_play_on_the_engine() -> anyhow::Result<()> {
+        register_async_controller("SlowLastTest", Handle::current(), || Box::new(SlowLast))?;
+        let settings = GameSettings::builder()
+            .seed(7)
+            .all_seats("TotoBot")
+            .seat(2, "SlowLastTest")
+            .build()?;
+        assert!(matches!(
+            AsyncGameState::from_settings(settings.clone()).await,
+            Err(MahjongFFIError::RustControllerOnNative(_))
+        ));
+
+        let game = AsyncGameState::with_backend(settings, Backend::Engine).await?;
+        let mut stream = game.into_stream().with_step_timeout(Duration::from_secs(5));
+        let mut discarded = false;
+        while let Some(observed) = stream.next().await {
+            let observed = observed?;
+            discarded |=
+                observed.curr_state == StateFunctionType::Discard && observed.current_player == 2;
+            if discarded {
+                break;
+            }
+        }
+        assert!(discarded);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn can_stream_and_cancel_game() -> anyhow::Result<()> {
+        let settings = GameSettings::builder()
+            .seed(12345)
+            .all_seats("AlphabeticalBot")
+            .build()?;
+        let game = AsyncGameState::from_settings(settings).await?;
+        let mut stream = game.into_stream().with_step_timeout(Duration::from_secs(5));
+        let cancel = stream.cancel_handle();
+
+        let mut seen = 0;
+        while let Some(observed) = stream.next().await {
+            observed?;
+            seen += 1;
+            if seen == 3 {
+                cancel.cancel();
+            }
+        }
+        assert_eq!(seen, 3);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+//! Command-line front end for running and inspecting libmahjong games.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use libmahjong_rs::controller::available_controllers;
+use libmahjong_rs::observe::ObservedGameState;
+use libmahjong_rs::rules::RuleSet;
+use libmahjong_rs::runner::{GameRunner, DEFAULT_STEP_LIMIT};
+use libmahjong_rs::settings::GameSettings;
+use libmahjong_rs::simulate::Simulation;
+use libmahjong_rs::snapshot;
+use libmahjong_rs::state_machine;
+
+#[derive(Parser)]
+#[command(name = "libmahjong", about = "Run and inspect libmahjong games")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Play a single game and print the final scores
+    Run {
+        #[command(flatten)]
+        game: GameArgs,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Print every state the game passes through
+        #[arg(long)]
+        trace: bool,
+    },
+    /// Play one game per seed across a thread pool
+    Simulate {
+        #[command(flatten)]
+        game: GameArgs,
+        /// Seeds to play, as START..END
+        #[arg(long, value_parser = parse_seed_range)]
+        seeds: Range<u64>,
+        /// Worker threads, defaults to the available parallelism
+        #[arg(long)]
+        threads: Option<usize>,
+        /// Print one JSON result per line instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Play a single game and write every observed state as JSON
+    Dump {
+        #[command(flatten)]
+        game: GameArgs,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// File to write to, standard output if omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// List the controllers that can be seated
+    Controllers,
+    /// Print the legal state transitions as a graph
+    States {
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Args)]
+struct GameArgs {
+    /// One controller for every seat, or four comma-separated controllers
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        num_args = 1..=4,
+        default_value = "AlphabeticalBot"
+    )]
+    controllers: Vec<String>,
+    /// Rule preset: WRC, EMA or "Tenhou ranked"
+    #[arg(long)]
+    rules: Option<String>,
+    /// Play the East round only
+    #[arg(long)]
+    tonpuusen: bool,
+    /// Give up on a game after this many advances
+    #[arg(long, default_value_t = DEFAULT_STEP_LIMIT)]
+    step_limit: usize,
+}
+
+impl GameArgs {
+    fn settings(&self, seed: u64) -> Result<GameSettings, Box<dyn Error>> {
+        let mut rules = match &self.rules {
+            Some(name) => RuleSet::preset(name)?,
+            None => RuleSet::default(),
+        };
+        if self.tonpuusen {
+            rules = rules.tonpuusen();
+        }
+
+        let builder = match self.controllers.as_slice() {
+            [controller] => GameSettings::builder().all_seats(controller),
+            [_, _, _, _] => self
+                .controllers
+                .iter()
+                .enumerate()
+                .fold(GameSettings::builder(), |builder, (seat, controller)| {
+                    builder.seat(seat, controller)
+                }),
+            controllers => {
+                return Err(
+                    format!("Expected 1 or 4 controllers, got {}", controllers.len()).into(),
+                )
+            }
+        };
+
+        Ok(builder.seed(seed).rules(rules).build()?)
+    }
+}
+
+#[derive(serde::Serialize)]
+struct GameLog {
+    settings: GameSettings,
+    states: Vec<ObservedGameState>,
+}
+
+fn parse_seed_range(value: &str) -> Result<Range<u64>, String> {
+    let (start, end) = value
+        .split_once("..")
+        .ok_or_else(|| format!("Expected START..END, got '{value}'"))?;
+    let parse = |bound: &str| {
+        bound
+            .trim()
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid seed '{bound}': {e}"))
+    };
+    let range = parse(start)?..parse(end)?;
+    if range.is_empty() {
+        return Err(format!("Seed range '{value}' is empty"));
+    }
+    Ok(range)
+}
+
+fn print_scores(state: &ObservedGameState, controllers: &[String; 4]) {
+    let placements = libmahjong_rs::runner::placements(&state.points);
+    for (seat, controller) in controllers.iter().enumerate() {
+        println!(
+            "Seat {seat}  {:>6}  #{}  {controller}",
+            state.points[seat],
+            placements[seat] + 1
+        );
+    }
+}
+
+fn run(game: &GameArgs, seed: u64, trace: bool) -> Result<(), Box<dyn Error>> {
+    let settings = game.settings(seed)?;
+    let controllers = settings.seat_controllers.clone();
+    let mut runner = GameRunner::from_settings(settings)?.with_step_limit(game.step_limit);
+
+    for (step, state) in runner.by_ref().enumerate() {
+        if trace {
+            println!(
+                "{step:>6}  round {:>2}  turn {:>3}  player {:>2}  {:?}",
+                state.round_num, state.turn_num, state.current_player, state.curr_state
+            );
+        }
+    }
+
+    let summary = runner.summary()?;
+    println!(
+        "Seed {seed}: {} rounds in {} steps{}",
+        summary.rounds,
+        summary.steps,
+        if summary.reached_game_end {
+            ""
+        } else {
+            " (no GameEnd state)"
+        }
+    );
+    for transition in &summary.illegal_transitions {
+        eprintln!("warning: illegal transition at {transition}");
+    }
+    print_scores(&summary.final_state, &controllers);
+    Ok(())
+}
+
+fn simulate(
+    game: &GameArgs,
+    seeds: Range<u64>,
+    threads: Option<usize>,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let settings = game.settings(seeds.start)?;
+    let mut simulation = Simulation::from_settings(seeds, &settings)
+        .with_step_limit(game.step_limit)
+        .on_progress(|progress| {
+            eprint!(
+                "\r{}/{} games, {} failed",
+                progress.completed, progress.total, progress.failed
+            );
+        });
+    if let Some(threads) = threads {
+        simulation = simulation.with_threads(threads);
+    }
+
+    let results = simulation.run();
+    eprintln!();
+
+    let mut stdout = io::stdout().lock();
+    for result in results {
+        match result {
+            Ok(result) if json => writeln!(stdout, "{}", serde_json::to_string(&result)?)?,
+            Ok(result) => writeln!(
+                stdout,
+                "{:>10}  {:?}  {:?}  {:>2} rounds  {:>2} wins",
+                result.seed,
+                result.points,
+                result.placements.map(|place| place + 1),
+                result.rounds,
+                result.wins.len()
+            )?,
+            Err(error) => eprintln!("{error}"),
+        }
+    }
+    Ok(())
+}
+
+fn dump(game: &GameArgs, seed: u64, output: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let settings = game.settings(seed)?;
+    let mut runner = GameRunner::from_settings(settings.clone())?.with_step_limit(game.step_limit);
+    let states: Vec<_> = runner.by_ref().collect();
+    if let Some(error) = runner.error() {
+        return Err(error.to_string().into());
+    }
+
+    let json = snapshot::to_json(&GameLog { settings, states })?;
+    match output {
+        Some(path) => File::create(path)?.write_all(json.as_bytes())?,
+        None => println!("{json}"),
+    }
+    Ok(())
+}
+
+fn list_controllers() {
+    for controller in available_controllers() {
+        println!(
+            "{:<20} {:?}  {}",
+            controller.name, controller.source, controller.description
+        );
+    }
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Run { game, seed, trace } => run(&game, seed, trace),
+        Command::Simulate {
+            game,
+            seeds,
+            threads,
+            json,
+        } => simulate(&game, seeds, threads, json),
+        Command::Dump { game, seed, output } => dump(&game, seed, output),
+        Command::Controllers => {
+            list_controllers();
+            Ok(())
+        }
+        Command::States { format } => {
+            match format {
+                GraphFormat::Dot => print!("{}", state_machine::to_dot()),
+                GraphFormat::Mermaid => print!("{}", state_machine::to_mermaid()),
+            }
+            Ok(())
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_seed_ranges() {
+        assert_eq!(parse_seed_range("10..20"), Ok(10..20));
+        assert!(parse_seed_range("20..10").is_err());
+        assert!(parse_seed_range("10").is_err());
+        assert!(parse_seed_range("a..b").is_err());
+    }
+
+    #[test]
+    fn cli_definition_is_valid() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+    }
+}
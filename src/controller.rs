@@ -0,0 +1,579 @@
+//! Rust seat controllers.
+//!
+//! Implement [`Controller`] and register a factory with
+//! [`register_controller`]; the registered name can then be used in
+//! `GameSettings::seat_controllers` alongside the bots. libmahjong has no
+//! entry point for foreign controllers, so games seating a Rust controller
+//! must be played by [`Backend::Engine`](crate::ffi::gamestate::Backend::Engine).
+//! Every controller call goes through [`ControllerAdapter`], which catches
+//! panics, so a misbehaving controller falls back to declining calls and
+//! discarding its first legal option instead of taking the game down.
+
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::ffi::error::MahjongFFIError;
+use crate::ffi::gamestate::GameState;
+use crate::piece::{Honor, Piece};
+use crate::settings::GameSettings;
+use crate::view::PlayerView;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Ron,
+    Kan,
+    Pon,
+    Chi,
+    Tsumo,
+    ConcealedKan,
+    ConvertedKan,
+    Riichi,
+    Discard,
+    Decline,
+    Dora,
+    PointDiff,
+    ExhaustiveDraw,
+    End,
+}
+
+/// An event sent to a controller. When `decision` is set the event is an
+/// option the controller may take rather than something that happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub player: i32,
+    /// The piece involved, or the point difference for `PointDiff` events
+    pub value: i32,
+    pub decision: bool,
+}
+
+impl Event {
+    /// Get the piece involved in the event, if `value` holds one
+    pub fn piece(&self) -> Option<Piece> {
+        Piece::try_from(self.value).ok()
+    }
+}
+
+/// A call a controller may accept when it is offered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Call {
+    Ron,
+    Tsumo,
+    Pon,
+    Chi,
+    Kan,
+    ConcealedKan,
+    ConvertedKan,
+    Riichi,
+}
+
+impl Call {
+    fn from_kind(kind: EventKind) -> Option<Self> {
+        match kind {
+            EventKind::Ron => Some(Call::Ron),
+            EventKind::Tsumo => Some(Call::Tsumo),
+            EventKind::Pon => Some(Call::Pon),
+            EventKind::Chi => Some(Call::Chi),
+            EventKind::Kan => Some(Call::Kan),
+            EventKind::ConcealedKan => Some(Call::ConcealedKan),
+            EventKind::ConvertedKan => Some(Call::ConvertedKan),
+            EventKind::Riichi => Some(Call::Riichi),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn kind(self) -> EventKind {
+        match self {
+            Call::Ron => EventKind::Ron,
+            Call::Tsumo => EventKind::Tsumo,
+            Call::Pon => EventKind::Pon,
+            Call::Chi => EventKind::Chi,
+            Call::Kan => EventKind::Kan,
+            Call::ConcealedKan => EventKind::ConcealedKan,
+            Call::ConvertedKan => EventKind::ConvertedKan,
+            Call::Riichi => EventKind::Riichi,
+        }
+    }
+}
+
+/// An offered call together with the piece it concerns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallOption {
+    pub call: Call,
+    pub piece: i32,
+}
+
+/// The answer a controller gives when a decision is requested
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Discard(Piece),
+    Call(CallOption),
+    Decline,
+}
+
+/// A seat controller implemented in Rust.
+///
+/// Every hook after `game_start` receives the [`PlayerView`] of the
+/// controller's seat, so a controller sees what a player at the table would
+/// and nothing more.
+pub trait Controller: Send {
+    /// Called once when the game starts with the controller's seat
+    fn game_start(&mut self, _seat: usize) {}
+
+    /// Called at the start of every round with the dealt hand
+    fn round_start(
+        &mut self,
+        _hand: &[Piece],
+        _seat_wind: Honor,
+        _round_wind: Honor,
+        _view: &PlayerView,
+    ) {
+    }
+
+    /// Called for every non-decision event
+    fn receive_event(&mut self, _event: &Event, _view: &PlayerView) {}
+
+    /// Pick one of the offered calls, or `None` to decline all of them
+    fn decide_call(&mut self, _options: &[CallOption], _view: &PlayerView) -> Option<CallOption> {
+        None
+    }
+
+    /// Pick a piece to discard from the legal options
+    fn choose_discard(&mut self, options: &[Piece], view: &PlayerView) -> Piece;
+
+    /// Answer a decision request with every option offered since the last
+    /// one. The default asks [`Controller::decide_call`] first and falls back
+    /// to [`Controller::choose_discard`] when a discard is required.
+    fn decide(&mut self, calls: &[CallOption], discards: &[Piece], view: &PlayerView) -> Decision {
+        if !calls.is_empty() {
+            if let Some(call) = self.decide_call(calls, view) {
+                return Decision::Call(call);
+            }
+        }
+        if discards.is_empty() {
+            Decision::Decline
+        } else {
+            Decision::Discard(self.choose_discard(discards, view))
+        }
+    }
+}
+
+/// Declines every call and discards the first legal option. Used in place
+/// of a controller that panicked.
+#[derive(Debug, Default)]
+pub struct FallbackController;
+
+impl Controller for FallbackController {
+    fn choose_discard(&mut self, options: &[Piece], _view: &PlayerView) -> Piece {
+        options[0]
+    }
+}
+
+/// Creates controller instances for a registered name
+pub type ControllerFactory = dyn Fn() -> Box<dyn Controller> + Send + Sync;
+
+/// Tracks the decision options offered since the last decision and turns
+/// them into trait calls
+pub struct ControllerAdapter {
+    controller: Box<dyn Controller>,
+    calls: Vec<CallOption>,
+    discards: Vec<Piece>,
+    panicked: bool,
+}
+
+impl ControllerAdapter {
+    pub fn new(controller: Box<dyn Controller>) -> Self {
+        Self {
+            controller,
+            calls: Vec::new(),
+            discards: Vec::new(),
+            panicked: false,
+        }
+    }
+
+    /// Check if the wrapped controller has panicked and been replaced
+    pub fn panicked(&self) -> bool {
+        self.panicked
+    }
+
+    fn guard<T>(&mut self, f: impl FnOnce(&mut dyn Controller) -> T) -> Option<T> {
+        let result = catch_unwind(AssertUnwindSafe(|| f(self.controller.as_mut())));
+        if result.is_err() {
+            self.panicked = true;
+            self.controller = Box::new(FallbackController);
+        }
+        result.ok()
+    }
+
+    pub fn game_start(&mut self, seat: usize) {
+        self.guard(|c| c.game_start(seat));
+    }
+
+    pub fn round_start(
+        &mut self,
+        hand: &[Piece],
+        seat_wind: Honor,
+        round_wind: Honor,
+        view: &PlayerView,
+    ) {
+        self.calls.clear();
+        self.discards.clear();
+        self.guard(|c| c.round_start(hand, seat_wind, round_wind, view));
+    }
+
+    pub fn receive_event(&mut self, event: Event, view: &PlayerView) {
+        if !event.decision {
+            self.guard(|c| c.receive_event(&event, view));
+            return;
+        }
+
+        match (event.kind, Call::from_kind(event.kind)) {
+            (EventKind::Discard, _) => self.discards.extend(event.piece()),
+            (_, Some(call)) => self.calls.push(CallOption {
+                call,
+                piece: event.value,
+            }),
+            _ => {}
+        }
+    }
+
+    pub fn retrieve_decision(&mut self, view: &PlayerView) -> Decision {
+        let calls = std::mem::take(&mut self.calls);
+        let discards = std::mem::take(&mut self.discards);
+
+        // Only accept options that were actually offered
+        let chosen = self.guard(|c| c.decide(&calls, &discards, view));
+        match chosen {
+            Some(Decision::Call(call)) if calls.contains(&call) => Decision::Call(call),
+            Some(Decision::Discard(piece)) if discards.contains(&piece) => Decision::Discard(piece),
+            _ => match discards.first() {
+                Some(&piece) => Decision::Discard(piece),
+                None => Decision::Decline,
+            },
+        }
+    }
+}
+
+struct Registration {
+    factory: Arc<ControllerFactory>,
+    description: String,
+}
+
+fn registry() -> &'static Mutex<HashMap<String, Registration>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, Registration>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Register a Rust controller under `name`. The name can only be seated by
+/// the engine backend; other backends fail with
+/// [`MahjongFFIError::RustControllerOnNative`].
+pub fn register_controller<F>(name: &str, factory: F) -> Result<(), MahjongFFIError>
+where
+    F: Fn() -> Box<dyn Controller> + Send + Sync + 'static,
+{
+    register_controller_with_description(name, "", factory)
+}
+
+/// Register a Rust controller with a description shown by
+/// [`available_controllers`]
+pub fn register_controller_with_description<F>(
+    name: &str,
+    description: &str,
+    factory: F,
+) -> Result<(), MahjongFFIError>
+where
+    F: Fn() -> Box<dyn Controller> + Send + Sync + 'static,
+{
+    let mut registry = registry()
+        .lock()
+        .map_err(|_| MahjongFFIError::MutexPoisoned)?;
+    registry.insert(
+        name.to_string(),
+        Registration {
+            factory: Arc::new(factory),
+            description: description.to_string(),
+        },
+    );
+    Ok(())
+}
+
+/// Check if `name` is a registered Rust controller
+pub fn is_registered(name: &str) -> bool {
+    registry()
+        .lock()
+        .map(|registry| registry.contains_key(name))
+        .unwrap_or(false)
+}
+
+/// Get the names of all registered Rust controllers
+pub fn registered_controllers() -> Vec<String> {
+    registry()
+        .lock()
+        .map(|registry| registry.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Create an instance of a registered Rust controller
+pub fn create_controller(name: &str) -> Option<Box<dyn Controller>> {
+    let factory = registry()
+        .lock()
+        .ok()?
+        .get(name)
+        .map(|registration| Arc::clone(&registration.factory))?;
+    catch_unwind(AssertUnwindSafe(|| factory())).ok()
+}
+
+/// Bots shipped with libmahjong. The native library has no registry query,
+/// so each name is probed before it is reported as available.
+pub const NATIVE_CONTROLLERS: [(&str, &str); 6] = [
+    (
+        "AngryDiscardoBot",
+        "Declines every call and discards the first legal piece",
+    ),
+    (
+        "AlphabeticalBot",
+        "Discards pieces in a fixed order and never calls",
+    ),
+    ("FastTanyao", "Calls aggressively towards an open tanyao"),
+    (
+        "GentlemanBot",
+        "Plays a closed hand and avoids dealing in to riichi",
+    ),
+    (
+        "ThriceBot",
+        "Builds triplets, calling pon whenever possible",
+    ),
+    ("TotoBot", "Discards towards the lowest shanten"),
+];
+
+/// Where a controller is implemented
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerSource {
+    Native,
+    Rust,
+}
+
+/// A controller name accepted by `InitGameState`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerInfo {
+    pub name: String,
+    pub description: String,
+    pub source: ControllerSource,
+}
+
+/// Check whether the native library can start a game with `name` in every seat
+fn native_accepts(name: &str) -> bool {
+    let settings = GameSettings {
+        seed: 0,
+        seat_controllers: std::array::from_fn(|_| name.to_string()),
+        rules: Default::default(),
+    };
+    GameState::new(settings).is_ok()
+}
+
+fn collect_controllers(
+    accepts: impl Fn(&str) -> bool,
+    registered: Vec<(String, String)>,
+) -> Vec<ControllerInfo> {
+    let native = NATIVE_CONTROLLERS
+        .iter()
+        .filter(|(name, _)| accepts(name))
+        .map(|&(name, description)| ControllerInfo {
+            name: name.to_string(),
+            description: description.to_string(),
+            source: ControllerSource::Native,
+        });
+
+    let mut rust: Vec<_> = registered
+        .into_iter()
+        .map(|(name, description)| ControllerInfo {
+            name,
+            description,
+            source: ControllerSource::Rust,
+        })
+        .collect();
+    rust.sort_by(|a, b| a.name.cmp(&b.name));
+
+    native.chain(rust).collect()
+}
+
+/// List every controller name `InitGameState` accepts: the native bots that
+/// the linked library provides, followed by registered Rust controllers.
+/// Native bots are probed once per process.
+pub fn available_controllers() -> Vec<ControllerInfo> {
+    static NATIVE: OnceLock<Vec<String>> = OnceLock::new();
+    let native = NATIVE.get_or_init(|| {
+        NATIVE_CONTROLLERS
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .filter(|name| native_accepts(name))
+            .collect()
+    });
+
+    let registered = registry()
+        .lock()
+        .map(|registry| {
+            registry
+                .iter()
+                .map(|(name, registration)| (name.clone(), registration.description.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    collect_controllers(|name| native.iter().any(|n| n == name), registered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observe::ObservedGameState;
+
+    struct Greedy;
+
+    impl Controller for Greedy {
+        fn decide_call(
+            &mut self,
+            options: &[CallOption],
+            _view: &PlayerView,
+        ) -> Option<CallOption> {
+            options.first().copied()
+        }
+
+        fn choose_discard(&mut self, options: &[Piece], _view: &PlayerView) -> Piece {
+            *options.last().unwrap()
+        }
+    }
+
+    struct Panicking;
+
+    impl Controller for Panicking {
+        fn choose_discard(&mut self, _options: &[Piece], _view: &PlayerView) -> Piece {
+            panic!("controller bug")
+        }
+    }
+
+    /// Records what it saw of the table on every discard
+    struct Watcher(Arc<Mutex<Vec<(usize, bool, bool)>>>);
+
+    impl Controller for Watcher {
+        fn choose_discard(&mut self, options: &[Piece], view: &PlayerView) -> Piece {
+            let holds_options = options
+                .iter()
+                .all(|piece| view.hand.live_pieces.contains(piece));
+            let mut seen = self.0.lock().unwrap();
+            seen.push((view.seat, view.is_own_turn(), holds_options));
+            options[0]
+        }
+    }
+
+    fn offer(kind: EventKind, piece: Piece) -> Event {
+        Event {
+            kind,
+            player: 0,
+            value: piece.into(),
+            decision: true,
+        }
+    }
+
+    fn pieces() -> [Piece; 2] {
+        ["1m".parse().unwrap(), "7z".parse().unwrap()]
+    }
+
+    #[test]
+    fn adapter_routes_decisions() {
+        let mut adapter = ControllerAdapter::new(Box::new(Greedy));
+        let [one, red] = pieces();
+        let view = ObservedGameState::default().view_for(0);
+
+        adapter.game_start(2);
+        adapter.receive_event(offer(EventKind::Discard, one), &view);
+        adapter.receive_event(offer(EventKind::Discard, red), &view);
+        assert_eq!(adapter.retrieve_decision(&view), Decision::Discard(red));
+
+        adapter.receive_event(offer(EventKind::Pon, one), &view);
+        assert_eq!(
+            adapter.retrieve_decision(&view),
+            Decision::Call(CallOption {
+                call: Call::Pon,
+                piece: one.into(),
+            })
+        );
+
+        assert_eq!(adapter.retrieve_decision(&view), Decision::Decline);
+        assert!(!adapter.panicked());
+    }
+
+    #[test]
+    fn contains_controller_panics() {
+        let mut adapter = ControllerAdapter::new(Box::new(Panicking));
+        let [one, red] = pieces();
+        let view = ObservedGameState::default().view_for(0);
+
+        adapter.receive_event(offer(EventKind::Discard, red), &view);
+        adapter.receive_event(offer(EventKind::Discard, one), &view);
+        assert_eq!(adapter.retrieve_decision(&view), Decision::Discard(red));
+        assert!(adapter.panicked());
+    }
+
+    #[test]
+    fn controllers_see_their_seat() {
+        use crate::ffi::gamestate::Backend;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let shared = Arc::clone(&seen);
+        register_controller("WatcherTest", move || {
+            Box::new(Watcher(Arc::clone(&shared)))
+        })
+        .unwrap();
+        let settings = GameSettings::builder()
+            .seed(3)
+            .all_seats("TotoBot")
+            .seat(1, "WatcherTest")
+            .build()
+            .unwrap();
+
+        GameState::with_backend(settings, Backend::Engine)
+            .unwrap()
+            .into_iter()
+            .take(100)
+            .for_each(drop);
+
+        let seen = seen.lock().unwrap();
+        assert!(!seen.is_empty());
+        assert!(seen.iter().all(|&seen| seen == (1, true, true)));
+    }
+
+    #[test]
+    fn rust_controllers_are_seated_by_the_engine() {
+        use crate::ffi::gamestate::Backend;
+
+        register_controller("GreedyEngineTest", || Box::new(Greedy)).unwrap();
+        assert!(is_registered("GreedyEngineTest"));
+        let settings = GameSettings {
+            seed: 0,
+            seat_controllers: std::array::from_fn(|_| "GreedyEngineTest".to_string()),
+            rules: Default::default(),
+        };
+        assert!(GameState::with_backend(settings.clone(), Backend::Engine).is_ok());
+        assert!(matches!(
+            GameState::new(settings),
+            Err(MahjongFFIError::RustControllerOnNative(name)) if name == "GreedyEngineTest"
+        ));
+    }
+
+    #[test]
+    fn lists_probed_native_and_registered_controllers() {
+        let registered = vec![
+            ("Zeta".to_string(), "".to_string()),
+            ("Alpha".to_string(), "Greedy".to_string()),
+        ];
+        let controllers = collect_controllers(|name| name == "TotoBot", registered);
+
+        let names: Vec<_> = controllers.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["TotoBot", "Alpha", "Zeta"]);
+        assert_eq!(controllers[0].source, ControllerSource::Native);
+        assert_eq!(controllers[1].description, "Greedy");
+        assert_eq!(controllers[1].source, ControllerSource::Rust);
+    }
+}
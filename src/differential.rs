@@ -0,0 +1,427 @@
+//! Play the same game through two backends in lockstep and report where
+//! they disagree.
+//!
+//! [`Differential`] runs one [`GameSettings`] seed on two
+//! [`Backend`]s and compares every [`ObservedGameState`] field by field. To
+//! compare two builds of the library, or a recorded game, pass any two
+//! streams of states to [`compare`].
+//!
+//! Only games played by the same rules engine can agree: a backend matches
+//! itself, but libmahjong and the pure-Rust engine shuffle their walls
+//! differently and diverge at the first deal.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::ffi::error::MahjongFFIError;
+use crate::ffi::gamestate::{Backend, GameState};
+use crate::notation::{format_meld, format_pieces};
+use crate::observe::{Hand, ObservedGameState};
+use crate::runner::{GameRunner, DEFAULT_STEP_LIMIT};
+use crate::settings::GameSettings;
+
+/// Number of matching states kept before a divergence
+pub const HISTORY_LEN: usize = 20;
+
+/// One of the two games being compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Left => write!(f, "left"),
+            Self::Right => write!(f, "right"),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DifferentialError {
+    #[error("Failed to create the {side} game: {source}")]
+    Create {
+        side: Side,
+        #[source]
+        source: MahjongFFIError,
+    },
+    #[error("The {side} game failed after {step} steps: {source}")]
+    Backend {
+        side: Side,
+        step: usize,
+        #[source]
+        source: MahjongFFIError,
+    },
+}
+
+/// A field that differs between the two states
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    /// Path of the field, e.g. `hands[2].discards`
+    pub field: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} != {}", self.field, self.left, self.right)
+    }
+}
+
+/// The first point where the two games disagree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the diverging state, 0 being the initial state
+    pub step: usize,
+    pub differences: Vec<FieldDiff>,
+    /// The diverging left state, or `None` if the left game had ended
+    pub left: Option<ObservedGameState>,
+    /// The diverging right state, or `None` if the right game had ended
+    pub right: Option<ObservedGameState>,
+    /// Up to [`HISTORY_LEN`] matching states before the divergence, oldest
+    /// first
+    pub history: Vec<ObservedGameState>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Games diverged at step {}:", self.step)?;
+        for difference in &self.differences {
+            writeln!(f, "  {difference}")?;
+        }
+        writeln!(f, "Preceding states:")?;
+        let first = self.step - self.history.len();
+        for (i, state) in self.history.iter().enumerate() {
+            writeln!(
+                f,
+                "  {:>6}  round {:>2}  turn {:>3}  player {:>2}  {:?}",
+                first + i,
+                state.round_num,
+                state.turn_num,
+                state.current_player,
+                state.curr_state
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of a lockstep comparison
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    /// Number of state pairs that matched
+    pub matched: usize,
+    pub divergence: Option<Divergence>,
+}
+
+impl Comparison {
+    /// Check if both games produced the same states
+    pub fn is_consistent(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Play one game on two backends and compare them state by state
+pub struct Differential {
+    settings: GameSettings,
+    left: Backend,
+    right: Backend,
+    step_limit: usize,
+}
+
+impl Differential {
+    /// Compare the games played by the `left` and `right` backends
+    pub fn new(settings: GameSettings, left: Backend, right: Backend) -> Self {
+        Self {
+            settings,
+            left,
+            right,
+            step_limit: DEFAULT_STEP_LIMIT,
+        }
+    }
+
+    /// Set the maximum number of advances per game
+    pub fn with_step_limit(mut self, step_limit: usize) -> Self {
+        self.step_limit = step_limit;
+        self
+    }
+
+    fn runner(&self, side: Side) -> Result<GameRunner, DifferentialError> {
+        let backend = match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+        };
+        let state = GameState::with_backend(self.settings.clone(), backend)
+            .map_err(|source| DifferentialError::Create { side, source })?;
+        Ok(GameRunner::new(state).with_step_limit(self.step_limit))
+    }
+
+    /// Play both games until they diverge or end. Fails if either backend
+    /// errors before a divergence is found.
+    pub fn run(&self) -> Result<Comparison, DifferentialError> {
+        let mut left = self.runner(Side::Left)?;
+        let mut right = self.runner(Side::Right)?;
+        let comparison = compare(left.by_ref(), right.by_ref());
+
+        for (side, runner) in [(Side::Left, left), (Side::Right, right)] {
+            if runner.error().is_some() {
+                let step = runner.steps();
+                if let Err(source) = runner.summary() {
+                    return Err(DifferentialError::Backend { side, step, source });
+                }
+            }
+        }
+        Ok(comparison)
+    }
+}
+
+/// Compare two streams of states in lockstep, stopping at the first pair
+/// that differs or when one stream ends before the other
+pub fn compare<L, R>(left: L, right: R) -> Comparison
+where
+    L: IntoIterator<Item = ObservedGameState>,
+    R: IntoIterator<Item = ObservedGameState>,
+{
+    let mut left = left.into_iter();
+    let mut right = right.into_iter();
+    let mut history = VecDeque::with_capacity(HISTORY_LEN);
+    let mut step = 0;
+
+    loop {
+        let (l, r) = (left.next(), right.next());
+        let differences = match (&l, &r) {
+            (None, None) => {
+                return Comparison {
+                    matched: step,
+                    divergence: None,
+                }
+            }
+            (Some(l), Some(r)) => diff_states(l, r),
+            (l, r) => vec![FieldDiff {
+                field: "curr_state".to_string(),
+                left: ended_or_state(l.as_ref()),
+                right: ended_or_state(r.as_ref()),
+            }],
+        };
+
+        if !differences.is_empty() {
+            return Comparison {
+                matched: step,
+                divergence: Some(Divergence {
+                    step,
+                    differences,
+                    left: l,
+                    right: r,
+                    history: history.into(),
+                }),
+            };
+        }
+
+        if history.len() == HISTORY_LEN {
+            history.pop_front();
+        }
+        history.extend(l);
+        step += 1;
+    }
+}
+
+fn ended_or_state(state: Option<&ObservedGameState>) -> String {
+    match state {
+        Some(state) => format!("{:?}", state.curr_state),
+        None => "<ended>".to_string(),
+    }
+}
+
+/// List every field that differs between two states
+pub fn diff_states(left: &ObservedGameState, right: &ObservedGameState) -> Vec<FieldDiff> {
+    let mut out = Vec::new();
+    let mut field = |name: &str, l: String, r: String| {
+        if l != r {
+            out.push(FieldDiff {
+                field: name.to_string(),
+                left: l,
+                right: r,
+            });
+        }
+    };
+
+    macro_rules! fields {
+        ($($name:ident),*) => {
+            $(field(
+                stringify!($name),
+                format!("{:?}", left.$name),
+                format!("{:?}", right.$name),
+            );)*
+        };
+    }
+    fields!(
+        seed,
+        prev_state,
+        curr_state,
+        next_state,
+        current_player,
+        turn_num,
+        round_num,
+        riichi_sticks,
+        counters,
+        last_call,
+        last_caller,
+        concealed_kan,
+        pending_piece,
+        scores,
+        points,
+        has_ronned
+    );
+
+    for (seat, (l, r)) in left.hands.iter().zip(&right.hands).enumerate() {
+        for (name, l, r) in hand_fields(l)
+            .into_iter()
+            .zip(hand_fields(r))
+            .map(|(l, r)| (l.0, l.1, r.1))
+        {
+            field(&format!("hands[{seat}].{name}"), l, r);
+        }
+    }
+    out
+}
+
+fn hand_fields(hand: &Hand) -> [(&'static str, String); 7] {
+    [
+        ("live_pieces", format_pieces(&hand.live_pieces)),
+        (
+            "melds",
+            hand.melds
+                .iter()
+                .map(format_meld)
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        ("discards", format_pieces(&hand.discards)),
+        ("open", hand.open.to_string()),
+        ("riichi", hand.riichi.to_string()),
+        (
+            "riichi_piece_discard",
+            hand.riichi_piece_discard.to_string(),
+        ),
+        ("riichi_round", hand.riichi_round.to_string()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observe::StateFunctionType;
+    use crate::rules::RuleSet;
+
+    fn engine_states(seed: u64, count: usize) -> Vec<ObservedGameState> {
+        let settings = GameSettings::builder()
+            .seed(seed)
+            .all_seats("TotoBot")
+            .build()
+            .unwrap();
+        GameState::with_backend(settings, Backend::Engine)
+            .unwrap()
+            .into_iter()
+            .take(count)
+            .collect()
+    }
+
+    #[test]
+    fn reports_the_first_divergence_with_history() {
+        let states = engine_states(4, 120);
+        assert!(compare(states.clone(), states.clone()).is_consistent());
+
+        let mut tampered = states.clone();
+        tampered[100].points[2] += 1000;
+        tampered[100].hands[1].discards.clear();
+        let comparison = compare(states.clone(), tampered);
+        let divergence = comparison.divergence.unwrap();
+        assert_eq!(comparison.matched, 100);
+        assert_eq!(divergence.step, 100);
+        assert_eq!(divergence.history.len(), HISTORY_LEN);
+        assert_eq!(divergence.history[HISTORY_LEN - 1], states[99]);
+
+        let fields: Vec<&str> = divergence
+            .differences
+            .iter()
+            .map(|d| d.field.as_str())
+            .collect();
+        assert!(fields.contains(&"points"));
+        if !states[100].hands[1].discards.is_empty() {
+            assert!(fields.contains(&"hands[1].discards"));
+        }
+        assert!(divergence.to_string().contains("diverged at step 100"));
+
+        let short = compare(states.clone(), states[..10].to_vec())
+            .divergence
+            .unwrap();
+        assert_eq!(short.step, 10);
+        assert_eq!(short.right, None);
+        assert_eq!(short.differences[0].right, "<ended>");
+    }
+
+    #[test]
+    fn compares_backends_in_lockstep() {
+        let settings = GameSettings::builder()
+            .seed(2)
+            .all_seats("TotoBot")
+            .build()
+            .unwrap();
+        let error = Differential::new(settings.clone(), Backend::Engine, Backend::Engine)
+            .with_step_limit(50)
+            .run()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            DifferentialError::Backend {
+                side: Side::Left,
+                step: 50,
+                source: MahjongFFIError::StepLimitExceeded(50)
+            }
+        ));
+
+        let left = engine_states(2, 30);
+        let right = engine_states(3, 30);
+        let divergence = compare(left, right).divergence.unwrap();
+        // Both games start identically apart from the seed
+        assert_eq!(divergence.step, 0);
+        assert_eq!(divergence.differences[0].field, "seed");
+        assert_eq!(
+            divergence.left.map(|state| state.curr_state),
+            Some(StateFunctionType::Error)
+        );
+    }
+
+    #[test]
+    fn a_backend_agrees_with_itself() {
+        let settings = GameSettings::builder()
+            .seed(6)
+            .all_seats("TotoBot")
+            .rules(RuleSet::default().tonpuusen())
+            .build()
+            .unwrap();
+        let comparison = Differential::new(settings, Backend::Engine, Backend::Engine)
+            .run()
+            .unwrap();
+        assert!(comparison.is_consistent());
+        assert!(comparison.matched > 2);
+    }
+
+    #[cfg(not(feature = "native"))]
+    #[test]
+    fn the_mock_agrees_with_itself() {
+        let settings = GameSettings::builder()
+            .seed(6)
+            .all_seats("AlphabeticalBot")
+            .build()
+            .unwrap();
+        let comparison = Differential::new(settings, Backend::Native, Backend::Native)
+            .run()
+            .unwrap();
+        assert!(comparison.is_consistent());
+        assert!(comparison.matched > 2);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::analysis::shanten;
+use crate::controller::{Call, CallOption, Decision};
+use crate::observe::Hand;
+use crate::piece::Piece;
+
+/// Rust stand-ins for the bots shipped with libmahjong, so the engine accepts
+/// the same seat names. They follow the described strategy but do not
+/// reproduce the native bots move for move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinBot {
+    AngryDiscardo,
+    Alphabetical,
+    FastTanyao,
+    Gentleman,
+    Thrice,
+    Toto,
+}
+
+impl BuiltinBot {
+    /// Look up a bot by its libmahjong name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "AngryDiscardoBot" => Some(Self::AngryDiscardo),
+            "AlphabeticalBot" => Some(Self::Alphabetical),
+            "FastTanyao" => Some(Self::FastTanyao),
+            "GentlemanBot" => Some(Self::Gentleman),
+            "ThriceBot" => Some(Self::Thrice),
+            "TotoBot" => Some(Self::Toto),
+            _ => None,
+        }
+    }
+
+    /// Pick one of the offered options for `seat`
+    pub fn decide(
+        self,
+        seat: usize,
+        hands: &[Hand; 4],
+        calls: &[CallOption],
+        discards: &[Piece],
+    ) -> Decision {
+        let hand = &hands[seat];
+        let call = match self {
+            Self::AngryDiscardo | Self::Alphabetical => None,
+            Self::Toto | Self::Gentleman => find(calls, &[Call::Tsumo, Call::Ron, Call::Riichi])
+                .map(|option| {
+                    if option.call == Call::Riichi {
+                        best_riichi(hand, calls)
+                    } else {
+                        option
+                    }
+                }),
+            Self::Thrice => find(
+                calls,
+                &[
+                    Call::Tsumo,
+                    Call::Ron,
+                    Call::Kan,
+                    Call::ConvertedKan,
+                    Call::ConcealedKan,
+                    Call::Pon,
+                ],
+            ),
+            Self::FastTanyao => find(calls, &[Call::Tsumo, Call::Ron]).or_else(|| {
+                calls.iter().copied().find(|option| match option.call {
+                    Call::Pon => piece(option).is_some_and(Piece::is_simple),
+                    // Chi options name the lowest piece of the sequence
+                    Call::Chi => piece(option).is_some_and(|start| {
+                        start.is_simple() && start.rank().is_some_and(|rank| rank <= 6)
+                    }),
+                    _ => false,
+                })
+            }),
+        };
+        if let Some(option) = call {
+            return Decision::Call(option);
+        }
+        if discards.is_empty() {
+            return Decision::Decline;
+        }
+
+        let discard = match self {
+            Self::AngryDiscardo => discards[0],
+            Self::Alphabetical => *discards
+                .iter()
+                .min_by_key(|piece| piece.to_string())
+                .expect("discards is not empty"),
+            Self::FastTanyao => discards
+                .iter()
+                .copied()
+                .find(|piece| piece.is_terminal_or_honor())
+                .unwrap_or_else(|| best_discards(hand, discards)[0]),
+            Self::Gentleman => {
+                let safe: Vec<Piece> = discards
+                    .iter()
+                    .copied()
+                    .filter(|piece| is_safe(*piece, seat, hands))
+                    .collect();
+                let riichi_opponent = (0..4).any(|other| other != seat && hands[other].riichi);
+                if riichi_opponent && !safe.is_empty() {
+                    best_discards(hand, &safe)[0]
+                } else {
+                    best_discards(hand, discards)[0]
+                }
+            }
+            Self::Thrice | Self::Toto => best_discards(hand, discards)[0],
+        };
+        Decision::Discard(discard)
+    }
+}
+
+fn piece(option: &CallOption) -> Option<Piece> {
+    Piece::try_from(option.piece).ok()
+}
+
+/// Find the first offered call of the most preferred kind
+fn find(calls: &[CallOption], preference: &[Call]) -> Option<CallOption> {
+    preference
+        .iter()
+        .find_map(|&call| calls.iter().copied().find(|option| option.call == call))
+}
+
+fn shanten_without(hand: &Hand, piece: Piece) -> i8 {
+    let mut hand = hand.clone();
+    if let Some(i) = hand.live_pieces.iter().position(|&p| p == piece) {
+        hand.live_pieces.remove(i);
+    }
+    shanten(&hand).min()
+}
+
+/// Options that leave the lowest shanten, honors and terminals first
+fn best_discards(hand: &Hand, options: &[Piece]) -> Vec<Piece> {
+    let scored: Vec<(i8, Piece)> = options
+        .iter()
+        .map(|&piece| (shanten_without(hand, piece), piece))
+        .collect();
+    let best = scored.iter().map(|(s, _)| *s).min().unwrap_or(0);
+    let mut pieces: Vec<Piece> = scored
+        .into_iter()
+        .filter(|(s, _)| *s == best)
+        .map(|(_, piece)| piece)
+        .collect();
+    pieces.sort_by_key(|piece| (!piece.is_terminal_or_honor(), piece.is_red_five()));
+    pieces
+}
+
+fn best_riichi(hand: &Hand, calls: &[CallOption]) -> CallOption {
+    let options: Vec<CallOption> = calls
+        .iter()
+        .copied()
+        .filter(|option| option.call == Call::Riichi)
+        .collect();
+    let pieces: Vec<Piece> = options.iter().filter_map(piece).collect();
+    let best = best_discards(hand, &pieces);
+    options
+        .iter()
+        .copied()
+        .find(|option| piece(option) == best.first().copied())
+        .unwrap_or(options[0])
+}
+
+/// A piece is safe against every riichi opponent who has already discarded it
+fn is_safe(piece: Piece, seat: usize, hands: &[Hand; 4]) -> bool {
+    (0..4)
+        .filter(|&other| other != seat && hands[other].riichi)
+        .all(|other| {
+            hands[other]
+                .discards
+                .iter()
+                .any(|discard| discard.index() == piece.index())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_hand;
+
+    #[test]
+    fn bots_follow_their_strategy() {
+        let hand = parse_hand("123m456p789s1122z5z").unwrap();
+        let hands = [hand.clone(), hand.clone(), hand.clone(), hand];
+        let options: Vec<Piece> = {
+            let mut options = hands[0].live_pieces.clone();
+            options.dedup();
+            options
+        };
+        let tsumo = CallOption {
+            call: Call::Tsumo,
+            piece: 0,
+        };
+
+        let angry = BuiltinBot::AngryDiscardo.decide(0, &hands, &[tsumo], &options);
+        assert_eq!(angry, Decision::Discard(options[0]));
+
+        // The lone white dragon is the only piece that keeps the hand tenpai
+        let toto = BuiltinBot::Toto.decide(0, &hands, &[], &options);
+        assert_eq!(toto, Decision::Discard("5z".parse().unwrap()));
+        assert_eq!(
+            BuiltinBot::Toto.decide(0, &hands, &[tsumo], &options),
+            Decision::Call(tsumo)
+        );
+
+        assert_eq!(BuiltinBot::from_name("ThriceBot"), Some(BuiltinBot::Thrice));
+        assert_eq!(BuiltinBot::from_name("NoSuchBot"), None);
+    }
+}
@@ -0,0 +1,1198 @@
+//! Pure-Rust Riichi engine.
+//!
+//! [`Engine`] plays a full game with the same [`StateFunctionType`]
+//! transitions and [`ObservedGameState`] output as libmahjong: dealing,
+//! draws, calls, riichi, kan replacement draws, exhaustive draws, wins scored
+//! through [`crate::scoring`] and dealer rotation. Select it per game with
+//! [`Backend::Engine`](crate::ffi::gamestate::Backend::Engine).
+//!
+//! Each advance moves to the next state function:
+//!
+//! - `PlayerHand` asks the current player for a tsumo, kan, riichi or discard
+//! - `Discard` offers the discard to the other seats as ron, kan, pon or chi.
+//!   Ron beats kan and pon, which beat chi
+//! - `ConvertedKan` moves to `KanDiscard`, where the kan piece can be robbed
+//! - `Exhaust`, `Ron` and `Tsumo` settle points and lead to `RoundEnd`
+//!
+//! Seats named after a libmahjong bot are played by a [`BuiltinBot`];
+//! registered Rust controllers are asked through the same decision events
+//! the native library sends.
+
+mod bots;
+pub mod wall;
+
+pub use bots::BuiltinBot;
+
+use crate::analysis::{shanten, winning_pieces};
+use crate::controller::{
+    create_controller, Call, CallOption, ControllerAdapter, Decision, Event, EventKind,
+};
+use crate::ffi::error::MahjongFFIError;
+use crate::ffi::gamestate::RawGameState;
+use crate::observe::{Hand, Meld, MeldType, ObservedGameState, StateFunctionType};
+use crate::piece::{Honor, Piece, TILE_KINDS};
+use crate::rules::{GameLength, LeftoverRiichi, RuleSet, RIICHI_DEPOSIT};
+use crate::runner::placements;
+use crate::scoring::{score, ScoreResult, WinContext};
+use crate::settings::GameSettings;
+use wall::{Rng, Wall};
+
+/// Pieces dealt to each player at the start of a round
+pub const STARTING_HAND_SIZE: usize = 13;
+
+/// Points paid between tenpai and noten players after an exhaustive draw
+pub const NOTEN_PAYMENT: i32 = 3000;
+
+/// Kans allowed in a single round
+pub const MAX_KANS: usize = 4;
+
+/// Pieces that must be left in the wall to declare riichi
+const RIICHI_MIN_WALL: usize = 4;
+
+enum SeatController {
+    Builtin(BuiltinBot),
+    Rust(ControllerAdapter),
+}
+
+struct Seat {
+    controller: SeatController,
+    hand: Hand,
+    double_riichi: bool,
+    ippatsu: bool,
+    /// Riichi declared this turn; the deposit is paid once the discard passes
+    riichi_pending: bool,
+    /// Temporary furiten, or permanent furiten after riichi
+    furiten: bool,
+    /// Every tile kind discarded this round, including called discards
+    discarded: [bool; TILE_KINDS],
+    has_discarded: bool,
+}
+
+impl Seat {
+    fn new(controller: SeatController) -> Self {
+        Self {
+            controller,
+            hand: Hand::default(),
+            double_riichi: false,
+            ippatsu: false,
+            riichi_pending: false,
+            furiten: false,
+            discarded: [false; TILE_KINDS],
+            has_discarded: false,
+        }
+    }
+
+    fn reset(&mut self, live_pieces: Vec<Piece>) {
+        self.hand = Hand {
+            live_pieces,
+            ..Hand::default()
+        };
+        self.hand.live_pieces.sort();
+        self.double_riichi = false;
+        self.ippatsu = false;
+        self.riichi_pending = false;
+        self.furiten = false;
+        self.discarded = [false; TILE_KINDS];
+        self.has_discarded = false;
+    }
+
+    fn count(&self, piece: Piece) -> usize {
+        self.hand
+            .live_pieces
+            .iter()
+            .filter(|p| p.index() == piece.index())
+            .count()
+    }
+
+    /// Remove a live piece of the same kind, keeping red fives if possible
+    fn take(&mut self, piece: Piece) -> Option<Piece> {
+        let live = &mut self.hand.live_pieces;
+        let i = live
+            .iter()
+            .position(|p| p.index() == piece.index() && !p.is_red_five())
+            .or_else(|| live.iter().position(|p| p.index() == piece.index()))?;
+        Some(live.remove(i))
+    }
+
+    fn is_closed(&self) -> bool {
+        self.hand
+            .melds
+            .iter()
+            .all(|meld| meld.meld_type == MeldType::ConcealedKan)
+    }
+}
+
+/// What the current player chose in `PlayerHand`
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Discard(Piece),
+    Riichi(Piece),
+    Tsumo,
+    ConcealedKan(Piece),
+    ConvertedKan(Piece),
+}
+
+/// A pon, chi or open kan waiting to be applied
+#[derive(Debug, Clone, Copy)]
+struct Claim {
+    seat: usize,
+    call: Call,
+    /// The lowest piece of the meld
+    start: Piece,
+}
+
+/// Whether the dealer keeps the deal and whether the round was drawn
+#[derive(Debug, Clone, Copy)]
+struct RoundResult {
+    dealer_keeps: bool,
+    drawn: bool,
+}
+
+/// A Riichi game played entirely in Rust
+pub struct Engine {
+    seed: u64,
+    rules: RuleSet,
+    rng: Rng,
+    wall: Wall,
+    seats: [Seat; 4],
+    round: i32,
+    counters: i32,
+    riichi_sticks: i32,
+    points: [i32; 4],
+    scores: [i32; 4],
+    current: i32,
+    turn_num: i32,
+    last_call: i32,
+    last_caller: i32,
+    concealed_kan: bool,
+    pending_piece: Option<Piece>,
+    has_ronned: [bool; 4],
+    drawn: Option<Piece>,
+    /// The current player drew a replacement piece after a kan
+    rinshan: bool,
+    /// No call has been made this round
+    uninterrupted: bool,
+    kans: usize,
+    action: Option<Action>,
+    claim: Option<Claim>,
+    result: Option<RoundResult>,
+    prev_state: StateFunctionType,
+    curr_state: StateFunctionType,
+    next_state: StateFunctionType,
+}
+
+impl Engine {
+    /// Create a game. Fails like `InitGameState` when a seat controller is
+    /// neither registered nor a libmahjong bot name.
+    pub fn new(settings: &GameSettings) -> Result<Self, MahjongFFIError> {
+        settings.rules.validate()?;
+
+        let mut seats = Vec::with_capacity(4);
+        for name in &settings.seat_controllers {
+            let controller = match create_controller(name) {
+                Some(controller) => SeatController::Rust(ControllerAdapter::new(controller)),
+                None => SeatController::Builtin(
+                    BuiltinBot::from_name(name)
+                        .ok_or(MahjongFFIError::FailedToAllocateGameState)?,
+                ),
+            };
+            seats.push(Seat::new(controller));
+        }
+        let seats: [Seat; 4] = seats
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("four seat controllers"));
+
+        let mut rng = Rng::new(settings.seed);
+        let wall = Wall::shuffled(&mut rng, settings.rules.red_fives);
+        Ok(Self {
+            seed: settings.seed,
+            rules: settings.rules.clone(),
+            rng,
+            wall,
+            seats,
+            round: 0,
+            counters: 0,
+            riichi_sticks: 0,
+            points: [0; 4],
+            scores: [0; 4],
+            current: -1,
+            turn_num: 0,
+            last_call: -1,
+            last_caller: -1,
+            concealed_kan: false,
+            pending_piece: None,
+            has_ronned: [false; 4],
+            drawn: None,
+            rinshan: false,
+            uninterrupted: true,
+            kans: 0,
+            action: None,
+            claim: None,
+            result: None,
+            prev_state: StateFunctionType::Error,
+            curr_state: StateFunctionType::Error,
+            next_state: StateFunctionType::GameStart,
+        })
+    }
+
+    /// Get the state function the game is in
+    pub fn state(&self) -> StateFunctionType {
+        self.curr_state
+    }
+
+    /// Move to the next state function. Returns false once the game has
+    /// ended, like `AdvanceGameState` returning null.
+    pub fn advance(&mut self) -> bool {
+        if self.curr_state == StateFunctionType::GameEnd {
+            return false;
+        }
+        let state = self.next_state;
+        self.prev_state = self.curr_state;
+        self.curr_state = state;
+        self.concealed_kan = false;
+        self.next_state = self.enter(state);
+        true
+    }
+
+    pub fn observe(&self) -> ObservedGameState {
+        ObservedGameState {
+            current_player: self.current,
+            turn_num: self.turn_num,
+            round_num: self.round,
+            riichi_sticks: self.riichi_sticks,
+            counters: self.counters,
+            last_call: self.last_call,
+            last_caller: self.last_caller,
+            concealed_kan: self.concealed_kan,
+            seed: self.seed,
+            pending_piece: self.pending_piece,
+            scores: self.scores,
+            points: self.points,
+            has_ronned: self.has_ronned,
+            hands: std::array::from_fn(|seat| self.seats[seat].hand.clone()),
+            prev_state: self.prev_state,
+            curr_state: self.curr_state,
+            next_state: self.next_state,
+        }
+    }
+
+    fn dealer(&self) -> usize {
+        self.round.rem_euclid(4) as usize
+    }
+
+    fn seat(&self) -> usize {
+        self.current.rem_euclid(4) as usize
+    }
+
+    fn seat_wind(&self, seat: usize) -> Honor {
+        Honor::wind_for_seat(seat + 4 - self.dealer())
+    }
+
+    fn round_wind(&self) -> Honor {
+        Honor::wind_for_seat((self.round / 4) as usize)
+    }
+
+    fn total_rounds(&self) -> i32 {
+        match self.rules.length {
+            GameLength::Tonpuusen => 4,
+            GameLength::Hanchan => 8,
+        }
+    }
+
+    fn enter(&mut self, state: StateFunctionType) -> StateFunctionType {
+        use StateFunctionType::*;
+        match state {
+            GameStart => {
+                self.points = [self.rules.starting_points; 4];
+                for (seat, slot) in self.seats.iter_mut().enumerate() {
+                    if let SeatController::Rust(adapter) = &mut slot.controller {
+                        adapter.game_start(seat);
+                    }
+                }
+                RoundStart
+            }
+            RoundStart => {
+                self.start_round();
+                Draw
+            }
+            Draw => {
+                if self.prev_state == Discard {
+                    self.current = (self.current + 1) % 4;
+                }
+                self.draw(false);
+                PlayerHand
+            }
+            PlayerHand => {
+                let action = self.decide_turn();
+                self.action = Some(action);
+                match action {
+                    Action::Discard(_) => Discard,
+                    Action::Riichi(_) => Riichi,
+                    Action::Tsumo => Tsumo,
+                    Action::ConcealedKan(_) => ConcealedKan,
+                    Action::ConvertedKan(_) => ConvertedKan,
+                }
+            }
+            Riichi => {
+                self.declare_riichi();
+                Discard
+            }
+            Discard => self.discard(),
+            Pon | Chi | Kan => self.apply_claim(),
+            ConcealedKan => {
+                self.concealed_kan();
+                Replacement
+            }
+            ConvertedKan => {
+                self.converted_kan();
+                KanDiscard
+            }
+            KanDiscard => {
+                let piece = self.pending_piece.expect("converted kan piece is pending");
+                if self.collect_rons(piece, true) {
+                    Ron
+                } else {
+                    self.kans += 1;
+                    Replacement
+                }
+            }
+            Replacement => {
+                self.draw(true);
+                let indicators = self.wall.dora_indicators();
+                let indicator = indicators[indicators.len() - 1];
+                self.notify(EventKind::Dora, -1, indicator.into());
+                PlayerHand
+            }
+            Tsumo => {
+                self.settle_tsumo();
+                RoundEnd
+            }
+            Ron => {
+                self.settle_rons();
+                RoundEnd
+            }
+            Exhaust => {
+                self.settle_exhaust();
+                RoundEnd
+            }
+            RoundEnd => self.end_round(),
+            GameEnd | Error => {
+                self.end_game();
+                GameEnd
+            }
+        }
+    }
+
+    fn start_round(&mut self) {
+        self.wall = Wall::shuffled(&mut self.rng, self.rules.red_fives);
+        for seat in 0..4 {
+            let pieces = self.wall.deal(STARTING_HAND_SIZE);
+            self.seats[seat].reset(pieces);
+        }
+
+        self.current = self.dealer() as i32;
+        self.turn_num = 0;
+        self.last_call = -1;
+        self.last_caller = -1;
+        self.pending_piece = None;
+        self.has_ronned = [false; 4];
+        self.scores = [0; 4];
+        self.drawn = None;
+        self.rinshan = false;
+        self.uninterrupted = true;
+        self.kans = 0;
+        self.action = None;
+        self.claim = None;
+        self.result = None;
+
+        let round_wind = self.round_wind();
+        let observed = self.observe_for_controllers();
+        for seat in 0..4 {
+            let seat_wind = self.seat_wind(seat);
+            let slot = &mut self.seats[seat];
+            if let (SeatController::Rust(adapter), Some(observed)) =
+                (&mut slot.controller, &observed)
+            {
+                let view = observed.view_for(seat);
+                adapter.round_start(&slot.hand.live_pieces, seat_wind, round_wind, &view);
+            }
+        }
+        let indicator = self.wall.dora_indicators()[0];
+        self.notify(EventKind::Dora, -1, indicator.into());
+    }
+
+    fn draw(&mut self, replacement: bool) {
+        let piece = if replacement {
+            self.wall.draw_replacement()
+        } else {
+            self.wall.draw()
+        };
+        let seat = self.seat();
+        self.seats[seat].hand.live_pieces.extend(piece);
+        self.drawn = piece;
+        self.rinshan = replacement;
+    }
+
+    /// Offer options to a Rust controller along with the view of its seat
+    fn ask_controller(
+        &mut self,
+        seat: usize,
+        calls: &[CallOption],
+        discards: &[Piece],
+    ) -> Decision {
+        let view = self.observe().view_for(seat);
+        let SeatController::Rust(adapter) = &mut self.seats[seat].controller else {
+            return Decision::Decline;
+        };
+        let offered = calls
+            .iter()
+            .map(|option| (option.call.kind(), option.piece))
+            .chain(discards.iter().map(|&p| (EventKind::Discard, p.into())));
+        for (kind, value) in offered {
+            let event = Event {
+                kind,
+                player: seat as i32,
+                value,
+                decision: true,
+            };
+            adapter.receive_event(event, &view);
+        }
+        adapter.retrieve_decision(&view)
+    }
+
+    /// Observe the game for the views handed to Rust controllers, or `None`
+    /// when every seat is a builtin bot
+    fn observe_for_controllers(&self) -> Option<ObservedGameState> {
+        self.seats
+            .iter()
+            .any(|seat| matches!(seat.controller, SeatController::Rust(_)))
+            .then(|| self.observe())
+    }
+
+    /// Send a non-decision event to every Rust controller
+    fn notify(&mut self, kind: EventKind, player: i32, value: i32) {
+        let event = Event {
+            kind,
+            player,
+            value,
+            decision: false,
+        };
+        let Some(observed) = self.observe_for_controllers() else {
+            return;
+        };
+        for (i, seat) in self.seats.iter_mut().enumerate() {
+            if let SeatController::Rust(adapter) = &mut seat.controller {
+                adapter.receive_event(event, &observed.view_for(i));
+            }
+        }
+    }
+
+    /// Offer options to a seat and return its validated choice
+    fn ask(&mut self, seat: usize, calls: &[CallOption], discards: &[Piece]) -> Decision {
+        let decision = match &self.seats[seat].controller {
+            SeatController::Rust(_) => self.ask_controller(seat, calls, discards),
+            SeatController::Builtin(bot) => {
+                let bot = *bot;
+                let hands = std::array::from_fn(|i| self.seats[i].hand.clone());
+                bot.decide(seat, &hands, calls, discards)
+            }
+        };
+
+        match decision {
+            Decision::Call(option) if calls.contains(&option) => decision,
+            Decision::Discard(piece) if discards.contains(&piece) => decision,
+            _ => match discards.first() {
+                Some(&piece) => Decision::Discard(piece),
+                None => Decision::Decline,
+            },
+        }
+    }
+
+    fn win_context(&self, seat: usize, piece: Piece, tsumo: bool, chankan: bool) -> WinContext {
+        let player = &self.seats[seat];
+        let riichi = player.hand.riichi;
+        WinContext {
+            winning_piece: piece,
+            tsumo,
+            seat_wind: self.seat_wind(seat),
+            round_wind: self.round_wind(),
+            riichi: riichi && !player.double_riichi,
+            double_riichi: player.double_riichi,
+            ippatsu: player.ippatsu,
+            last_tile: self.wall.remaining() == 0 && !(tsumo && self.rinshan),
+            rinshan: tsumo && self.rinshan,
+            chankan,
+            first_turn: tsumo && self.uninterrupted && !player.has_discarded,
+            open_tanyao: self.rules.open_tanyao,
+            dora_indicators: self.wall.dora_indicators().to_vec(),
+            ura_dora_indicators: if riichi {
+                self.wall.ura_indicators().to_vec()
+            } else {
+                Vec::new()
+            },
+            honba: self.counters as u32,
+            riichi_sticks: self.riichi_sticks as u32,
+        }
+    }
+
+    /// Check if `piece` completes the seat's hand, ignoring yaku and furiten
+    fn completes(&self, seat: usize, piece: Piece) -> bool {
+        let mut hand = self.seats[seat].hand.clone();
+        hand.live_pieces.push(piece);
+        shanten(&hand).is_complete()
+    }
+
+    fn can_ron(&self, seat: usize, piece: Piece, chankan: bool) -> bool {
+        let player = &self.seats[seat];
+        let waits = winning_pieces(&player.hand);
+        let furiten = player.furiten || waits.iter().any(|wait| player.discarded[wait.index()]);
+        !furiten
+            && waits.iter().any(|wait| wait.index() == piece.index())
+            && score(&player.hand, &self.win_context(seat, piece, false, chankan)).is_ok()
+    }
+
+    fn can_kan(&self) -> bool {
+        self.kans < MAX_KANS && self.wall.has_replacement() && self.wall.remaining() > 0
+    }
+
+    fn decide_turn(&mut self) -> Action {
+        let seat = self.seat();
+        let player = &self.seats[seat];
+        let hand = &player.hand;
+        let mut calls = Vec::new();
+        let option = |call, piece: Piece| CallOption {
+            call,
+            piece: piece.into(),
+        };
+
+        // Only a drawn piece can win by tsumo, never one taken with a call
+        if let Some(drawn) = self.drawn {
+            if hand.live_pieces.len() % 3 == 2
+                && shanten(hand).is_complete()
+                && score(hand, &self.win_context(seat, drawn, true, false)).is_ok()
+            {
+                calls.push(option(Call::Tsumo, drawn));
+            }
+        }
+
+        let mut distinct = hand.live_pieces.clone();
+        distinct.sort();
+        distinct.dedup();
+
+        if !hand.riichi && self.can_kan() {
+            for &piece in &distinct {
+                if !piece.is_red_five() && player.count(piece) == 4 {
+                    calls.push(option(Call::ConcealedKan, piece));
+                }
+            }
+            for meld in hand.melds.iter().filter(|m| m.meld_type == MeldType::Pon) {
+                if player.count(meld.start) > 0 {
+                    calls.push(option(Call::ConvertedKan, meld.start));
+                }
+            }
+        }
+
+        let can_riichi = !hand.riichi
+            && player.is_closed()
+            && shanten(hand).min() <= 0
+            && self.points[seat] >= RIICHI_DEPOSIT
+            && self.wall.remaining() >= RIICHI_MIN_WALL;
+        if can_riichi {
+            for &piece in &distinct {
+                let mut after = hand.clone();
+                if let Some(i) = after.live_pieces.iter().position(|&p| p == piece) {
+                    after.live_pieces.remove(i);
+                }
+                if shanten(&after).is_tenpai() {
+                    calls.push(option(Call::Riichi, piece));
+                }
+            }
+        }
+
+        let discards = match self.drawn {
+            Some(drawn) if hand.riichi => vec![drawn],
+            _ => distinct,
+        };
+
+        match self.ask(seat, &calls, &discards) {
+            Decision::Call(option) => {
+                let piece = Piece::try_from(option.piece).ok();
+                match (option.call, piece) {
+                    (Call::Tsumo, _) => Action::Tsumo,
+                    (Call::ConcealedKan, Some(piece)) => Action::ConcealedKan(piece),
+                    (Call::ConvertedKan, Some(piece)) => Action::ConvertedKan(piece),
+                    (Call::Riichi, Some(piece)) => Action::Riichi(piece),
+                    _ => Action::Discard(discards[0]),
+                }
+            }
+            Decision::Discard(piece) => Action::Discard(piece),
+            Decision::Decline => Action::Discard(discards[0]),
+        }
+    }
+
+    fn declare_riichi(&mut self) {
+        let seat = self.seat();
+        let uninterrupted = self.uninterrupted;
+        let turn_num = self.turn_num;
+        let player = &mut self.seats[seat];
+        player.hand.riichi = true;
+        player.hand.riichi_piece_discard = player.hand.discards.len() as i32;
+        player.hand.riichi_round = turn_num;
+        player.double_riichi = uninterrupted && !player.has_discarded;
+        player.ippatsu = true;
+        player.riichi_pending = true;
+        self.notify(EventKind::Riichi, seat as i32, 0);
+    }
+
+    fn discard(&mut self) -> StateFunctionType {
+        let seat = self.seat();
+        let (piece, declared) = match self.action.take() {
+            Some(Action::Discard(piece)) => (piece, false),
+            Some(Action::Riichi(piece)) => (piece, true),
+            _ => unreachable!("discard follows a discard or riichi decision"),
+        };
+
+        let player = &mut self.seats[seat];
+        if let Some(i) = player.hand.live_pieces.iter().position(|&p| p == piece) {
+            player.hand.live_pieces.remove(i);
+        }
+        player.hand.live_pieces.sort();
+        player.hand.discards.push(piece);
+        player.discarded[piece.index()] = true;
+        player.has_discarded = true;
+        if !declared {
+            player.ippatsu = false;
+        }
+        if !player.hand.riichi {
+            player.furiten = false;
+        }
+
+        self.turn_num += 1;
+        self.pending_piece = Some(piece);
+        self.drawn = None;
+        self.notify(EventKind::Discard, seat as i32, piece.into());
+
+        if self.collect_rons(piece, false) {
+            return StateFunctionType::Ron;
+        }
+        self.pay_riichi_deposit(seat);
+
+        self.claim = self.collect_claim(piece);
+        match self.claim.map(|claim| claim.call) {
+            Some(Call::Kan) => StateFunctionType::Kan,
+            Some(Call::Pon) => StateFunctionType::Pon,
+            Some(Call::Chi) => StateFunctionType::Chi,
+            _ if self.wall.remaining() == 0 => StateFunctionType::Exhaust,
+            _ => StateFunctionType::Draw,
+        }
+    }
+
+    fn pay_riichi_deposit(&mut self, seat: usize) {
+        let player = &mut self.seats[seat];
+        if player.riichi_pending {
+            player.riichi_pending = false;
+            self.points[seat] -= RIICHI_DEPOSIT;
+            self.riichi_sticks += 1;
+        }
+    }
+
+    /// Offer a ron on `piece` to every other seat in turn order. Seats that
+    /// pass on a winning piece become furiten. Returns whether anyone won.
+    fn collect_rons(&mut self, piece: Piece, chankan: bool) -> bool {
+        let discarder = self.seat();
+        let mut winners = Vec::new();
+        for offset in 1..4 {
+            let seat = (discarder + offset) % 4;
+            if !self.completes(seat, piece) {
+                continue;
+            }
+            let ron = CallOption {
+                call: Call::Ron,
+                piece: piece.into(),
+            };
+            let won = self.can_ron(seat, piece, chankan)
+                && self.ask(seat, &[ron], &[]) == Decision::Call(ron);
+            if won {
+                winners.push(seat);
+            } else {
+                self.seats[seat].furiten = true;
+            }
+        }
+
+        if !self.rules.double_ron {
+            winners.truncate(1);
+        }
+        for &seat in &winners {
+            self.has_ronned[seat] = true;
+        }
+        !winners.is_empty()
+    }
+
+    /// Offer pon, chi and open kan on a discard. Pon and kan take priority
+    /// over chi.
+    fn collect_claim(&mut self, piece: Piece) -> Option<Claim> {
+        if self.wall.remaining() == 0 {
+            return None;
+        }
+        let discarder = self.seat();
+        let mut chosen: Option<Claim> = None;
+
+        for offset in 1..4 {
+            let seat = (discarder + offset) % 4;
+            let player = &self.seats[seat];
+            if player.hand.riichi {
+                continue;
+            }
+
+            let option = |call, piece: Piece| CallOption {
+                call,
+                piece: piece.into(),
+            };
+            let mut calls = Vec::new();
+            let count = player.count(piece);
+            if count >= 3 && self.can_kan() {
+                calls.push(option(Call::Kan, piece));
+            }
+            if count >= 2 {
+                calls.push(option(Call::Pon, piece));
+            }
+            if offset == 1 {
+                calls.extend(
+                    chi_starts(piece)
+                        .into_iter()
+                        .filter(|&start| {
+                            sequence(start)
+                                .iter()
+                                .filter(|p| p.index() != piece.index())
+                                .all(|&p| player.count(p) > 0)
+                        })
+                        .map(|start| option(Call::Chi, start)),
+                );
+            }
+            if calls.is_empty() {
+                continue;
+            }
+
+            if let Decision::Call(option) = self.ask(seat, &calls, &[]) {
+                let start = Piece::try_from(option.piece).unwrap_or(piece);
+                let claim = Claim {
+                    seat,
+                    call: option.call,
+                    start,
+                };
+                let better = match chosen {
+                    None => true,
+                    Some(current) => current.call == Call::Chi && option.call != Call::Chi,
+                };
+                if better {
+                    chosen = Some(claim);
+                }
+            }
+        }
+        chosen
+    }
+
+    fn interrupt(&mut self, caller: usize) {
+        self.uninterrupted = false;
+        for seat in &mut self.seats {
+            seat.ippatsu = false;
+        }
+        self.last_call = self.turn_num;
+        self.last_caller = caller as i32;
+    }
+
+    fn apply_claim(&mut self) -> StateFunctionType {
+        let claim = self.claim.take().expect("a claim was accepted");
+        let piece = self
+            .pending_piece
+            .take()
+            .expect("claimed discard is pending");
+        let discarder = self.seat();
+        self.seats[discarder].hand.discards.pop();
+
+        let player = &mut self.seats[claim.seat];
+        let (meld_type, taken, next) = match claim.call {
+            Call::Kan => (
+                MeldType::Kan,
+                vec![piece; 3],
+                StateFunctionType::Replacement,
+            ),
+            Call::Pon => (MeldType::Pon, vec![piece; 2], StateFunctionType::PlayerHand),
+            _ => {
+                let others = sequence(claim.start)
+                    .into_iter()
+                    .filter(|p| p.index() != piece.index())
+                    .collect();
+                (MeldType::Chi, others, StateFunctionType::PlayerHand)
+            }
+        };
+        for piece in taken {
+            player.take(piece);
+        }
+        player.hand.melds.push(Meld {
+            meld_type,
+            start: claim.start.normalized(),
+        });
+        player.hand.open = true;
+
+        let kind = claim.call.kind();
+        if claim.call == Call::Kan {
+            self.kans += 1;
+        }
+        self.current = claim.seat as i32;
+        self.drawn = None;
+        self.interrupt(claim.seat);
+        self.notify(kind, claim.seat as i32, piece.into());
+        next
+    }
+
+    fn concealed_kan(&mut self) {
+        let seat = self.seat();
+        let Some(Action::ConcealedKan(piece)) = self.action.take() else {
+            unreachable!("concealed kan follows a concealed kan decision");
+        };
+        let player = &mut self.seats[seat];
+        for _ in 0..4 {
+            player.take(piece);
+        }
+        player.hand.melds.push(Meld {
+            meld_type: MeldType::ConcealedKan,
+            start: piece.normalized(),
+        });
+        self.kans += 1;
+        self.concealed_kan = true;
+        self.interrupt(seat);
+        self.notify(EventKind::ConcealedKan, seat as i32, piece.into());
+    }
+
+    fn converted_kan(&mut self) {
+        let seat = self.seat();
+        let Some(Action::ConvertedKan(piece)) = self.action.take() else {
+            unreachable!("converted kan follows a converted kan decision");
+        };
+        let player = &mut self.seats[seat];
+        let taken = player.take(piece).unwrap_or(piece);
+        if let Some(meld) = player
+            .hand
+            .melds
+            .iter_mut()
+            .find(|m| m.meld_type == MeldType::Pon && m.start.index() == piece.index())
+        {
+            meld.meld_type = MeldType::Kan;
+        }
+        self.pending_piece = Some(taken);
+        self.interrupt(seat);
+        self.notify(EventKind::ConvertedKan, seat as i32, piece.into());
+    }
+
+    fn apply_deltas(&mut self, deltas: [i32; 4]) {
+        for (seat, delta) in deltas.into_iter().enumerate() {
+            self.points[seat] += delta;
+            self.scores[seat] += delta;
+            if delta != 0 {
+                self.notify(EventKind::PointDiff, seat as i32, delta);
+            }
+        }
+    }
+
+    fn settle(
+        &self,
+        seat: usize,
+        piece: Piece,
+        tsumo: bool,
+        chankan: bool,
+        first: bool,
+    ) -> Option<ScoreResult> {
+        let mut ctx = self.win_context(seat, piece, tsumo, chankan);
+        if !first {
+            // Honba and riichi sticks go to the first winner only
+            ctx.honba = 0;
+            ctx.riichi_sticks = 0;
+        }
+        score(&self.seats[seat].hand, &ctx).ok()
+    }
+
+    fn settle_tsumo(&mut self) {
+        let seat = self.seat();
+        let piece = self.drawn.expect("tsumo is only offered after a draw");
+        if let Some(result) = self.settle(seat, piece, true, false, true) {
+            let deltas = result.point_deltas(seat, None, self.dealer());
+            self.apply_deltas(deltas);
+            self.riichi_sticks = 0;
+        }
+        self.notify(EventKind::Tsumo, seat as i32, piece.into());
+        self.result = Some(RoundResult {
+            dealer_keeps: seat == self.dealer(),
+            drawn: false,
+        });
+    }
+
+    fn settle_rons(&mut self) {
+        let discarder = self.seat();
+        let piece = self.pending_piece.expect("ronned piece is pending");
+        let chankan = self.prev_state == StateFunctionType::KanDiscard;
+        if chankan {
+            // A robbed kan stays a pon
+            let player = &mut self.seats[discarder];
+            if let Some(meld) = player
+                .hand
+                .melds
+                .iter_mut()
+                .find(|m| m.meld_type == MeldType::Kan && m.start.index() == piece.index())
+            {
+                meld.meld_type = MeldType::Pon;
+            }
+        }
+
+        let mut deltas = [0; 4];
+        let winners: Vec<usize> = (1..4)
+            .map(|offset| (discarder + offset) % 4)
+            .filter(|&seat| self.has_ronned[seat])
+            .collect();
+        for (i, &seat) in winners.iter().enumerate() {
+            if let Some(result) = self.settle(seat, piece, false, chankan, i == 0) {
+                let gained = result.point_deltas(seat, Some(discarder), self.dealer());
+                for (delta, gain) in deltas.iter_mut().zip(gained) {
+                    *delta += gain;
+                }
+            }
+            self.notify(EventKind::Ron, seat as i32, piece.into());
+        }
+        self.apply_deltas(deltas);
+        self.riichi_sticks = 0;
+        self.result = Some(RoundResult {
+            dealer_keeps: winners.contains(&self.dealer()),
+            drawn: false,
+        });
+    }
+
+    fn settle_exhaust(&mut self) {
+        let tenpai: [bool; 4] =
+            std::array::from_fn(|seat| shanten(&self.seats[seat].hand).is_tenpai());
+        let count = tenpai.iter().filter(|&&t| t).count() as i32;
+
+        let mut deltas = [0; 4];
+        if (1..4).contains(&count) {
+            for (delta, tenpai) in deltas.iter_mut().zip(tenpai) {
+                *delta = if tenpai {
+                    NOTEN_PAYMENT / count
+                } else {
+                    -NOTEN_PAYMENT / (4 - count)
+                };
+            }
+        }
+        self.notify(EventKind::ExhaustiveDraw, -1, 0);
+        self.apply_deltas(deltas);
+        self.result = Some(RoundResult {
+            dealer_keeps: tenpai[self.dealer()],
+            drawn: true,
+        });
+    }
+
+    /// Work out the next round and whether the game is over. The new round
+    /// number is applied when the next round starts, so `RoundEnd` still
+    /// reports the round that ended.
+    fn end_round(&mut self) -> StateFunctionType {
+        let result = self.result.unwrap_or(RoundResult {
+            dealer_keeps: false,
+            drawn: true,
+        });
+        let (round, counters) = if result.dealer_keeps {
+            (self.round, self.counters + 1)
+        } else if result.drawn {
+            (self.round + 1, self.counters + 1)
+        } else {
+            (self.round + 1, 0)
+        };
+
+        let busted = self.rules.tobi && self.points.iter().any(|&p| p < 0);
+        if busted || round >= self.total_rounds() {
+            StateFunctionType::GameEnd
+        } else {
+            self.round = round;
+            self.counters = counters;
+            StateFunctionType::RoundStart
+        }
+    }
+
+    fn end_game(&mut self) {
+        if self.rules.leftover_riichi == LeftoverRiichi::ToFirstPlace && self.riichi_sticks > 0 {
+            let first = placements(&self.points)
+                .iter()
+                .position(|&place| place == 0)
+                .unwrap_or(0);
+            self.points[first] += self.riichi_sticks * RIICHI_DEPOSIT;
+            self.riichi_sticks = 0;
+        }
+        self.notify(EventKind::End, -1, 0);
+    }
+}
+
+/// Lowest pieces of every sequence `piece` could complete
+fn chi_starts(piece: Piece) -> Vec<Piece> {
+    let (Some(suit), Some(rank)) = (piece.suit(), piece.rank()) else {
+        return Vec::new();
+    };
+    (rank.saturating_sub(2).max(1)..=rank.min(7))
+        .filter_map(|start| Piece::suited(suit, start))
+        .collect()
+}
+
+fn sequence(start: Piece) -> Vec<Piece> {
+    Meld {
+        meld_type: MeldType::Chi,
+        start,
+    }
+    .pieces()
+}
+
+/// Hand an engine to a [`GameState`](crate::ffi::gamestate::GameState)
+/// behind the same opaque pointer as native games
+pub(crate) fn into_raw(engine: Engine) -> *mut RawGameState {
+    Box::into_raw(Box::new(engine)) as *mut RawGameState
+}
+
+/// Advance an engine game, freeing it and returning null once it has ended
+///
+/// # Safety
+/// `state` must come from [`into_raw`] and not have been freed.
+pub(crate) unsafe fn advance_raw(state: *mut RawGameState) -> *mut RawGameState {
+    let engine = &mut *(state as *mut Engine);
+    if engine.advance() {
+        state
+    } else {
+        free_raw(state);
+        std::ptr::null_mut()
+    }
+}
+
+/// # Safety
+/// `state` must come from [`into_raw`] and not have been freed.
+pub(crate) unsafe fn observe_raw(state: *mut RawGameState) -> ObservedGameState {
+    (*(state as *const Engine)).observe()
+}
+
+/// # Safety
+/// `state` must come from [`into_raw`] and is invalid afterwards.
+pub(crate) unsafe fn free_raw(state: *mut RawGameState) {
+    drop(Box::from_raw(state as *mut Engine));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{event_log, GameEvent};
+    use crate::piece::TILE_COUNT;
+
+    fn play(seed: u64, controller: &str) -> Vec<ObservedGameState> {
+        let settings = GameSettings::builder()
+            .seed(seed)
+            .all_seats(controller)
+            .rules(RuleSet::default().tonpuusen())
+            .build()
+            .unwrap();
+        let mut engine = Engine::new(&settings).unwrap();
+        let mut states = vec![engine.observe()];
+        while engine.advance() {
+            states.push(engine.observe());
+            assert!(states.len() < 20_000, "game did not end");
+        }
+        states
+    }
+
+    #[test]
+    fn plays_complete_games() {
+        for seed in 0..2 {
+            let states = play(seed, "TotoBot");
+            let last = states.last().unwrap();
+            assert_eq!(last.curr_state, StateFunctionType::GameEnd);
+
+            let total: i32 = last.points.iter().sum::<i32>() + last.riichi_sticks * RIICHI_DEPOSIT;
+            assert_eq!(total, 4 * 25000, "seed {seed} lost points");
+
+            for state in &states {
+                let pieces: usize = state
+                    .hands
+                    .iter()
+                    .map(|hand| {
+                        hand.live_piece_count()
+                            + hand.discard_count()
+                            + hand.melds.iter().map(|m| m.pieces().len()).sum::<usize>()
+                    })
+                    .sum();
+                assert!(pieces <= TILE_COUNT);
+            }
+        }
+    }
+
+    #[test]
+    fn games_are_deterministic_and_have_wins() {
+        assert_eq!(play(7, "ThriceBot"), play(7, "ThriceBot"));
+
+        let wins = (0..2)
+            .flat_map(|seed| event_log(&play(seed, "TotoBot")))
+            .filter(|event| matches!(event, GameEvent::Ron { .. } | GameEvent::Tsumo { .. }))
+            .count();
+        assert!(wins > 0);
+    }
+
+    #[test]
+    fn follows_libmahjong_transitions() {
+        use StateFunctionType::*;
+        let states = play(3, "FastTanyao");
+        assert_eq!(states[0].curr_state, Error);
+        assert_eq!(states[0].current_player, -1);
+        assert_eq!(states[1].curr_state, GameStart);
+        assert_eq!(states[2].curr_state, RoundStart);
+
+        for pair in states.windows(2) {
+            let (before, after) = (pair[0].curr_state, pair[1].curr_state);
+            assert_eq!(before, pair[1].prev_state);
+            assert_eq!(pair[0].next_state, after);
+            assert!(before.can_transition_to(after), "{before:?} -> {after:?}");
+            if after == Tsumo {
+                // Pieces taken with pon or chi cannot win by tsumo
+                assert!(matches!(pair[0].prev_state, Draw | Replacement));
+            }
+        }
+    }
+
+    #[test]
+    fn runs_behind_game_state() {
+        use crate::ffi::gamestate::{Backend, GameState};
+
+        let settings = GameSettings::builder()
+            .seed(5)
+            .all_seats("GentlemanBot")
+            .build()
+            .unwrap();
+        let mut engine = Engine::new(&settings).unwrap();
+        let mut state = GameState::with_backend(settings, Backend::Engine).unwrap();
+        assert_eq!(state.backend(), Backend::Engine);
+        assert!(matches!(
+            state.as_ptr(),
+            Err(crate::ffi::error::MahjongFFIError::NotNativeState)
+        ));
+        for _ in 0..50 {
+            assert_eq!(state.observe(), Some(engine.observe()));
+            state = state.advance().unwrap();
+            engine.advance();
+        }
+
+        let unknown = GameSettings::builder()
+            .all_seats("NoSuchBot")
+            .allow_unknown_controllers()
+            .build()
+            .unwrap();
+        assert!(GameState::with_backend(unknown, Backend::Engine).is_err());
+    }
+
+    #[test]
+    fn offers_chi_sequences() {
+        let five: Piece = "5p".parse().unwrap();
+        let starts: Vec<String> = chi_starts(five).iter().map(|p| p.to_string()).collect();
+        assert_eq!(starts, ["3p", "4p", "5p"]);
+        assert_eq!(chi_starts("1s".parse().unwrap()).len(), 1);
+        assert!(chi_starts("1z".parse().unwrap()).is_empty());
+    }
+}
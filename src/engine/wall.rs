@@ -0,0 +1,144 @@
+use crate::piece::{Piece, Suit, DEAD_WALL_SIZE, TILE_KINDS};
+
+/// Dora indicators that can be revealed in a round, one plus one per kan
+pub const MAX_DORA_INDICATORS: usize = 5;
+
+/// Replacement pieces drawn after a kan
+pub const REPLACEMENT_PIECES: usize = 4;
+
+/// SplitMix64, so a seed always produces the same sequence of walls
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Fisher-Yates shuffle
+    pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// All 136 pieces in index order, with one five per suit replaced by its
+/// red five when `red_fives` is set
+pub(crate) fn tile_set(red_fives: bool) -> Vec<Piece> {
+    let mut pieces: Vec<Piece> = (0..TILE_KINDS)
+        .filter_map(Piece::from_index)
+        .flat_map(|piece| [piece; 4])
+        .collect();
+    if red_fives {
+        for suit in Suit::ALL {
+            let five = Piece::suited(suit, 5).expect("five is a valid rank");
+            if let Some(slot) = pieces.iter_mut().find(|piece| **piece == five) {
+                *slot = Piece::red_five(suit);
+            }
+        }
+    }
+    pieces
+}
+
+/// A shuffled wall split into the live wall and the dead wall
+#[derive(Debug, Clone)]
+pub struct Wall {
+    /// Drawn from the back
+    live: Vec<Piece>,
+    replacements: Vec<Piece>,
+    indicators: Vec<Piece>,
+    ura_indicators: Vec<Piece>,
+    revealed: usize,
+}
+
+impl Wall {
+    pub(crate) fn shuffled(rng: &mut Rng, red_fives: bool) -> Self {
+        let mut live = tile_set(red_fives);
+        rng.shuffle(&mut live);
+
+        let mut dead = live.split_off(live.len() - DEAD_WALL_SIZE);
+        let replacements = dead.split_off(dead.len() - REPLACEMENT_PIECES);
+        let ura_indicators = dead.split_off(MAX_DORA_INDICATORS);
+        Self {
+            live,
+            replacements,
+            indicators: dead,
+            ura_indicators,
+            revealed: 1,
+        }
+    }
+
+    /// Deal a starting hand from the live wall
+    pub fn deal(&mut self, count: usize) -> Vec<Piece> {
+        self.live.split_off(self.live.len().saturating_sub(count))
+    }
+
+    /// Draw the next piece of the live wall
+    pub fn draw(&mut self) -> Option<Piece> {
+        self.live.pop()
+    }
+
+    /// Draw a replacement piece after a kan and reveal the next dora
+    /// indicator. The dead wall is topped up from the end of the live wall.
+    pub fn draw_replacement(&mut self) -> Option<Piece> {
+        let piece = self.replacements.pop()?;
+        if !self.live.is_empty() {
+            self.live.remove(0);
+        }
+        self.revealed = (self.revealed + 1).min(MAX_DORA_INDICATORS);
+        Some(piece)
+    }
+
+    /// Get the number of pieces left to draw
+    pub fn remaining(&self) -> usize {
+        self.live.len()
+    }
+
+    /// Check if another kan can be declared
+    pub fn has_replacement(&self) -> bool {
+        !self.replacements.is_empty()
+    }
+
+    pub fn dora_indicators(&self) -> &[Piece] {
+        &self.indicators[..self.revealed]
+    }
+
+    /// Ura dora indicators under the revealed dora indicators
+    pub fn ura_indicators(&self) -> &[Piece] {
+        &self.ura_indicators[..self.revealed]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_the_wall() {
+        let mut wall = Wall::shuffled(&mut Rng::new(3), true);
+        assert_eq!(wall.remaining(), 136 - DEAD_WALL_SIZE);
+        assert_eq!(wall.dora_indicators().len(), 1);
+
+        let hand = wall.deal(13);
+        assert_eq!(hand.len(), 13);
+        assert_eq!(wall.remaining(), 136 - DEAD_WALL_SIZE - 13);
+
+        assert!(wall.draw_replacement().is_some());
+        assert_eq!(wall.remaining(), 136 - DEAD_WALL_SIZE - 14);
+        assert_eq!(wall.dora_indicators().len(), 2);
+        assert_eq!(wall.ura_indicators().len(), 2);
+
+        let reds = tile_set(true).iter().filter(|p| p.is_red_five()).count();
+        assert_eq!(reds, 3);
+    }
+}
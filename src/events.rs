@@ -0,0 +1,471 @@
+//! Semantic game events derived from consecutive observed states.
+//!
+//! [`EventDiffer`] compares each [`ObservedGameState`] with the one before
+//! it. Tile movements (draws, discards, calls, riichi) come from differences
+//! between hands, while deals, wins, exhaustive draws and round/game ends
+//! come from the state function that produced the snapshot. States should be
+//! pushed for every step, as [`GameRunner`](crate::runner::GameRunner)
+//! yields them; skipping steps can merge or hide tile movements.
+
+use crate::observe::{Meld, MeldType, ObservedGameState, StateFunctionType};
+use crate::piece::Piece;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    /// Starting hands for a new round
+    Deal {
+        round: i32,
+        /// Honba counters carried into the round
+        counters: i32,
+        /// Riichi sticks left on the table from earlier rounds
+        riichi_sticks: i32,
+        hands: [Vec<Piece>; 4],
+    },
+    Draw {
+        seat: usize,
+        piece: Piece,
+    },
+    Discard {
+        seat: usize,
+        piece: Piece,
+    },
+    /// Chi called on the previous player's discard. `called` is the
+    /// discard and `consumed` the pieces taken from the caller's hand, both
+    /// keeping red fives, which the normalized `meld` does not.
+    Chi {
+        seat: usize,
+        from: Option<usize>,
+        meld: Meld,
+        called: Option<Piece>,
+        consumed: Vec<Piece>,
+    },
+    /// Pon called on a discard, with the pieces as for [`GameEvent::Chi`]
+    Pon {
+        seat: usize,
+        from: Option<usize>,
+        meld: Meld,
+        called: Option<Piece>,
+        consumed: Vec<Piece>,
+    },
+    /// Open kan called on another player's discard, with the pieces as for
+    /// [`GameEvent::Chi`]
+    Kan {
+        seat: usize,
+        from: Option<usize>,
+        meld: Meld,
+        called: Option<Piece>,
+        consumed: Vec<Piece>,
+    },
+    /// Kan declared from four concealed pieces, listed in `consumed`
+    ConcealedKan {
+        seat: usize,
+        meld: Meld,
+        consumed: Vec<Piece>,
+    },
+    /// Pon upgraded to a kan with the `added` piece
+    ConvertedKan {
+        seat: usize,
+        meld: Meld,
+        added: Option<Piece>,
+    },
+    Riichi {
+        seat: usize,
+    },
+    Ron {
+        seat: usize,
+        from: Option<usize>,
+        piece: Option<Piece>,
+    },
+    Tsumo {
+        seat: usize,
+        piece: Option<Piece>,
+    },
+    ExhaustiveDraw,
+    RoundEnd {
+        round: i32,
+        points: [i32; 4],
+    },
+    GameEnd {
+        points: [i32; 4],
+    },
+}
+
+impl GameEvent {
+    /// Get the seat that performed the event, if it belongs to one
+    pub fn seat(&self) -> Option<usize> {
+        match *self {
+            GameEvent::Draw { seat, .. }
+            | GameEvent::Discard { seat, .. }
+            | GameEvent::Chi { seat, .. }
+            | GameEvent::Pon { seat, .. }
+            | GameEvent::Kan { seat, .. }
+            | GameEvent::ConcealedKan { seat, .. }
+            | GameEvent::ConvertedKan { seat, .. }
+            | GameEvent::Riichi { seat }
+            | GameEvent::Ron { seat, .. }
+            | GameEvent::Tsumo { seat, .. } => Some(seat),
+            _ => None,
+        }
+    }
+}
+
+fn seat_index(player: i32) -> Option<usize> {
+    usize::try_from(player).ok().filter(|&seat| seat < 4)
+}
+
+/// Pieces in `after` that are not in `before`, counted as multisets
+fn added_pieces(before: &[Piece], after: &[Piece]) -> Vec<Piece> {
+    let mut remaining = before.to_vec();
+    after
+        .iter()
+        .filter(|piece| match remaining.iter().position(|p| p == *piece) {
+            Some(i) => {
+                remaining.swap_remove(i);
+                false
+            }
+            None => true,
+        })
+        .copied()
+        .collect()
+}
+
+/// Take a piece of the same tile as `piece` out of `pieces`
+fn take_matching(pieces: &mut Vec<Piece>, piece: Piece) -> Option<Piece> {
+    let i = pieces.iter().position(|p| p.index() == piece.index())?;
+    Some(pieces.swap_remove(i))
+}
+
+/// Take the pieces of `meld` other than the `called` one out of the pieces
+/// that left the caller's hand. Pieces that cannot be found, e.g. because
+/// states were skipped, are assumed to be plain.
+fn take_consumed(meld: &Meld, called: Option<Piece>, removed: &mut Vec<Piece>) -> Vec<Piece> {
+    let mut pieces = meld.pieces();
+    if let Some(called) = called {
+        if let Some(i) = pieces.iter().position(|p| p.index() == called.index()) {
+            pieces.remove(i);
+        }
+    }
+    pieces
+        .into_iter()
+        .map(|piece| take_matching(removed, piece).unwrap_or(piece))
+        .collect()
+}
+
+/// Turns a sequence of observed states into [`GameEvent`]s
+#[derive(Debug, Default)]
+pub struct EventDiffer {
+    previous: Option<ObservedGameState>,
+    awaiting_deal: bool,
+    last_draw: [Option<Piece>; 4],
+}
+
+impl EventDiffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare the state with the previously pushed one and return the
+    /// events that happened in between
+    pub fn push(&mut self, state: &ObservedGameState) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+
+        if state.curr_state == StateFunctionType::RoundStart {
+            self.awaiting_deal = true;
+        }
+
+        let previous = self.previous.take();
+        let dealt = state.hands.iter().all(|hand| hand.live_piece_count() >= 13);
+        if self.awaiting_deal && dealt {
+            self.awaiting_deal = false;
+            self.last_draw = [None; 4];
+            events.push(GameEvent::Deal {
+                round: state.round_num,
+                counters: state.counters,
+                riichi_sticks: state.riichi_sticks,
+                hands: state.hands.clone().map(|hand| hand.live_pieces),
+            });
+        } else if let Some(previous) = previous.filter(|_| !self.awaiting_deal) {
+            self.diff_hands(&previous, state, &mut events);
+        }
+
+        self.state_events(state, &mut events);
+        self.previous = Some(state.clone());
+        events
+    }
+
+    fn diff_hands(
+        &mut self,
+        previous: &ObservedGameState,
+        state: &ObservedGameState,
+        events: &mut Vec<GameEvent>,
+    ) {
+        let discarder = seat_index(previous.current_player);
+
+        for seat in 0..4 {
+            let before = &previous.hands[seat];
+            let after = &state.hands[seat];
+            let from = discarder.filter(|&d| d != seat);
+
+            for piece in added_pieces(&before.live_pieces, &after.live_pieces) {
+                self.last_draw[seat] = Some(piece);
+                events.push(GameEvent::Draw { seat, piece });
+            }
+
+            // Pieces that left the hand, to tell which copies went into melds
+            let mut removed = added_pieces(&after.live_pieces, &before.live_pieces);
+            for (i, meld) in after.melds.iter().enumerate() {
+                let called = previous.pending_piece;
+                let event = match before.melds.get(i) {
+                    // A pon turning into a kan in place is an upgrade
+                    Some(old)
+                        if old.meld_type == MeldType::Pon && meld.meld_type == MeldType::Kan =>
+                    {
+                        GameEvent::ConvertedKan {
+                            seat,
+                            meld: meld.clone(),
+                            added: take_matching(&mut removed, meld.start),
+                        }
+                    }
+                    Some(_) => continue,
+                    None => match meld.meld_type {
+                        MeldType::Chi => GameEvent::Chi {
+                            seat,
+                            from,
+                            meld: meld.clone(),
+                            called,
+                            consumed: take_consumed(meld, called, &mut removed),
+                        },
+                        MeldType::Pon => GameEvent::Pon {
+                            seat,
+                            from,
+                            meld: meld.clone(),
+                            called,
+                            consumed: take_consumed(meld, called, &mut removed),
+                        },
+                        MeldType::Kan => GameEvent::Kan {
+                            seat,
+                            from,
+                            meld: meld.clone(),
+                            called,
+                            consumed: take_consumed(meld, called, &mut removed),
+                        },
+                        MeldType::ConcealedKan => GameEvent::ConcealedKan {
+                            seat,
+                            meld: meld.clone(),
+                            consumed: take_consumed(meld, None, &mut removed),
+                        },
+                    },
+                };
+                events.push(event);
+            }
+
+            if after.riichi && !before.riichi {
+                events.push(GameEvent::Riichi { seat });
+            }
+
+            for &piece in after.discards.iter().skip(before.discard_count()) {
+                events.push(GameEvent::Discard { seat, piece });
+            }
+        }
+    }
+
+    fn state_events(&mut self, state: &ObservedGameState, events: &mut Vec<GameEvent>) {
+        match state.curr_state {
+            StateFunctionType::Ron => {
+                let from = seat_index(state.current_player);
+                for (seat, _) in state.has_ronned.iter().enumerate().filter(|(_, &r)| r) {
+                    events.push(GameEvent::Ron {
+                        seat,
+                        from,
+                        piece: state.pending_piece,
+                    });
+                }
+            }
+            StateFunctionType::Tsumo => {
+                if let Some(seat) = seat_index(state.current_player) {
+                    events.push(GameEvent::Tsumo {
+                        seat,
+                        piece: self.last_draw[seat],
+                    });
+                }
+            }
+            StateFunctionType::Exhaust => events.push(GameEvent::ExhaustiveDraw),
+            StateFunctionType::RoundEnd => events.push(GameEvent::RoundEnd {
+                round: state.round_num,
+                points: state.points,
+            }),
+            StateFunctionType::GameEnd => events.push(GameEvent::GameEnd {
+                points: state.points,
+            }),
+            _ => {}
+        }
+    }
+}
+
+/// Convert a full sequence of observed states into an event log
+pub fn event_log<'a>(states: impl IntoIterator<Item = &'a ObservedGameState>) -> Vec<GameEvent> {
+    let mut differ = EventDiffer::new();
+    states
+        .into_iter()
+        .flat_map(|state| differ.push(state))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{parse_hand, parse_pieces};
+
+    fn state(
+        curr_state: StateFunctionType,
+        current_player: i32,
+        hands: [&str; 4],
+    ) -> ObservedGameState {
+        ObservedGameState {
+            current_player,
+            turn_num: 0,
+            round_num: 0,
+            riichi_sticks: 0,
+            counters: 0,
+            last_call: 0,
+            last_caller: 0,
+            concealed_kan: false,
+            seed: 0,
+            pending_piece: None,
+            scores: [0; 4],
+            points: [25000; 4],
+            has_ronned: [false; 4],
+            hands: hands.map(|h| parse_hand(h).unwrap()),
+            prev_state: StateFunctionType::Error,
+            curr_state,
+            next_state: StateFunctionType::Error,
+        }
+    }
+
+    fn with_discards(
+        mut state: ObservedGameState,
+        seat: usize,
+        discards: &str,
+    ) -> ObservedGameState {
+        state.hands[seat].discards = parse_pieces(discards).unwrap();
+        state
+    }
+
+    #[test]
+    fn derives_deal_draw_discard_and_call() {
+        let hands = [
+            "1112345678999m",
+            "1234567899p111z",
+            "123456789s1234z",
+            "1122334455667z",
+        ];
+        let mut differ = EventDiffer::new();
+
+        let dealt = state(StateFunctionType::RoundStart, 0, hands);
+        assert!(matches!(differ.push(&dealt)[..], [GameEvent::Deal { .. }]));
+
+        let east: Piece = "1z".parse().unwrap();
+        let mut drawn = dealt.clone();
+        drawn.curr_state = StateFunctionType::Draw;
+        drawn.hands[0].add_live_piece(east);
+        assert_eq!(
+            differ.push(&drawn),
+            [GameEvent::Draw {
+                seat: 0,
+                piece: east
+            }]
+        );
+
+        let mut discarded = with_discards(drawn.clone(), 0, "1z");
+        discarded.curr_state = StateFunctionType::Discard;
+        discarded.pending_piece = Some(east);
+        discarded.hands[0].live_pieces.pop();
+        assert_eq!(
+            differ.push(&discarded),
+            [GameEvent::Discard {
+                seat: 0,
+                piece: east
+            }]
+        );
+
+        // Seat 1 pons the discard with two of its own copies
+        let mut called = discarded.clone();
+        called.curr_state = StateFunctionType::Pon;
+        let caller = &mut called.hands[1];
+        for _ in 0..2 {
+            let held = caller.live_pieces.iter().position(|&p| p == east).unwrap();
+            caller.live_pieces.remove(held);
+        }
+        caller.melds.push(Meld {
+            meld_type: MeldType::Pon,
+            start: east,
+        });
+        caller.open = true;
+        assert_eq!(
+            differ.push(&called),
+            [GameEvent::Pon {
+                seat: 1,
+                from: Some(0),
+                meld: Meld {
+                    meld_type: MeldType::Pon,
+                    start: east
+                },
+                called: Some(east),
+                consumed: vec![east; 2],
+            }]
+        );
+    }
+
+    #[test]
+    fn calls_keep_red_fives() {
+        let hands = [
+            "1112345678999m",
+            "0555p123456789s",
+            "123456789s1234z",
+            "1122334455667z",
+        ];
+        let mut differ = EventDiffer::new();
+        let dealt = state(StateFunctionType::RoundStart, 0, hands);
+        differ.push(&dealt);
+
+        let five: Piece = "5p".parse().unwrap();
+        let mut discarded = with_discards(dealt, 0, "5p");
+        discarded.curr_state = StateFunctionType::Discard;
+        discarded.pending_piece = Some(five);
+        differ.push(&discarded);
+
+        // The red five goes into the meld along with one plain five
+        let mut called = discarded.clone();
+        called.curr_state = StateFunctionType::Pon;
+        let caller = &mut called.hands[1];
+        caller.live_pieces = parse_pieces("55p123456789s").unwrap();
+        caller.melds.push(Meld {
+            meld_type: MeldType::Pon,
+            start: five,
+        });
+        let events = differ.push(&called);
+        let [GameEvent::Pon {
+            called, consumed, ..
+        }] = &events[..]
+        else {
+            panic!("expected a pon, got {events:?}");
+        };
+        assert_eq!(*called, Some(five));
+        assert_eq!(consumed.len(), 2);
+        assert!(consumed.contains(&Piece::red_five(crate::piece::Suit::Pin)));
+        assert!(consumed.contains(&five));
+    }
+
+    #[test]
+    fn derives_terminal_events() {
+        let hands = ["1m", "2m", "3m", "4m"];
+        let mut ron = state(StateFunctionType::Ron, 2, hands);
+        ron.has_ronned = [true, false, false, true];
+
+        let log = event_log(&[ron, state(StateFunctionType::GameEnd, 0, hands)]);
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[0].seat(), Some(0));
+        assert_eq!(log[1].seat(), Some(3));
+        assert_eq!(log[2], GameEvent::GameEnd { points: [25000; 4] });
+    }
+}
@@ -11,4 +11,27 @@ pub enum MahjongFFIError {
     MutexPoisoned,
     #[error("Game state was already consumed")]
     GameStateConsumed,
+    #[error("Invalid piece encoding {0:#x}")]
+    InvalidPiece(i32),
+    #[error("Controller {0} is implemented in Rust, which libmahjong cannot seat; use the engine backend")]
+    RustControllerOnNative(String),
+    #[error("The game did not end within {0} steps")]
+    StepLimitExceeded(usize),
+    #[error("Failed to start a match, StartGame returned {0}")]
+    FailedToStartMatch(i32),
+    #[error("The game was cancelled")]
+    Cancelled,
+    #[error("Advancing the game took longer than {0:?}")]
+    TimedOut(std::time::Duration),
+    #[error("Blocking task failed: {0}")]
+    BlockingTaskFailed(String),
+    #[error("Illegal transition from {from:?} to {to:?}")]
+    IllegalTransition {
+        from: crate::observe::StateFunctionType,
+        to: crate::observe::StateFunctionType,
+    },
+    #[error("The game is played by the engine and has no libmahjong state")]
+    NotNativeState,
+    #[error("Invalid rules: {0}")]
+    InvalidRules(#[from] crate::rules::RuleError),
 }
@@ -0,0 +1,113 @@
+use std::ffi::c_int;
+
+use super::{
+    error::MahjongFFIError,
+    gamesettings::OwnedCGameSettings,
+    gamestate::{exit_game, start_game},
+};
+
+/// How the native library runs a match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// `StartGame` returns once the match has been played out
+    Sync,
+    /// `StartGame` returns immediately and the match runs on a native thread
+    Async,
+}
+
+/// Lifecycle of a [`Match`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchStatus {
+    /// Running on a native thread. libmahjong does not report when an async
+    /// match finishes, so it stays running until it is exited.
+    Running,
+    /// Played to completion by a sync start
+    Finished,
+    /// Stopped through `ExitGame`
+    Exited,
+}
+
+/// Handle to a match started through `StartGame`. Dropping a running match
+/// calls `ExitGame`.
+///
+/// `StartGame` does not report how the match ended. To get final points and
+/// placements, play the game through [`crate::runner::GameRunner`] instead.
+pub struct Match {
+    id: c_int,
+    mode: MatchMode,
+    status: MatchStatus,
+    // The native library may read the settings for as long as the match runs
+    _settings: Box<OwnedCGameSettings>,
+}
+
+impl Match {
+    /// Start a match with the given settings
+    pub fn start<S: TryInto<OwnedCGameSettings, Error = MahjongFFIError>>(
+        settings: S,
+        mode: MatchMode,
+    ) -> Result<Self, MahjongFFIError> {
+        let settings: Box<OwnedCGameSettings> = Box::new(settings.try_into()?);
+        settings.reject_rust_controllers()?;
+        let id = start_game(settings.as_raw(), mode == MatchMode::Async);
+        if id < 0 {
+            return Err(MahjongFFIError::FailedToStartMatch(id));
+        }
+
+        let status = match mode {
+            MatchMode::Sync => MatchStatus::Finished,
+            MatchMode::Async => MatchStatus::Running,
+        };
+        Ok(Self {
+            id,
+            mode,
+            status,
+            _settings: settings,
+        })
+    }
+
+    /// Play a match to completion on the calling thread
+    pub fn run<S: TryInto<OwnedCGameSettings, Error = MahjongFFIError>>(
+        settings: S,
+    ) -> Result<Self, MahjongFFIError> {
+        Self::start(settings, MatchMode::Sync)
+    }
+
+    /// Start a match on a native thread
+    pub fn spawn<S: TryInto<OwnedCGameSettings, Error = MahjongFFIError>>(
+        settings: S,
+    ) -> Result<Self, MahjongFFIError> {
+        Self::start(settings, MatchMode::Async)
+    }
+
+    /// Get the id assigned by the native library
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn mode(&self) -> MatchMode {
+        self.mode
+    }
+
+    pub fn status(&self) -> MatchStatus {
+        self.status
+    }
+
+    /// Check if the match was played to completion
+    pub fn is_finished(&self) -> bool {
+        self.status == MatchStatus::Finished
+    }
+
+    /// Stop the match if it is still running
+    pub fn exit(&mut self) {
+        if self.status == MatchStatus::Running {
+            exit_game(self.id);
+            self.status = MatchStatus::Exited;
+        }
+    }
+}
+
+impl Drop for Match {
+    fn drop(&mut self) {
+        self.exit();
+    }
+}
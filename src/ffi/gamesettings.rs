@@ -1,36 +1,145 @@
 use std::ffi::{c_char, c_int, CString};
 
+use crate::rules::{GameLength, LeftoverRiichi, RuleSet};
 use crate::settings::GameSettings;
 
 use super::error::MahjongFFIError;
 
+/// C-compatible rule set
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CRuleSet {
+    pub hanchan: bool,
+    pub starting_points: c_int,
+    pub red_fives: bool,
+    pub open_tanyao: bool,
+    pub double_ron: bool,
+    pub tobi: bool,
+    pub leftover_riichi_to_first: bool,
+}
+
+impl From<&RuleSet> for CRuleSet {
+    fn from(rules: &RuleSet) -> Self {
+        Self {
+            hanchan: rules.length == GameLength::Hanchan,
+            starting_points: rules.starting_points,
+            red_fives: rules.red_fives,
+            open_tanyao: rules.open_tanyao,
+            double_ron: rules.double_ron,
+            tobi: rules.tobi,
+            leftover_riichi_to_first: rules.leftover_riichi == LeftoverRiichi::ToFirstPlace,
+        }
+    }
+}
+
 /// C-compatible GameSettings structure
 #[repr(C)]
 pub struct CGameSettings {
     pub seed: u64,
     pub seat_controllers: [*const c_char; 4],
     pub num_controllers: c_int,
+    /// Comes after the fields every libmahjong version reads, so builds
+    /// that do not know about rules still find the seed and controllers
+    pub rules: CRuleSet,
 }
 
-fn try_string_to_cstring(s: &str) -> Result<*const c_char, MahjongFFIError> {
-    CString::new(s)
-        .map(|cstr| cstr.into_raw() as *const c_char)
-        .map_err(|_| MahjongFFIError::FailedToCreateCString)
+fn try_string_to_cstring(s: &str) -> Result<CString, MahjongFFIError> {
+    CString::new(s).map_err(|_| MahjongFFIError::FailedToCreateCString)
 }
 
-impl TryFrom<GameSettings> for CGameSettings {
+/// Owning guard for [`CGameSettings`]. The controller name pointers borrow
+/// from the `CString`s held here, so they stay valid for as long as the guard
+/// lives and are freed when it is dropped.
+pub struct OwnedCGameSettings {
+    raw: CGameSettings,
+    seat_controllers: [CString; 4],
+}
+
+impl OwnedCGameSettings {
+    /// Get the C settings to pass across the FFI boundary. The pointers
+    /// inside must not outlive `self`.
+    pub fn as_raw(&self) -> &CGameSettings {
+        &self.raw
+    }
+
+    /// Get the controller names of each seat
+    pub fn seat_controllers(&self) -> impl Iterator<Item = &str> {
+        self.seat_controllers
+            .iter()
+            .map(|name| name.to_str().unwrap_or_default())
+    }
+
+    /// Fail if a seat names a registered Rust controller, which libmahjong
+    /// cannot seat
+    pub(crate) fn reject_rust_controllers(&self) -> Result<(), MahjongFFIError> {
+        match self
+            .seat_controllers()
+            .find(|name| crate::controller::is_registered(name))
+        {
+            Some(name) => Err(MahjongFFIError::RustControllerOnNative(name.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TryFrom<GameSettings> for OwnedCGameSettings {
     type Error = MahjongFFIError;
 
     fn try_from(value: GameSettings) -> Result<Self, Self::Error> {
+        value.rules.validate()?;
+
+        let seat_controllers = [
+            try_string_to_cstring(&value.seat_controllers[0])?,
+            try_string_to_cstring(&value.seat_controllers[1])?,
+            try_string_to_cstring(&value.seat_controllers[2])?,
+            try_string_to_cstring(&value.seat_controllers[3])?,
+        ];
+
         Ok(Self {
-            seed: value.seed,
-            seat_controllers: [
-                try_string_to_cstring(&value.seat_controllers[0])?,
-                try_string_to_cstring(&value.seat_controllers[1])?,
-                try_string_to_cstring(&value.seat_controllers[2])?,
-                try_string_to_cstring(&value.seat_controllers[3])?,
-            ],
-            num_controllers: 4,
+            raw: CGameSettings {
+                seed: value.seed,
+                // CString buffers live on the heap, so moving the guard keeps
+                // these pointers valid
+                seat_controllers: seat_controllers.each_ref().map(|name| name.as_ptr()),
+                num_controllers: 4,
+                rules: (&value.rules).into(),
+            },
+            seat_controllers,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(seed: u64) -> GameSettings {
+        GameSettings {
+            seed,
+            seat_controllers: std::array::from_fn(|_| "AlphabeticalBot".to_string()),
+            rules: Default::default(),
+        }
+    }
+
+    #[test]
+    fn controller_names_outlive_the_move_into_the_guard() {
+        let owned = OwnedCGameSettings::try_from(settings(1)).unwrap();
+        let moved = Box::new(owned);
+        for name in moved.as_raw().seat_controllers {
+            let name = unsafe { std::ffi::CStr::from_ptr(name) };
+            assert_eq!(name.to_str().unwrap(), "AlphabeticalBot");
+        }
+    }
+
+    #[test]
+    fn rules_are_handed_over() {
+        let mut custom = settings(1);
+        custom.rules = RuleSet::wrc().tonpuusen();
+        let owned = OwnedCGameSettings::try_from(custom).unwrap();
+        let rules = owned.as_raw().rules;
+        assert!(!rules.hanchan);
+        assert_eq!(rules.starting_points, 30000);
+        assert!(!rules.red_fives);
+        assert!(!rules.leftover_riichi_to_first);
+    }
+}
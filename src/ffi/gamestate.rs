@@ -1,8 +1,14 @@
 use std::ffi::c_int;
-use std::sync::{Arc, Mutex, MutexGuard};
-
-use super::{error::MahjongFFIError, gamesettings::CGameSettings, observe::ObserveGameState};
+use std::sync::{Mutex, MutexGuard};
+
+use super::{
+    error::MahjongFFIError,
+    gamesettings::{CGameSettings, OwnedCGameSettings},
+    observe::ObserveGameState,
+};
+use crate::engine::{self, Engine};
 use crate::observe::ObservedGameState;
+use crate::settings::GameSettings;
 
 /// Opaque type representing a GameState
 #[repr(C)]
@@ -11,6 +17,7 @@ pub struct RawGameState {
     _marker: std::marker::PhantomData<(*mut u8, std::marker::PhantomPinned)>,
 }
 
+#[cfg(feature = "native")]
 #[link(name = "mahjong")]
 extern "C" {
     /// Start a game with the given settings
@@ -29,9 +36,24 @@ extern "C" {
     pub fn FreeGameState(state: *mut RawGameState);
 }
 
+#[cfg(not(feature = "native"))]
+pub use super::mock::{AdvanceGameState, ExitGame, FreeGameState, InitGameState, StartGame};
+
+/// Which implementation plays a [`GameState`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// libmahjong through the C API, or the mock backend without the
+    /// `native` feature
+    #[default]
+    Native,
+    /// The pure-Rust [`Engine`]
+    Engine,
+}
+
 /// Safe wrapper for GameState
 pub struct GameState {
-    ptr: Arc<Mutex<Option<*mut RawGameState>>>,
+    ptr: Mutex<Option<*mut RawGameState>>,
+    backend: Backend,
 }
 
 // Safe due to the use of mutexes
@@ -40,22 +62,38 @@ unsafe impl Sync for GameState {}
 
 impl GameState {
     /// Create a new game state from settings
-    pub fn new<S: TryInto<CGameSettings, Error = MahjongFFIError>>(
+    pub fn new<S: TryInto<OwnedCGameSettings, Error = MahjongFFIError>>(
         settings: S,
     ) -> Result<Self, MahjongFFIError> {
-        let ptr = unsafe {
-            let settings: CGameSettings = settings.try_into()?;
-            Ok(InitGameState(&settings))
-        }?;
+        let settings: OwnedCGameSettings = settings.try_into()?;
+        settings.reject_rust_controllers()?;
+        let ptr = unsafe { InitGameState(settings.as_raw()) };
         if ptr.is_null() {
             Err(MahjongFFIError::FailedToAllocateGameState)
         } else {
             Ok(Self {
-                ptr: Arc::new(Mutex::new(Some(ptr))),
+                ptr: Mutex::new(Some(ptr)),
+                backend: Backend::Native,
             })
         }
     }
 
+    /// Create a new game state played by the given backend
+    pub fn with_backend(settings: GameSettings, backend: Backend) -> Result<Self, MahjongFFIError> {
+        match backend {
+            Backend::Native => Self::new(settings),
+            Backend::Engine => Ok(Self {
+                ptr: Mutex::new(Some(engine::into_raw(Engine::new(&settings)?))),
+                backend,
+            }),
+        }
+    }
+
+    /// Get the backend playing this game
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
     /// Advance the game state
     pub fn advance(self) -> Result<Self, MahjongFFIError> {
         let mut guard = self
@@ -66,14 +104,18 @@ impl GameState {
         let take_ptr = guard.take();
 
         if let Some(ptr) = take_ptr {
-            let new_ptr = unsafe { AdvanceGameState(ptr) };
+            let new_ptr = match self.backend {
+                Backend::Native => unsafe { AdvanceGameState(ptr) },
+                Backend::Engine => unsafe { engine::advance_raw(ptr) },
+            };
             let _ = ptr; // Prevent double-free since C++ takes ownership
 
             if new_ptr.is_null() {
                 Err(MahjongFFIError::GameEnded)
             } else {
                 Ok(Self {
-                    ptr: Arc::new(Mutex::new(Some(new_ptr))),
+                    ptr: Mutex::new(Some(new_ptr)),
+                    backend: self.backend,
                 })
             }
         } else {
@@ -83,24 +125,33 @@ impl GameState {
 
     /// Observe the current game state
     pub fn observe(&self) -> Option<ObservedGameState> {
-        let guard = self.ptr.lock().ok()?;
+        self.try_observe().ok()
+    }
+
+    /// Observe the current game state, reporting why observation failed
+    pub fn try_observe(&self) -> Result<ObservedGameState, MahjongFFIError> {
+        let guard = self
+            .ptr
+            .lock()
+            .map_err(|_| MahjongFFIError::MutexPoisoned)?;
 
         if let Some(ptr) = *guard {
-            let c_observed = unsafe { ObserveGameState(ptr) };
-            Some(c_observed.into())
+            match self.backend {
+                Backend::Native => unsafe { ObserveGameState(ptr) }.try_into(),
+                Backend::Engine => Ok(unsafe { engine::observe_raw(ptr) }),
+            }
         } else {
-            None
+            Err(MahjongFFIError::GameStateConsumed)
         }
     }
 
-    /// Get the raw pointer (sync version for internal use)
-    pub fn as_ptr(
-        &self,
-    ) -> Result<
-        MutexGuard<'_, Option<*mut RawGameState>>,
-        std::sync::PoisonError<MutexGuard<'_, Option<*mut RawGameState>>>,
-    > {
-        self.ptr.lock()
+    /// Get the raw pointer (sync version for internal use). Engine games
+    /// have no libmahjong state to hand out.
+    pub fn as_ptr(&self) -> Result<MutexGuard<'_, Option<*mut RawGameState>>, MahjongFFIError> {
+        if self.backend == Backend::Engine {
+            return Err(MahjongFFIError::NotNativeState);
+        }
+        self.ptr.lock().map_err(|_| MahjongFFIError::MutexPoisoned)
     }
 }
 
@@ -109,7 +160,10 @@ impl Drop for GameState {
         // Now we can safely lock synchronously in Drop
         if let Ok(mut guard) = self.ptr.lock() {
             if let Some(ptr) = guard.take() {
-                unsafe { FreeGameState(ptr) };
+                match self.backend {
+                    Backend::Native => unsafe { FreeGameState(ptr) },
+                    Backend::Engine => unsafe { engine::free_raw(ptr) },
+                }
             }
         }
         // If the mutex is poisoned, we can't clean up safely
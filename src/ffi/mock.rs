@@ -0,0 +1,356 @@
+//! Pure-Rust stand-in for libmahjong, used when the `native` feature is
+//! disabled.
+//!
+//! The functions here mirror the C API so the safe wrappers work unchanged.
+//! Games are simplified: the wall is shuffled from the seed, nobody calls or
+//! wins, and every round is cut short to [`DRAWS_PER_ROUND`] draws before
+//! ending in an exhaustive draw without payments. Only
+//! the native bot names are accepted, and they always discard the piece they
+//! drew. This is enough to exercise the state machine, not to evaluate play.
+
+#![allow(non_snake_case)]
+
+use std::collections::HashSet;
+use std::ffi::{c_int, CStr};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use super::gamesettings::{CGameSettings, CRuleSet};
+use super::gamestate::RawGameState;
+use super::observe::{
+    CHand, CObservedGameState, CStateFunctionType, MAX_DISCARDS_PER_PLAYER, MAX_LIVE_HAND_SIZE,
+};
+use crate::controller::NATIVE_CONTROLLERS;
+use crate::engine::wall::{tile_set, Rng};
+use crate::engine::STARTING_HAND_SIZE;
+use crate::piece::Piece;
+
+/// Draws before a mock round ends, six per player
+pub const DRAWS_PER_ROUND: usize = 24;
+
+fn exited_games() -> &'static Mutex<HashSet<c_int>> {
+    static EXITED: OnceLock<Mutex<HashSet<c_int>>> = OnceLock::new();
+    EXITED.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+static NEXT_GAME_ID: AtomicI32 = AtomicI32::new(0);
+
+struct MockGame {
+    seed: u64,
+    rng: Rng,
+    rules: CRuleSet,
+    wall: Vec<Piece>,
+    hands: [Vec<Piece>; 4],
+    discards: [Vec<Piece>; 4],
+    drawn: Option<Piece>,
+    chosen: Option<Piece>,
+    draws: usize,
+    round: c_int,
+    counters: c_int,
+    current_player: c_int,
+    turn_num: c_int,
+    points: [c_int; 4],
+    prev_state: CStateFunctionType,
+    curr_state: CStateFunctionType,
+}
+
+impl MockGame {
+    fn rounds(&self) -> c_int {
+        if self.rules.hanchan {
+            8
+        } else {
+            4
+        }
+    }
+
+    fn dealer(&self) -> usize {
+        self.round.rem_euclid(4) as usize
+    }
+
+    fn seat(&self) -> usize {
+        self.current_player.rem_euclid(4) as usize
+    }
+
+    fn next_state(&self) -> CStateFunctionType {
+        use CStateFunctionType::*;
+        match self.curr_state {
+            Error => GameStart,
+            GameStart => RoundStart,
+            RoundStart => Draw,
+            Draw => PlayerHand,
+            PlayerHand => Discard,
+            Discard if self.draws < DRAWS_PER_ROUND => Draw,
+            Discard => Exhaust,
+            Exhaust => RoundEnd,
+            RoundEnd if self.round < self.rounds() => RoundStart,
+            _ => GameEnd,
+        }
+    }
+
+    fn build_wall(&mut self) {
+        self.wall = tile_set(self.rules.red_fives);
+        self.rng.shuffle(&mut self.wall);
+    }
+
+    fn enter(&mut self, state: CStateFunctionType) {
+        use CStateFunctionType::*;
+        match state {
+            GameStart => {
+                self.points = [self.rules.starting_points; 4];
+                self.current_player = 0;
+            }
+            RoundStart => {
+                self.build_wall();
+                self.discards = Default::default();
+                self.drawn = None;
+                self.draws = 0;
+                self.current_player = self.dealer() as c_int;
+                for hand in &mut self.hands {
+                    *hand = self.wall.split_off(self.wall.len() - STARTING_HAND_SIZE);
+                    hand.sort();
+                }
+            }
+            Draw => {
+                if self.curr_state == Discard {
+                    self.current_player = (self.current_player + 1) % 4;
+                }
+                let seat = self.seat();
+                self.drawn = self.wall.pop();
+                self.draws += 1;
+                self.hands[seat].extend(self.drawn);
+            }
+            // Every seat is a native bot name, which discards the drawn piece
+            PlayerHand => self.chosen = self.drawn.or(self.hands[self.seat()].last().copied()),
+            Discard => {
+                let seat = self.seat();
+                if let Some(piece) = self.chosen.take() {
+                    if let Some(i) = self.hands[seat].iter().position(|&p| p == piece) {
+                        self.hands[seat].remove(i);
+                        self.hands[seat].sort();
+                        self.discards[seat].push(piece);
+                    }
+                }
+                self.drawn = None;
+                self.turn_num += 1;
+            }
+            RoundEnd => {
+                // Nobody is tenpai in a mock game, so the deal always passes
+                self.round += 1;
+                self.counters += 1;
+            }
+            _ => {}
+        }
+        self.prev_state = self.curr_state;
+        self.curr_state = state;
+    }
+
+    /// Move to the next state, returning false once the game is over
+    fn advance(&mut self) -> bool {
+        if self.curr_state == CStateFunctionType::GameEnd {
+            return false;
+        }
+        let next = self.next_state();
+        self.enter(next);
+        true
+    }
+
+    fn observe(&self) -> CObservedGameState {
+        let hands = std::array::from_fn(|seat| {
+            let mut hand = CHand::default();
+            for (slot, &piece) in hand
+                .live_pieces
+                .iter_mut()
+                .zip(self.hands[seat].iter().take(MAX_LIVE_HAND_SIZE))
+            {
+                *slot = piece.into();
+            }
+            hand.live_piece_count = self.hands[seat].len().min(MAX_LIVE_HAND_SIZE) as c_int;
+            for (slot, &piece) in hand
+                .discards
+                .iter_mut()
+                .zip(self.discards[seat].iter().take(MAX_DISCARDS_PER_PLAYER))
+            {
+                *slot = piece.into();
+            }
+            hand.discard_count = self.discards[seat].len().min(MAX_DISCARDS_PER_PLAYER) as c_int;
+            hand
+        });
+
+        CObservedGameState {
+            current_player: self.current_player,
+            turn_num: self.turn_num,
+            round_num: self.round,
+            counters: self.counters,
+            seed: self.seed,
+            points: self.points,
+            hands,
+            prev_state: self.prev_state,
+            curr_state: self.curr_state,
+            next_state: self.next_state(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Check that a seat names one of the native bots
+fn is_native_controller(name: &str) -> bool {
+    NATIVE_CONTROLLERS.iter().any(|(native, _)| *native == name)
+}
+
+unsafe fn new_game(settings: *const CGameSettings) -> Option<Box<MockGame>> {
+    let settings = settings.as_ref()?;
+
+    let game = Box::new(MockGame {
+        seed: settings.seed,
+        rng: Rng::new(settings.seed),
+        rules: settings.rules,
+        wall: Vec::new(),
+        hands: Default::default(),
+        discards: Default::default(),
+        drawn: None,
+        chosen: None,
+        draws: 0,
+        round: 0,
+        counters: 0,
+        current_player: -1,
+        turn_num: 0,
+        points: [0; 4],
+        prev_state: CStateFunctionType::Error,
+        curr_state: CStateFunctionType::Error,
+    });
+    for &name in &settings.seat_controllers {
+        if name.is_null() || !is_native_controller(CStr::from_ptr(name).to_str().ok()?) {
+            return None;
+        }
+    }
+    Some(game)
+}
+
+/// Create a game, returning null if a seat controller is unknown
+///
+/// # Safety
+/// `settings` must point to valid settings whose controller names are valid
+/// C strings.
+pub unsafe fn InitGameState(settings: *const CGameSettings) -> *mut RawGameState {
+    match new_game(settings) {
+        Some(game) => Box::into_raw(game) as *mut RawGameState,
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Advance a game, taking ownership of `state`. Returns null once the game
+/// has ended.
+///
+/// # Safety
+/// `state` must come from [`InitGameState`] or [`AdvanceGameState`] and not
+/// have been freed.
+pub unsafe fn AdvanceGameState(state: *mut RawGameState) -> *mut RawGameState {
+    let game = &mut *(state as *mut MockGame);
+    if game.advance() {
+        state
+    } else {
+        FreeGameState(state);
+        std::ptr::null_mut()
+    }
+}
+
+/// Observe a game
+///
+/// # Safety
+/// `state` must be a live game state.
+pub unsafe fn ObserveGameState(state: *mut RawGameState) -> CObservedGameState {
+    (*(state as *const MockGame)).observe()
+}
+
+/// Free a game
+///
+/// # Safety
+/// `state` must be a live game state and is invalid afterwards.
+pub unsafe fn FreeGameState(state: *mut RawGameState) {
+    if !state.is_null() {
+        drop(Box::from_raw(state as *mut MockGame));
+    }
+}
+
+fn play_to_end(id: c_int, mut game: Box<MockGame>) {
+    let exited = || {
+        exited_games()
+            .lock()
+            .map(|exited| exited.contains(&id))
+            .unwrap_or(true)
+    };
+    while !exited() && game.advance() {}
+}
+
+/// Play a game, on a new thread when `async_mode` is set. Returns the game
+/// id, or -1 if the settings are rejected.
+///
+/// # Safety
+/// `settings` must point to valid settings whose controller names are valid
+/// C strings.
+pub unsafe fn StartGame(settings: *const CGameSettings, async_mode: bool) -> c_int {
+    let Some(game) = new_game(settings) else {
+        return -1;
+    };
+    let id = NEXT_GAME_ID.fetch_add(1, Ordering::Relaxed);
+
+    if async_mode {
+        std::thread::spawn(move || play_to_end(id, game));
+    } else {
+        play_to_end(id, game);
+    }
+    id
+}
+
+/// Stop an async game after its current state
+///
+/// # Safety
+/// Always safe to call; unsafe to match the native declaration.
+pub unsafe fn ExitGame(game: c_int) {
+    if let Ok(mut exited) = exited_games().lock() {
+        exited.insert(game);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ffi::gamestate::GameState;
+    use crate::observe::StateFunctionType;
+    use crate::runner::GameRunner;
+    use crate::settings::GameSettings;
+
+    fn settings(seed: u64, controller: &str) -> GameSettings {
+        GameSettings::builder()
+            .seed(seed)
+            .all_seats(controller)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn plays_a_seeded_game_to_the_end() {
+        let states: Vec<_> = GameRunner::from_settings(settings(5, "AlphabeticalBot"))
+            .unwrap()
+            .collect();
+        let again: Vec<_> = GameRunner::from_settings(settings(5, "AlphabeticalBot"))
+            .unwrap()
+            .collect();
+        assert_eq!(states, again);
+
+        let last = states.last().unwrap();
+        assert_eq!(last.curr_state, StateFunctionType::GameEnd);
+        assert_eq!(last.round_num, 8);
+        assert_eq!(last.points, [25000; 4]);
+
+        let dealt = &states[2];
+        assert_eq!(dealt.curr_state, StateFunctionType::RoundStart);
+        assert!(dealt.hands.iter().all(|hand| hand.live_piece_count() == 13));
+
+        let unknown = GameSettings::builder()
+            .all_seats("NoSuchBot")
+            .allow_unknown_controllers()
+            .build()
+            .unwrap();
+        assert!(GameState::new(unknown).is_err());
+    }
+}
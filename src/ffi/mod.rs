@@ -1,10 +1,14 @@
 pub mod error;
+pub mod gamematch;
 pub mod gamesettings;
 pub mod gamestate;
+#[cfg(not(feature = "native"))]
+pub mod mock;
 pub mod observe;
 
 #[cfg(test)]
 mod tests {
+    use crate::ffi::gamematch::{Match, MatchMode, MatchStatus};
     use crate::ffi::gamestate::GameState;
     use crate::observe::StateFunctionType;
     use crate::settings::GameSettings;
@@ -21,6 +25,7 @@ mod tests {
                 "AlphabeticalBot".to_string(),
                 "AlphabeticalBot".to_string(),
             ],
+            rules: Default::default(),
         };
 
         let game_state = GameState::new(settings)?;
@@ -46,6 +51,7 @@ mod tests {
                 "AlphabeticalBot".to_string(),
                 "AlphabeticalBot".to_string(),
             ],
+            rules: Default::default(),
         };
 
         let mut game_state = GameState::new(settings)?;
@@ -81,6 +87,7 @@ mod tests {
                 "AlphabeticalBot".to_string(),
                 "AlphabeticalBot".to_string(),
             ],
+            rules: Default::default(),
         };
 
         let game_state = GameState::new(settings)?;
@@ -125,6 +132,7 @@ mod tests {
                     "AlphabeticalBot".to_string(),
                     "AlphabeticalBot".to_string(),
                 ],
+                rules: Default::default(),
             };
             game_states.push(GameState::new(settings)?);
         }
@@ -153,4 +161,26 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn can_run_match_handle() -> anyhow::Result<()> {
+        let settings = || {
+            GameSettings::builder()
+                .seed(12345)
+                .all_seats("AlphabeticalBot")
+                .build()
+        };
+
+        let finished = Match::run(settings()?)?;
+        assert!(finished.is_finished());
+        assert_eq!(finished.status(), MatchStatus::Finished);
+
+        let mut running = Match::spawn(settings()?)?;
+        assert_eq!(running.mode(), MatchMode::Async);
+        assert!(running.id() >= 0);
+        running.exit();
+        assert_eq!(running.status(), MatchStatus::Exited);
+
+        Ok(())
+    }
 }
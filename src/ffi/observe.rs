@@ -1,3 +1,4 @@
+#[cfg(feature = "native")]
 use super::gamestate::RawGameState;
 use std::ffi::c_int;
 
@@ -142,8 +143,12 @@ impl Default for CObservedGameState {
 }
 
 // FFI function declaration
+#[cfg(feature = "native")]
 #[link(name = "mahjong")]
 extern "C" {
     /// Observe the current game state
     pub fn ObserveGameState(state: *mut RawGameState) -> CObservedGameState;
 }
+
+#[cfg(not(feature = "native"))]
+pub use super::mock::ObserveGameState;
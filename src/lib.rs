@@ -1,12 +1,38 @@
 // FFI (Foreign Function Interface) for Mahjong game controller
 
+pub mod analysis;
+#[cfg(feature = "async")]
+pub mod async_runner;
+pub mod controller;
+pub mod differential;
+pub mod engine;
+pub mod events;
 pub mod ffi;
+#[cfg(feature = "mjai")]
+pub mod mjai;
+pub mod notation;
 pub mod observe;
+pub mod piece;
+pub mod rules;
+pub mod runner;
+pub mod scoring;
 pub mod settings;
+pub mod simulate;
+#[cfg(feature = "serde")]
+pub mod snapshot;
+pub mod state_machine;
+#[cfg(feature = "tenhou")]
+pub mod tenhou;
+pub mod tournament;
+pub mod validate;
+pub mod view;
 
 #[cfg(test)]
 mod tests {
-    use crate::{ffi::gamestate::GameState, observe::StateFunctionType, settings::GameSettings};
+    use crate::{
+        ffi::gamestate::GameState, observe::StateFunctionType, runner::GameRunner,
+        settings::GameSettings,
+    };
     use std::{collections::VecDeque, fs::File, io::Write};
 
     #[test]
@@ -19,6 +45,7 @@ mod tests {
                 "AngryDiscardoBot".to_string(),
                 "AngryDiscardoBot".to_string(),
             ],
+            rules: Default::default(),
         };
 
         let mut log = File::create("test_match.log").unwrap();
@@ -76,4 +103,31 @@ mod tests {
             StateFunctionType::GameEnd
         );
     }
+
+    #[test]
+    fn can_run_match_with_runner() {
+        let settings = GameSettings {
+            seed: 1,
+            seat_controllers: [
+                "AngryDiscardoBot".to_string(),
+                "AngryDiscardoBot".to_string(),
+                "AngryDiscardoBot".to_string(),
+                "AngryDiscardoBot".to_string(),
+            ],
+            rules: Default::default(),
+        };
+
+        let summary = GameRunner::from_settings(settings)
+            .unwrap()
+            .with_step_limit(1000)
+            .run()
+            .unwrap();
+
+        assert!(summary.reached_game_end);
+        assert!(summary.rounds > 0);
+        assert_eq!(
+            summary.final_state.current_state(),
+            StateFunctionType::GameEnd
+        );
+    }
 }
@@ -0,0 +1,1303 @@
+//! MJAI JSON line protocol support.
+//!
+//! [`MjaiEncoder`] turns the crate's [`GameEvent`] log into MJAI messages,
+//! and [`MjaiBot`] runs an external MJAI bot as a local subprocess behind
+//! the [`Controller`] trait so it can be registered as a seat controller.
+//!
+//! Tiles use MJAI notation: `1m`-`9m`, `1p`-`9p`, `1s`-`9s`, `5mr`/`5pr`/`5sr`
+//! for red fives, `E S W N` for winds, `P F C` for the white, green and red
+//! dragons and `?` for hidden tiles.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::controller::{Call, CallOption, Controller, Decision, Event, EventKind};
+use crate::events::GameEvent;
+use crate::observe::{Meld, MeldType};
+use crate::piece::{Honor, Piece, Suit};
+use crate::view::PlayerView;
+
+/// Placeholder for a tile the receiver is not allowed to see
+pub const HIDDEN_TILE: &str = "?";
+
+/// How long [`MjaiBot`] waits for a response line by default
+pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors produced by the MJAI adapter
+#[derive(Debug, thiserror::Error)]
+pub enum MjaiError {
+    #[error("Failed to communicate with MJAI bot: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid MJAI message: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid MJAI tile '{0}'")]
+    InvalidTile(String),
+    #[error("MJAI bot closed its output")]
+    BotExited,
+    #[error("MJAI bot did not respond within {0:?}")]
+    TimedOut(Duration),
+}
+
+/// A single MJAI protocol message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MjaiMessage {
+    StartGame {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<usize>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        names: Vec<String>,
+    },
+    StartKyoku {
+        bakaze: String,
+        kyoku: u8,
+        honba: u32,
+        kyotaku: u32,
+        oya: usize,
+        /// Left out when the first dora indicator is not known, as when
+        /// encoding an event log, which has no dora indicators
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dora_marker: Option<String>,
+        tehais: Vec<Vec<String>>,
+    },
+    Tsumo {
+        actor: usize,
+        pai: String,
+    },
+    Dahai {
+        actor: usize,
+        pai: String,
+        #[serde(default)]
+        tsumogiri: bool,
+    },
+    Chi {
+        actor: usize,
+        target: usize,
+        pai: String,
+        consumed: Vec<String>,
+    },
+    Pon {
+        actor: usize,
+        target: usize,
+        pai: String,
+        consumed: Vec<String>,
+    },
+    Daiminkan {
+        actor: usize,
+        target: usize,
+        pai: String,
+        consumed: Vec<String>,
+    },
+    Ankan {
+        actor: usize,
+        consumed: Vec<String>,
+    },
+    Kakan {
+        actor: usize,
+        pai: String,
+        consumed: Vec<String>,
+    },
+    Reach {
+        actor: usize,
+    },
+    ReachAccepted {
+        actor: usize,
+    },
+    Dora {
+        dora_marker: String,
+    },
+    Hora {
+        actor: usize,
+        target: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pai: Option<String>,
+    },
+    Ryukyoku,
+    EndKyoku,
+    EndGame,
+    None,
+}
+
+impl MjaiMessage {
+    /// Serialize the message as a single JSON line without the trailing newline
+    pub fn to_line(&self) -> Result<String, MjaiError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse a single JSON line
+    pub fn from_line(line: &str) -> Result<Self, MjaiError> {
+        Ok(serde_json::from_str(line.trim())?)
+    }
+}
+
+/// Convert a piece to MJAI tile notation
+pub fn to_mjai_tile(piece: Piece) -> String {
+    match (piece.suit(), piece.honor_type()) {
+        (Some(suit), _) => {
+            let suffix = match suit {
+                Suit::Character => 'm',
+                Suit::Pin => 'p',
+                Suit::Bamboo => 's',
+            };
+            let red = if piece.is_red_five() { "r" } else { "" };
+            format!("{}{}{}", piece.rank().unwrap_or(0), suffix, red)
+        }
+        (None, Some(honor)) => match honor {
+            Honor::East => "E",
+            Honor::South => "S",
+            Honor::West => "W",
+            Honor::North => "N",
+            Honor::White => "P",
+            Honor::Green => "F",
+            Honor::Red => "C",
+        }
+        .to_string(),
+        (None, None) => HIDDEN_TILE.to_string(),
+    }
+}
+
+/// Parse an MJAI tile. Hidden tiles (`?`) are rejected.
+pub fn from_mjai_tile(tile: &str) -> Result<Piece, MjaiError> {
+    let invalid = || MjaiError::InvalidTile(tile.to_string());
+    let honor = match tile {
+        "E" => Some(Honor::East),
+        "S" => Some(Honor::South),
+        "W" => Some(Honor::West),
+        "N" => Some(Honor::North),
+        "P" => Some(Honor::White),
+        "F" => Some(Honor::Green),
+        "C" => Some(Honor::Red),
+        _ => None,
+    };
+    if let Some(honor) = honor {
+        return Ok(Piece::honor(honor));
+    }
+
+    let mut chars = tile.chars();
+    let rank = chars
+        .next()
+        .and_then(|c| c.to_digit(10))
+        .ok_or_else(invalid)? as u8;
+    let suit = match chars.next() {
+        Some('m') => Suit::Character,
+        Some('p') => Suit::Pin,
+        Some('s') => Suit::Bamboo,
+        _ => return Err(invalid()),
+    };
+    match chars.as_str() {
+        "" => Piece::suited(suit, rank).ok_or_else(invalid),
+        "r" if rank == 5 => Ok(Piece::red_five(suit)),
+        _ => Err(invalid()),
+    }
+}
+
+fn tiles(pieces: &[Piece]) -> Vec<String> {
+    pieces.iter().map(|&p| to_mjai_tile(p)).collect()
+}
+
+/// Remove one copy of `called` from the meld's pieces, leaving the plain
+/// pieces that came from the caller's hand
+fn plain_consumed(meld: &Meld, called: Piece) -> Vec<Piece> {
+    let mut pieces = meld.pieces();
+    if let Some(i) = pieces.iter().position(|p| p.index() == called.index()) {
+        pieces.remove(i);
+    }
+    pieces
+}
+
+/// The pieces of every seat's pons, so a later kakan can list them
+#[derive(Debug, Default)]
+struct Pons(Vec<(usize, Vec<Piece>)>);
+
+impl Pons {
+    fn add(&mut self, seat: usize, called: Piece, consumed: &[Piece]) {
+        let mut pieces = consumed.to_vec();
+        pieces.push(called);
+        self.0.push((seat, pieces));
+    }
+
+    /// Take the pieces of the pon the kakan upgrades, falling back to plain
+    /// pieces for a pon that was never seen
+    fn take(&mut self, seat: usize, meld: &Meld) -> Vec<Piece> {
+        let index = meld.start.index();
+        match self
+            .0
+            .iter()
+            .position(|(s, pieces)| *s == seat && pieces[0].index() == index)
+        {
+            Some(i) => self.0.swap_remove(i).1,
+            None => vec![meld.start; 3],
+        }
+    }
+}
+
+fn bakaze(round: i32) -> String {
+    to_mjai_tile(Piece::honor(Honor::wind_for_seat(
+        (round.max(0) / 4) as usize,
+    )))
+}
+
+/// Converts a [`GameEvent`] log into MJAI messages
+#[derive(Debug, Default)]
+pub struct MjaiEncoder {
+    last_discard: Option<(usize, Piece)>,
+    in_kyoku: bool,
+    /// Seat that declared riichi and has yet to discard
+    reach_declared: Option<usize>,
+    /// Seat whose riichi discard has yet to pass without a ron
+    reach_discarded: Option<usize>,
+    pons: Pons,
+}
+
+impl MjaiEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The opening message for a game with the given player names
+    pub fn start_game(&self, names: &[String]) -> MjaiMessage {
+        MjaiMessage::StartGame {
+            id: None,
+            names: names.to_vec(),
+        }
+    }
+
+    /// Convert a single event. Dora indicators are not part of the observed
+    /// state, so `start_kyoku` leaves out its dora marker.
+    ///
+    /// Riichi is sent as `reach` when declared and `reach_accepted` once the
+    /// riichi discard passes, i.e. before the next event that is not a ron.
+    pub fn encode(&mut self, event: &GameEvent) -> Vec<MjaiMessage> {
+        let target = |seat: usize, from: Option<usize>, last: Option<(usize, Piece)>| {
+            from.or(last.map(|(s, _)| s)).unwrap_or(seat)
+        };
+        let called = self.last_discard.map(|(_, piece)| piece);
+
+        let mut messages = Vec::new();
+        if let Some(actor) = self.reach_discarded.take() {
+            if !matches!(event, GameEvent::Ron { .. }) {
+                messages.push(MjaiMessage::ReachAccepted { actor });
+            }
+        }
+
+        let message = match event {
+            GameEvent::Deal {
+                round,
+                counters,
+                riichi_sticks,
+                hands,
+            } => {
+                if self.in_kyoku {
+                    messages.push(MjaiMessage::EndKyoku);
+                }
+                self.in_kyoku = true;
+                self.last_discard = None;
+                self.reach_declared = None;
+                self.pons = Pons::default();
+                messages.push(MjaiMessage::StartKyoku {
+                    bakaze: bakaze(*round),
+                    kyoku: (round.max(&0) % 4) as u8 + 1,
+                    honba: (*counters).max(0) as u32,
+                    kyotaku: (*riichi_sticks).max(0) as u32,
+                    oya: (round.max(&0) % 4) as usize,
+                    dora_marker: None,
+                    tehais: hands.iter().map(|hand| tiles(hand)).collect(),
+                });
+                return messages;
+            }
+            GameEvent::Draw { seat, piece } => MjaiMessage::Tsumo {
+                actor: *seat,
+                pai: to_mjai_tile(*piece),
+            },
+            GameEvent::Discard { seat, piece } => {
+                self.last_discard = Some((*seat, *piece));
+                if self.reach_declared == Some(*seat) {
+                    self.reach_declared = None;
+                    self.reach_discarded = Some(*seat);
+                }
+                MjaiMessage::Dahai {
+                    actor: *seat,
+                    pai: to_mjai_tile(*piece),
+                    tsumogiri: false,
+                }
+            }
+            GameEvent::Chi {
+                seat,
+                from,
+                meld,
+                called: piece,
+                consumed,
+            } => MjaiMessage::Chi {
+                actor: *seat,
+                target: target(*seat, *from, self.last_discard),
+                pai: to_mjai_tile(piece.or(called).unwrap_or(meld.start)),
+                consumed: tiles(consumed),
+            },
+            GameEvent::Pon {
+                seat,
+                from,
+                meld,
+                called: piece,
+                consumed,
+            } => {
+                let piece = piece.or(called).unwrap_or(meld.start);
+                self.pons.add(*seat, piece, consumed);
+                MjaiMessage::Pon {
+                    actor: *seat,
+                    target: target(*seat, *from, self.last_discard),
+                    pai: to_mjai_tile(piece),
+                    consumed: tiles(consumed),
+                }
+            }
+            GameEvent::Kan {
+                seat,
+                from,
+                meld,
+                called: piece,
+                consumed,
+            } => MjaiMessage::Daiminkan {
+                actor: *seat,
+                target: target(*seat, *from, self.last_discard),
+                pai: to_mjai_tile(piece.or(called).unwrap_or(meld.start)),
+                consumed: tiles(consumed),
+            },
+            GameEvent::ConcealedKan { seat, consumed, .. } => MjaiMessage::Ankan {
+                actor: *seat,
+                consumed: tiles(consumed),
+            },
+            GameEvent::ConvertedKan { seat, meld, added } => MjaiMessage::Kakan {
+                actor: *seat,
+                pai: to_mjai_tile(added.unwrap_or(meld.start)),
+                consumed: tiles(&self.pons.take(*seat, meld)),
+            },
+            GameEvent::Riichi { seat } => {
+                self.reach_declared = Some(*seat);
+                MjaiMessage::Reach { actor: *seat }
+            }
+            GameEvent::Ron { seat, from, piece } => MjaiMessage::Hora {
+                actor: *seat,
+                target: target(*seat, *from, self.last_discard),
+                pai: piece.or(called).map(to_mjai_tile),
+            },
+            GameEvent::Tsumo { seat, piece } => MjaiMessage::Hora {
+                actor: *seat,
+                target: *seat,
+                pai: piece.map(to_mjai_tile),
+            },
+            GameEvent::ExhaustiveDraw => MjaiMessage::Ryukyoku,
+            GameEvent::RoundEnd { .. } => {
+                self.in_kyoku = false;
+                MjaiMessage::EndKyoku
+            }
+            GameEvent::GameEnd { .. } => MjaiMessage::EndGame,
+        };
+        messages.push(message);
+        messages
+    }
+
+    /// Convert a full event log
+    pub fn encode_all<'a>(
+        &mut self,
+        events: impl IntoIterator<Item = &'a GameEvent>,
+    ) -> Vec<MjaiMessage> {
+        events
+            .into_iter()
+            .flat_map(|event| self.encode(event))
+            .collect()
+    }
+}
+
+fn option(call: Call, piece: Option<Piece>, calls: &[CallOption]) -> Option<CallOption> {
+    calls
+        .iter()
+        .find(|option| {
+            option.call == call
+                && piece.is_none_or(|piece| {
+                    Piece::try_from(option.piece).is_ok_and(|p| p.index() == piece.index())
+                })
+        })
+        .copied()
+        .or_else(|| calls.iter().find(|option| option.call == call).copied())
+}
+
+/// Interpret a bot's response to the latest message as a controller
+/// decision, given the options the native library offered
+pub fn response_to_decision(
+    response: &MjaiMessage,
+    calls: &[CallOption],
+    discards: &[Piece],
+) -> Result<Decision, MjaiError> {
+    let piece = |tile: &str| from_mjai_tile(tile);
+    let call = |call: Call, tile: Option<&String>| -> Result<Option<Decision>, MjaiError> {
+        let piece = tile.map(|t| piece(t)).transpose()?;
+        Ok(option(call, piece, calls).map(Decision::Call))
+    };
+
+    let decision = match response {
+        MjaiMessage::Dahai { pai, .. } => {
+            let wanted = piece(pai)?;
+            discards
+                .iter()
+                .find(|p| **p == wanted)
+                .or_else(|| discards.iter().find(|p| p.index() == wanted.index()))
+                .map(|&p| Decision::Discard(p))
+        }
+        MjaiMessage::Chi { pai, .. } => call(Call::Chi, Some(pai))?,
+        MjaiMessage::Pon { pai, .. } => call(Call::Pon, Some(pai))?,
+        MjaiMessage::Daiminkan { pai, .. } => call(Call::Kan, Some(pai))?,
+        MjaiMessage::Kakan { pai, .. } => call(Call::ConvertedKan, Some(pai))?,
+        MjaiMessage::Ankan { consumed, .. } => call(Call::ConcealedKan, consumed.first())?,
+        MjaiMessage::Reach { .. } => call(Call::Riichi, None)?,
+        MjaiMessage::Hora { actor, target, .. } if actor == target => call(Call::Tsumo, None)?,
+        MjaiMessage::Hora { .. } => call(Call::Ron, None)?,
+        _ => None,
+    };
+
+    Ok(decision.unwrap_or(Decision::Decline))
+}
+
+/// An external MJAI bot running as a subprocess, usable as a [`Controller`].
+///
+/// Every message is written as one JSON line to the bot's stdin and one
+/// response line is read back from its stdout, waiting at most
+/// [`DEFAULT_RESPONSE_TIMEOUT`] unless [`with_timeout`](Self::with_timeout)
+/// says otherwise. Communication failures are recorded in
+/// [`MjaiBot::last_error`] and the bot falls back to declining calls and
+/// discarding the piece it drew, or the last legal option.
+pub struct MjaiBot {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines read from the bot's stdout by a reader thread
+    responses: Receiver<io::Result<String>>,
+    timeout: Duration,
+    seat: usize,
+    hand: Vec<Piece>,
+    in_kyoku: bool,
+    last_discard: Option<(usize, Piece)>,
+    pending: Option<MjaiMessage>,
+    round: i32,
+    last_error: Option<MjaiError>,
+    /// Seat that declared riichi and has yet to discard
+    reach_declared: Option<usize>,
+    /// Seat whose riichi discard has yet to pass without a ron
+    reach_discarded: Option<usize>,
+    /// `start_kyoku` held back until the round's dora indicator is known
+    start_kyoku: Option<MjaiMessage>,
+    pons: Pons,
+}
+
+impl MjaiBot {
+    /// Spawn the bot with piped stdin and stdout
+    pub fn spawn(mut command: Command) -> Result<Self, MjaiError> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or(MjaiError::BotExited)?;
+        let stdout = child.stdout.take().ok_or(MjaiError::BotExited)?;
+
+        // Read on a separate thread so a hung bot cannot block the game
+        let (sender, responses) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdout = BufReader::new(stdout);
+            loop {
+                let mut line = String::new();
+                let read = match stdout.read_line(&mut line) {
+                    Ok(0) => break,
+                    Ok(_) => Ok(line),
+                    Err(error) => Err(error),
+                };
+                let failed = read.is_err();
+                if sender.send(read).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            responses,
+            timeout: DEFAULT_RESPONSE_TIMEOUT,
+            seat: 0,
+            hand: Vec::new(),
+            in_kyoku: false,
+            last_discard: None,
+            pending: None,
+            round: 0,
+            last_error: None,
+            reach_declared: None,
+            reach_discarded: None,
+            start_kyoku: None,
+            pons: Pons::default(),
+        })
+    }
+
+    /// Wait at most `timeout` for each response
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Get the last communication error, if any
+    pub fn last_error(&self) -> Option<&MjaiError> {
+        self.last_error.as_ref()
+    }
+
+    /// Send a message and read the bot's response line. Responses that
+    /// arrived after an earlier message timed out are skipped.
+    pub fn send(&mut self, message: &MjaiMessage) -> Result<MjaiMessage, MjaiError> {
+        while let Ok(Ok(_late)) = self.responses.try_recv() {}
+
+        writeln!(self.stdin, "{}", message.to_line()?)?;
+        self.stdin.flush()?;
+
+        match self.responses.recv_timeout(self.timeout) {
+            Ok(line) => MjaiMessage::from_line(&line?),
+            Err(RecvTimeoutError::Timeout) => Err(MjaiError::TimedOut(self.timeout)),
+            Err(RecvTimeoutError::Disconnected) => Err(MjaiError::BotExited),
+        }
+    }
+
+    fn send_logged(&mut self, message: &MjaiMessage) -> Option<MjaiMessage> {
+        match self.send(message) {
+            Ok(response) => Some(response),
+            Err(error) => {
+                self.last_error = Some(error);
+                None
+            }
+        }
+    }
+
+    /// Send the held back `start_kyoku`, with the dora indicator if it is
+    /// known by now
+    fn start_kyoku(&mut self, dora: Option<Piece>) {
+        if let Some(mut message) = self.start_kyoku.take() {
+            if let MjaiMessage::StartKyoku { dora_marker, .. } = &mut message {
+                *dora_marker = dora.map(to_mjai_tile);
+            }
+            self.send_logged(&message);
+        }
+    }
+
+    /// Send `reach_accepted` once a riichi discard passes. A ron on the
+    /// discard cancels the riichi instead.
+    fn settle_reach(&mut self, ron: bool) {
+        if let Some(actor) = self.reach_discarded.take() {
+            if !ron {
+                self.send_logged(&MjaiMessage::ReachAccepted { actor });
+            }
+        }
+    }
+
+    /// Find the piece drawn since the bot last saw its hand and catch up
+    /// with the game's hand. Discard options are deduplicated, so the draw
+    /// has to come from the view.
+    fn take_draw(&mut self, view: &PlayerView) -> Option<Piece> {
+        let held = &view.hand.live_pieces;
+        if held.len() != self.hand.len() + 1 {
+            return None;
+        }
+        let mut drawn = held.clone();
+        for piece in &self.hand {
+            if let Some(i) = drawn.iter().position(|p| p == piece) {
+                drawn.remove(i);
+            }
+        }
+        let piece = view
+            .pending_piece
+            .filter(|pending| drawn.contains(pending))
+            .or_else(|| drawn.last().copied())?;
+        self.hand = held.clone();
+        Some(piece)
+    }
+
+    fn remove_from_hand(&mut self, piece: Piece, count: usize) {
+        for _ in 0..count {
+            if let Some(i) = self.hand.iter().position(|p| p.index() == piece.index()) {
+                self.hand.remove(i);
+            }
+        }
+    }
+
+    /// The pieces `actor` took from its hand for a call. The bot's own
+    /// pieces come from its view, red fives included. Opponents' pieces are
+    /// hidden until then, so the `plain` ones are assumed.
+    fn consumed(&mut self, actor: usize, plain: Vec<Piece>, view: &PlayerView) -> Vec<Piece> {
+        if actor != self.seat {
+            return plain;
+        }
+        let held = &view.hand.live_pieces;
+        if view.seat != self.seat || held.len() + plain.len() != self.hand.len() {
+            for &piece in &plain {
+                self.remove_from_hand(piece, 1);
+            }
+            return plain;
+        }
+        let mut removed = self.hand.clone();
+        for piece in held {
+            if let Some(i) = removed.iter().position(|p| p == piece) {
+                removed.remove(i);
+            }
+        }
+        self.hand = held.clone();
+        removed
+    }
+
+    fn event_message(&mut self, event: &Event, view: &PlayerView) -> Option<MjaiMessage> {
+        let actor = usize::try_from(event.player).ok()?;
+        let piece = event.piece();
+        let target = self.last_discard.map(|(seat, _)| seat).unwrap_or(actor);
+        let tile = |p: Piece| to_mjai_tile(p);
+
+        let message = match event.kind {
+            EventKind::Discard => {
+                let piece = piece?;
+                self.last_discard = Some((actor, piece));
+                if actor == self.seat {
+                    self.remove_from_hand(piece, 1);
+                }
+                if self.reach_declared == Some(actor) {
+                    self.reach_declared = None;
+                    self.reach_discarded = Some(actor);
+                }
+                MjaiMessage::Dahai {
+                    actor,
+                    pai: tile(piece),
+                    tsumogiri: false,
+                }
+            }
+            EventKind::Pon | EventKind::Kan => {
+                let called = piece?;
+                let count = if event.kind == EventKind::Pon { 2 } else { 3 };
+                let consumed = self.consumed(actor, vec![called.normalized(); count], view);
+                if event.kind == EventKind::Pon {
+                    self.pons.add(actor, called, &consumed);
+                    MjaiMessage::Pon {
+                        actor,
+                        target,
+                        pai: tile(called),
+                        consumed: tiles(&consumed),
+                    }
+                } else {
+                    MjaiMessage::Daiminkan {
+                        actor,
+                        target,
+                        pai: tile(called),
+                        consumed: tiles(&consumed),
+                    }
+                }
+            }
+            EventKind::Chi => {
+                let called = piece?;
+                // The view shows where the run starts
+                let meld = view.seats[actor % 4]
+                    .melds
+                    .last()
+                    .and_then(|meld| meld.start.filter(|_| meld.meld_type == MeldType::Chi))
+                    .map(|start| Meld {
+                        meld_type: MeldType::Chi,
+                        start,
+                    })
+                    .unwrap_or(Meld {
+                        meld_type: MeldType::Chi,
+                        start: called.normalized(),
+                    });
+                let consumed = self.consumed(actor, plain_consumed(&meld, called), view);
+                MjaiMessage::Chi {
+                    actor,
+                    target,
+                    pai: tile(called),
+                    consumed: tiles(&consumed),
+                }
+            }
+            EventKind::ConcealedKan => {
+                let piece = piece?;
+                let consumed = self.consumed(actor, vec![piece.normalized(); 4], view);
+                MjaiMessage::Ankan {
+                    actor,
+                    consumed: tiles(&consumed),
+                }
+            }
+            EventKind::ConvertedKan => {
+                let piece = piece?;
+                let added = match self.consumed(actor, vec![piece], view)[..] {
+                    [own] if actor == self.seat => own,
+                    _ => view.pending_piece.unwrap_or(piece),
+                };
+                let meld = Meld {
+                    meld_type: MeldType::Pon,
+                    start: piece.normalized(),
+                };
+                MjaiMessage::Kakan {
+                    actor,
+                    pai: tile(added),
+                    consumed: tiles(&self.pons.take(actor, &meld)),
+                }
+            }
+            // Our own declaration was already sent while deciding
+            EventKind::Riichi if self.reach_declared == Some(actor) => return None,
+            EventKind::Riichi => {
+                self.reach_declared = Some(actor);
+                MjaiMessage::Reach { actor }
+            }
+            EventKind::Ron | EventKind::Tsumo => MjaiMessage::Hora {
+                actor,
+                target: if event.kind == EventKind::Tsumo {
+                    actor
+                } else {
+                    target
+                },
+                pai: piece.map(tile),
+            },
+            EventKind::Dora => MjaiMessage::Dora {
+                dora_marker: tile(piece?),
+            },
+            EventKind::ExhaustiveDraw => MjaiMessage::Ryukyoku,
+            EventKind::End => MjaiMessage::EndGame,
+            EventKind::Decline | EventKind::PointDiff => return None,
+        };
+        Some(message)
+    }
+}
+
+impl Controller for MjaiBot {
+    fn game_start(&mut self, seat: usize) {
+        self.seat = seat;
+        self.send_logged(&MjaiMessage::StartGame {
+            id: Some(seat),
+            names: Vec::new(),
+        });
+    }
+
+    fn round_start(
+        &mut self,
+        hand: &[Piece],
+        seat_wind: Honor,
+        round_wind: Honor,
+        view: &PlayerView,
+    ) {
+        self.start_kyoku(None);
+        if self.in_kyoku {
+            self.send_logged(&MjaiMessage::EndKyoku);
+        }
+        self.in_kyoku = true;
+        self.hand = hand.to_vec();
+        self.last_discard = None;
+        self.pending = None;
+        self.reach_declared = None;
+        self.reach_discarded = None;
+        self.pons = Pons::default();
+
+        // Seat winds count from the dealer, so the dealer sits `wind` seats back
+        let oya = (self.seat + 4 - seat_wind as usize % 4) % 4;
+        let tehais = (0..4)
+            .map(|seat| {
+                if seat == self.seat {
+                    tiles(hand)
+                } else {
+                    vec![HIDDEN_TILE.to_string(); 13]
+                }
+            })
+            .collect();
+
+        // The round's first dora event fills in the marker. Backends that
+        // never report it send the message without one.
+        self.start_kyoku = Some(MjaiMessage::StartKyoku {
+            bakaze: to_mjai_tile(Piece::honor(round_wind)),
+            kyoku: oya as u8 + 1,
+            honba: view.counters.max(0) as u32,
+            kyotaku: view.riichi_sticks.max(0) as u32,
+            oya,
+            dora_marker: None,
+            tehais,
+        });
+        self.round += 1;
+    }
+
+    fn receive_event(&mut self, event: &Event, view: &PlayerView) {
+        if self.start_kyoku.is_some() {
+            let dora = (event.kind == EventKind::Dora)
+                .then(|| event.piece())
+                .flatten();
+            self.start_kyoku(dora);
+            if dora.is_some() {
+                return;
+            }
+        }
+        if !matches!(event.kind, EventKind::Decline | EventKind::PointDiff) {
+            self.settle_reach(event.kind == EventKind::Ron);
+        }
+        if let Some(message) = self.event_message(event, view) {
+            self.pending = self.send_logged(&message);
+        }
+    }
+
+    fn choose_discard(&mut self, options: &[Piece], view: &PlayerView) -> Piece {
+        match self.decide(&[], options, view) {
+            Decision::Discard(piece) => piece,
+            _ => options[0],
+        }
+    }
+
+    fn decide(&mut self, calls: &[CallOption], discards: &[Piece], view: &PlayerView) -> Decision {
+        self.start_kyoku(None);
+        let drawn = if discards.is_empty() {
+            None
+        } else {
+            self.take_draw(view)
+        };
+        let response = if discards.is_empty() {
+            // Reacting to another player's discard, answered when it was sent
+            self.pending.take()
+        } else {
+            match drawn {
+                Some(piece) => {
+                    // Drawing means the last riichi discard passed
+                    self.settle_reach(false);
+                    self.send_logged(&MjaiMessage::Tsumo {
+                        actor: self.seat,
+                        pai: to_mjai_tile(piece),
+                    })
+                }
+                // No draw happened (we just called), so answer the call echo
+                None => self.pending.take(),
+            }
+        };
+
+        let mut response = response.unwrap_or(MjaiMessage::None);
+        if let MjaiMessage::Reach { .. } = response {
+            let riichi = calls.iter().any(|c| c.call == Call::Riichi);
+            if let Some(follow_up) = self.send_logged(&MjaiMessage::Reach { actor: self.seat }) {
+                if let (true, MjaiMessage::Dahai { pai, .. }) = (riichi, &follow_up) {
+                    if let Ok(piece) = from_mjai_tile(pai) {
+                        if let Some(option) = option(Call::Riichi, Some(piece), calls) {
+                            self.reach_declared = Some(self.seat);
+                            return Decision::Call(option);
+                        }
+                    }
+                }
+                response = follow_up;
+            }
+        }
+
+        let decision = response_to_decision(&response, calls, discards).unwrap_or_else(|error| {
+            self.last_error = Some(error);
+            Decision::Decline
+        });
+        match (decision, discards.last()) {
+            // Without a usable answer, discard the drawn piece
+            (Decision::Decline, Some(&last)) => Decision::Discard(
+                drawn
+                    .and_then(|drawn| discards.iter().copied().find(|&p| p == drawn))
+                    .unwrap_or(last),
+            ),
+            (decision, _) => decision,
+        }
+    }
+}
+
+impl Drop for MjaiBot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_pieces;
+
+    #[test]
+    fn tiles_round_trip() {
+        for piece in parse_pieces("19m0p5p5s1234567z").unwrap() {
+            assert_eq!(from_mjai_tile(&to_mjai_tile(piece)).unwrap(), piece);
+        }
+        assert_eq!(to_mjai_tile("0s".parse().unwrap()), "5sr");
+        assert_eq!(to_mjai_tile("5z".parse().unwrap()), "P");
+        assert!(from_mjai_tile(HIDDEN_TILE).is_err());
+        assert!(from_mjai_tile("0m").is_err());
+    }
+
+    #[test]
+    fn messages_use_mjai_json() {
+        let message = MjaiMessage::Dahai {
+            actor: 1,
+            pai: "5mr".to_string(),
+            tsumogiri: false,
+        };
+        let line = message.to_line().unwrap();
+        assert_eq!(
+            line,
+            r#"{"type":"dahai","actor":1,"pai":"5mr","tsumogiri":false}"#
+        );
+        assert_eq!(MjaiMessage::from_line(&line).unwrap(), message);
+        assert_eq!(
+            MjaiMessage::from_line(r#"{"type":"none"}"#).unwrap(),
+            MjaiMessage::None
+        );
+    }
+
+    #[test]
+    fn encodes_event_log() {
+        let hands = parse_pieces("1234567899m111z").unwrap();
+        let discard = "1z".parse().unwrap();
+        let pon = crate::notation::parse_hand("(111z)").unwrap().melds[0].clone();
+        let events = [
+            GameEvent::Deal {
+                round: 4,
+                counters: 2,
+                riichi_sticks: 1,
+                hands: [hands.clone(), hands.clone(), hands.clone(), hands],
+            },
+            GameEvent::Discard {
+                seat: 0,
+                piece: discard,
+            },
+            GameEvent::Pon {
+                seat: 2,
+                from: Some(0),
+                meld: pon,
+                called: Some(discard),
+                consumed: vec![discard; 2],
+            },
+        ];
+
+        let messages = MjaiEncoder::new().encode_all(&events);
+        assert!(matches!(
+            &messages[0],
+            MjaiMessage::StartKyoku {
+                bakaze,
+                kyoku: 1,
+                honba: 2,
+                kyotaku: 1,
+                dora_marker: None,
+                ..
+            } if bakaze == "S"
+        ));
+        assert!(!messages[0].to_line().unwrap().contains("dora_marker"));
+        assert_eq!(
+            messages[2],
+            MjaiMessage::Pon {
+                actor: 2,
+                target: 0,
+                pai: "E".to_string(),
+                consumed: vec!["E".to_string(), "E".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn encodes_red_fives_in_calls() {
+        let red = Piece::red_five(Suit::Pin);
+        let five: Piece = "5p".parse().unwrap();
+        let pon = Meld {
+            meld_type: MeldType::Pon,
+            start: five,
+        };
+        let events = [
+            GameEvent::Discard {
+                seat: 0,
+                piece: five,
+            },
+            GameEvent::Pon {
+                seat: 1,
+                from: Some(0),
+                meld: pon.clone(),
+                called: Some(five),
+                consumed: vec![red, five],
+            },
+            GameEvent::ConvertedKan {
+                seat: 1,
+                meld: Meld {
+                    meld_type: MeldType::Kan,
+                    ..pon
+                },
+                added: Some(five),
+            },
+        ];
+
+        let messages = MjaiEncoder::new().encode_all(&events);
+        let strings = |tiles: &[&str]| tiles.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            messages[1],
+            MjaiMessage::Pon {
+                actor: 1,
+                target: 0,
+                pai: "5p".to_string(),
+                consumed: strings(&["5pr", "5p"]),
+            }
+        );
+        assert_eq!(
+            messages[2],
+            MjaiMessage::Kakan {
+                actor: 1,
+                pai: "5p".to_string(),
+                consumed: strings(&["5pr", "5p", "5p"]),
+            }
+        );
+    }
+
+    #[test]
+    fn encodes_reach_then_accepts_it_after_the_discard() {
+        let piece: Piece = "1z".parse().unwrap();
+        let events = [
+            GameEvent::Riichi { seat: 1 },
+            GameEvent::Discard { seat: 1, piece },
+            GameEvent::Draw { seat: 2, piece },
+            GameEvent::Riichi { seat: 2 },
+            GameEvent::Discard { seat: 2, piece },
+            GameEvent::Ron {
+                seat: 3,
+                from: Some(2),
+                piece: Some(piece),
+            },
+        ];
+
+        let messages = MjaiEncoder::new().encode_all(&events);
+        assert_eq!(messages.len(), 7);
+        assert_eq!(messages[0], MjaiMessage::Reach { actor: 1 });
+        assert!(matches!(messages[1], MjaiMessage::Dahai { actor: 1, .. }));
+        assert_eq!(messages[2], MjaiMessage::ReachAccepted { actor: 1 });
+        assert!(matches!(messages[3], MjaiMessage::Tsumo { actor: 2, .. }));
+        assert_eq!(messages[4], MjaiMessage::Reach { actor: 2 });
+        // The ron on the riichi discard means it is never accepted
+        assert!(matches!(messages[6], MjaiMessage::Hora { actor: 3, .. }));
+    }
+
+    /// Spawn a shell bot that logs every message to a temporary file and
+    /// then answers with the message itself
+    #[cfg(unix)]
+    fn echo_bot(name: &str) -> (MjaiBot, std::path::PathBuf) {
+        let log = std::env::temp_dir().join(format!("mjai-{name}-{}.log", std::process::id()));
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(r#"while IFS= read -r line; do echo "$line" >> "$0"; echo "$line"; done"#)
+            .arg(&log);
+        (MjaiBot::spawn(command).unwrap(), log)
+    }
+
+    /// Read back what an [`echo_bot`] was sent
+    #[cfg(unix)]
+    fn sent_messages(log: &std::path::Path) -> Vec<MjaiMessage> {
+        let sent = std::fs::read_to_string(log)
+            .unwrap()
+            .lines()
+            .map(|line| MjaiMessage::from_line(line).unwrap())
+            .collect();
+        let _ = std::fs::remove_file(log);
+        sent
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bot_hears_reach_before_the_discard() {
+        let (mut bot, log) = echo_bot("reach");
+        let view = crate::observe::ObservedGameState::default().view_for(0);
+        let event = |kind, player, piece: &str| Event {
+            kind,
+            player,
+            value: piece.parse::<Piece>().map_or(0, i32::from),
+            decision: false,
+        };
+
+        bot.game_start(0);
+        bot.receive_event(&event(EventKind::Riichi, 2, ""), &view);
+        bot.receive_event(&event(EventKind::Discard, 2, "1z"), &view);
+        bot.receive_event(&event(EventKind::Discard, 3, "2z"), &view);
+        bot.receive_event(&event(EventKind::Riichi, 3, ""), &view);
+        bot.receive_event(&event(EventKind::Discard, 3, "3z"), &view);
+        bot.receive_event(&event(EventKind::PointDiff, 1, ""), &view);
+        bot.receive_event(&event(EventKind::Ron, 1, "3z"), &view);
+        drop(bot);
+
+        let sent = sent_messages(&log);
+        assert_eq!(sent[1], MjaiMessage::Reach { actor: 2 });
+        assert!(matches!(sent[2], MjaiMessage::Dahai { actor: 2, .. }));
+        assert_eq!(sent[3], MjaiMessage::ReachAccepted { actor: 2 });
+        assert!(matches!(sent[4], MjaiMessage::Dahai { actor: 3, .. }));
+        assert_eq!(sent[5], MjaiMessage::Reach { actor: 3 });
+        assert!(matches!(sent[6], MjaiMessage::Dahai { actor: 3, .. }));
+        assert!(matches!(sent[7], MjaiMessage::Hora { actor: 1, .. }));
+        assert_eq!(sent.len(), 8);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bot_hears_draws_of_pieces_it_already_holds() {
+        let (mut bot, log) = echo_bot("draw");
+        let hand = parse_pieces("1123456789m123p").unwrap();
+        let mut state = crate::observe::ObservedGameState::default();
+        state.hands[0].live_pieces = hand.clone();
+
+        bot.game_start(0);
+        bot.round_start(&hand, Honor::East, Honor::East, &state.view_for(0));
+        for _ in 0..2 {
+            // Discard options only list each kind once
+            state.hands[0].add_live_piece("1m".parse().unwrap());
+            let mut options: Vec<Piece> = Vec::new();
+            for &piece in &state.hands[0].live_pieces {
+                if !options.contains(&piece) {
+                    options.push(piece);
+                }
+            }
+            let view = state.view_for(0);
+            let Decision::Discard(piece) = bot.decide(&[], &options, &view) else {
+                panic!("a discard is expected after a draw");
+            };
+            let live = &mut state.hands[0].live_pieces;
+            live.remove(live.iter().position(|&p| p == piece).unwrap());
+            bot.receive_event(
+                &Event {
+                    kind: EventKind::Discard,
+                    player: 0,
+                    value: piece.into(),
+                    decision: false,
+                },
+                &state.view_for(0),
+            );
+        }
+        assert_eq!(bot.hand, state.hands[0].live_pieces);
+        drop(bot);
+
+        let draws = sent_messages(&log)
+            .into_iter()
+            .filter(|message| matches!(message, MjaiMessage::Tsumo { .. }))
+            .count();
+        assert_eq!(draws, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bot_hears_the_round_counters_and_dora_marker() {
+        let (mut bot, log) = echo_bot("kyoku");
+        let hand = parse_pieces("123456789m1234p").unwrap();
+        let mut state = crate::observe::ObservedGameState {
+            counters: 2,
+            riichi_sticks: 1,
+            ..Default::default()
+        };
+        state.hands[0].live_pieces = hand.clone();
+        let view = state.view_for(0);
+
+        bot.game_start(0);
+        bot.round_start(&hand, Honor::East, Honor::East, &view);
+        let dora = Event {
+            kind: EventKind::Dora,
+            player: -1,
+            value: "5z".parse::<Piece>().unwrap().into(),
+            decision: false,
+        };
+        bot.receive_event(&dora, &view);
+        drop(bot);
+
+        let sent = sent_messages(&log);
+        assert_eq!(sent.len(), 2);
+        assert!(matches!(
+            &sent[1],
+            MjaiMessage::StartKyoku {
+                honba: 2,
+                kyotaku: 1,
+                dora_marker: Some(marker),
+                ..
+            } if marker == "P"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bot_hears_its_own_red_five_in_a_pon() {
+        let (mut bot, log) = echo_bot("pon");
+        let hand = parse_pieces("05p123456789s11z").unwrap();
+        let mut state = crate::observe::ObservedGameState::default();
+        state.hands[0].live_pieces = hand.clone();
+        let event = |kind, player| Event {
+            kind,
+            player,
+            value: "5p".parse::<Piece>().map_or(0, i32::from),
+            decision: false,
+        };
+
+        bot.game_start(0);
+        bot.round_start(&hand, Honor::East, Honor::East, &state.view_for(0));
+        bot.receive_event(&event(EventKind::Discard, 3), &state.view_for(0));
+        state.hands[0].live_pieces = parse_pieces("123456789s11z").unwrap();
+        state.hands[0].melds.push(Meld {
+            meld_type: MeldType::Pon,
+            start: "5p".parse().unwrap(),
+        });
+        bot.receive_event(&event(EventKind::Pon, 0), &state.view_for(0));
+        assert_eq!(bot.hand, state.hands[0].live_pieces);
+        drop(bot);
+
+        let sent = sent_messages(&log);
+        assert_eq!(
+            sent.last(),
+            Some(&MjaiMessage::Pon {
+                actor: 0,
+                target: 3,
+                pai: "5p".to_string(),
+                consumed: vec!["5pr".to_string(), "5p".to_string()],
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn silent_bot_times_out_and_discards_its_draw() {
+        let mut command = Command::new("sleep");
+        command.arg("10");
+        let mut bot = MjaiBot::spawn(command)
+            .unwrap()
+            .with_timeout(Duration::from_millis(50));
+        let hand = parse_pieces("123456789m1234p").unwrap();
+        let mut state = crate::observe::ObservedGameState::default();
+        state.hands[0].live_pieces = hand.clone();
+
+        bot.round_start(&hand, Honor::East, Honor::East, &state.view_for(0));
+
+        let drawn = "2p".parse().unwrap();
+        state.hands[0].add_live_piece(drawn);
+        let options = parse_pieces("123456789m1234p").unwrap();
+        assert_eq!(
+            bot.decide(&[], &options, &state.view_for(0)),
+            Decision::Discard(drawn)
+        );
+        assert!(matches!(bot.last_error(), Some(MjaiError::TimedOut(_))));
+    }
+
+    #[test]
+    fn parses_responses_into_decisions() {
+        let discards = parse_pieces("13m0p").unwrap();
+        let pon = CallOption {
+            call: Call::Pon,
+            piece: "3m".parse::<Piece>().unwrap().into(),
+        };
+
+        let dahai = MjaiMessage::from_line(r#"{"type":"dahai","actor":0,"pai":"5pr"}"#).unwrap();
+        assert_eq!(
+            response_to_decision(&dahai, &[], &discards).unwrap(),
+            Decision::Discard(discards[2])
+        );
+
+        let response = MjaiMessage::from_line(
+            r#"{"type":"pon","actor":0,"target":3,"pai":"3m","consumed":["3m","3m"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response_to_decision(&response, &[pon], &[]).unwrap(),
+            Decision::Call(pon)
+        );
+        assert_eq!(
+            response_to_decision(&MjaiMessage::None, &[pon], &[]).unwrap(),
+            Decision::Decline
+        );
+    }
+}
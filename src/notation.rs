@@ -0,0 +1,370 @@
+//! Compact tile notation, e.g. `123m456p789s11z`.
+//!
+//! Digits are followed by their suit letter: `m` characters, `p` pins,
+//! `s` bamboo and `z` honors. `0` stands for a red five in the numbered
+//! suits, and honors run `1z`-`7z` as East, South, West, North, White,
+//! Green, Red.
+//!
+//! A hand is written as its concealed pieces followed by whitespace
+//! separated meld groups. Open melds use parentheses and concealed kans use
+//! brackets: `23m456p11z (789s) (5555m) [7777z]`. A [`Meld`] only records
+//! its first piece, so red fives are rejected inside meld groups and melds
+//! are formatted without them.
+//!
+//! Formatting keeps piece order and only merges adjacent pieces of the same
+//! suit, so `parse_pieces(&format_pieces(p)) == p` for any slice of pieces.
+
+use std::str::FromStr;
+
+use crate::observe::{Hand, Meld, MeldType};
+use crate::piece::{Honor, Piece, Suit};
+
+/// Error produced when notation cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum NotationError {
+    #[error("Unexpected character '{ch}' at position {position}")]
+    UnexpectedChar { ch: char, position: usize },
+    #[error("Digits ending at position {position} have no suit letter")]
+    MissingSuit { position: usize },
+    #[error("Rank {rank} is not valid for suit '{suit}' at position {position}")]
+    InvalidRank {
+        rank: char,
+        suit: char,
+        position: usize,
+    },
+    #[error("Meld starting at position {position} is not closed")]
+    UnterminatedMeld { position: usize },
+    #[error("Meld starting at position {position} is empty")]
+    EmptyMeld { position: usize },
+    #[error("'{group}' does not form a valid meld")]
+    InvalidMeld { group: String },
+    #[error("'{group}' holds a red five, which melds cannot record")]
+    RedFiveInMeld { group: String },
+    #[error("Expected a piece, found empty input")]
+    Empty,
+    #[error("Expected a single piece, found {count}")]
+    ExpectedSinglePiece { count: usize },
+}
+
+fn piece_from_chars(rank: char, suit: char, position: usize) -> Result<Piece, NotationError> {
+    let invalid = || NotationError::InvalidRank {
+        rank,
+        suit,
+        position,
+    };
+    let digit = rank.to_digit(10).ok_or_else(invalid)? as u8;
+
+    let numbered = match suit {
+        'm' => Suit::Character,
+        'p' => Suit::Pin,
+        's' => Suit::Bamboo,
+        'z' => {
+            return match digit {
+                1..=7 => Ok(Piece::honor(Honor::ALL[digit as usize - 1])),
+                _ => Err(invalid()),
+            }
+        }
+        _ => return Err(invalid()),
+    };
+
+    match digit {
+        0 => Ok(Piece::red_five(numbered)),
+        _ => Piece::suited(numbered, digit).ok_or_else(invalid),
+    }
+}
+
+fn suit_char(piece: Piece) -> char {
+    match piece.suit() {
+        Some(Suit::Character) => 'm',
+        Some(Suit::Pin) => 'p',
+        Some(Suit::Bamboo) => 's',
+        None => 'z',
+    }
+}
+
+fn parse_pieces_at(s: &str, offset: usize) -> Result<Vec<Piece>, NotationError> {
+    let mut pieces = Vec::new();
+    let mut pending: Vec<(char, usize)> = Vec::new();
+
+    for (i, ch) in s.char_indices() {
+        let position = offset + i;
+        match ch {
+            '0'..='9' => pending.push((ch, position)),
+            'm' | 'p' | 's' | 'z' => {
+                if pending.is_empty() {
+                    return Err(NotationError::UnexpectedChar { ch, position });
+                }
+                for (rank, rank_position) in pending.drain(..) {
+                    pieces.push(piece_from_chars(rank, ch, rank_position)?);
+                }
+            }
+            _ => return Err(NotationError::UnexpectedChar { ch, position }),
+        }
+    }
+
+    match pending.last() {
+        Some(&(_, position)) => Err(NotationError::MissingSuit { position }),
+        None => Ok(pieces),
+    }
+}
+
+/// Parse a run of pieces such as `123m0p77z`
+pub fn parse_pieces(s: &str) -> Result<Vec<Piece>, NotationError> {
+    parse_pieces_at(s, 0)
+}
+
+/// Format pieces in order, grouping adjacent pieces of the same suit
+pub fn format_pieces(pieces: &[Piece]) -> String {
+    let mut out = String::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        let label = piece.to_string();
+        out.push_str(&label[..label.len() - 1]);
+
+        let suit = suit_char(*piece);
+        if pieces.get(i + 1).map(|&next| suit_char(next)) != Some(suit) {
+            out.push(suit);
+        }
+    }
+    out
+}
+
+fn meld_from_pieces(pieces: &[Piece], concealed: bool, group: &str) -> Result<Meld, NotationError> {
+    let invalid = || NotationError::InvalidMeld {
+        group: group.to_string(),
+    };
+
+    if pieces.iter().any(|piece| piece.is_red_five()) {
+        return Err(NotationError::RedFiveInMeld {
+            group: group.to_string(),
+        });
+    }
+
+    let mut sorted = pieces.to_vec();
+    sorted.sort();
+    let first = *sorted.first().ok_or_else(invalid)?;
+    let all_same = sorted.iter().all(|p| p.index() == first.index());
+
+    let meld_type = match (sorted.len(), all_same, concealed) {
+        (4, true, true) => MeldType::ConcealedKan,
+        (4, true, false) => MeldType::Kan,
+        (3, true, false) => MeldType::Pon,
+        (3, false, false) => {
+            let is_run = sorted
+                .windows(2)
+                .all(|pair| pair[0].next_in_suit().map(Piece::index) == Some(pair[1].index()));
+            if !is_run {
+                return Err(invalid());
+            }
+            MeldType::Chi
+        }
+        _ => return Err(invalid()),
+    };
+
+    Ok(Meld {
+        meld_type,
+        start: first,
+    })
+}
+
+/// Parse a hand with optional meld groups, e.g. `23m456p11z (789s) [7777z]`.
+///
+/// Discards and riichi information cannot be expressed in the notation and
+/// are left empty. `open` is set when any meld other than a concealed kan is
+/// present.
+pub fn parse_hand(s: &str) -> Result<Hand, NotationError> {
+    let mut live_pieces = Vec::new();
+    let mut melds = Vec::new();
+
+    let mut rest = s;
+    let mut offset = 0;
+    loop {
+        let trimmed = rest.trim_start();
+        offset += rest.len() - trimmed.len();
+        rest = trimmed;
+
+        let Some(open) = rest.chars().next() else {
+            break;
+        };
+
+        let close = match open {
+            '(' => Some(')'),
+            '[' => Some(']'),
+            _ => None,
+        };
+
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        match close {
+            Some(close) => {
+                let end = rest
+                    .find(close)
+                    .ok_or(NotationError::UnterminatedMeld { position: offset })?;
+                let group = &rest[..=end];
+                let pieces = parse_pieces_at(&rest[1..end], offset + 1)?;
+                if pieces.is_empty() {
+                    return Err(NotationError::EmptyMeld { position: offset });
+                }
+                melds.push(meld_from_pieces(&pieces, open == '[', group)?);
+                offset += end + 1;
+                rest = &rest[end + 1..];
+            }
+            None => {
+                live_pieces.extend(parse_pieces_at(&rest[..end], offset)?);
+                offset += end;
+                rest = &rest[end..];
+            }
+        }
+    }
+
+    let open = melds
+        .iter()
+        .any(|meld: &Meld| meld.meld_type != MeldType::ConcealedKan);
+
+    Ok(Hand {
+        live_pieces,
+        melds,
+        discards: Vec::new(),
+        open,
+        riichi: false,
+        riichi_piece_discard: 0,
+        riichi_round: 0,
+    })
+}
+
+/// Format a meld as a bracketed group, writing red fives as plain fives
+pub fn format_meld(meld: &Meld) -> String {
+    let plain = Meld {
+        meld_type: meld.meld_type,
+        start: meld.start.normalized(),
+    };
+    let inner = format_pieces(&plain.pieces());
+    match meld.meld_type {
+        MeldType::ConcealedKan => format!("[{}]", inner),
+        _ => format!("({})", inner),
+    }
+}
+
+/// Format the live pieces and melds of a hand.
+///
+/// Round-trips through [`parse_hand`] for any hand without discards or
+/// riichi state.
+pub fn format_hand(hand: &Hand) -> String {
+    let mut parts = Vec::with_capacity(hand.melds.len() + 1);
+    if !hand.live_pieces.is_empty() {
+        parts.push(format_pieces(&hand.live_pieces));
+    }
+    parts.extend(hand.melds.iter().map(format_meld));
+    parts.join(" ")
+}
+
+impl FromStr for Piece {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_pieces(s)?.as_slice() {
+            [piece] => Ok(*piece),
+            [] => Err(NotationError::Empty),
+            pieces => Err(NotationError::ExpectedSinglePiece {
+                count: pieces.len(),
+            }),
+        }
+    }
+}
+
+impl FromStr for Hand {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_hand(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_basic_groups() {
+        let pieces = parse_pieces("123m0p77z").unwrap();
+        let names: Vec<_> = pieces.iter().map(ToString::to_string).collect();
+        assert_eq!(names, ["1m", "2m", "3m", "0p", "7z", "7z"]);
+        assert!(pieces[3].is_red_five());
+        assert_eq!(pieces[4], Piece::honor(Honor::Red));
+    }
+
+    #[test]
+    fn pieces_round_trip() {
+        let source = "19m19p19s1234567z1m";
+        let pieces = parse_pieces(source).unwrap();
+        assert_eq!(format_pieces(&pieces), source);
+        assert_eq!(parse_pieces(&format_pieces(&pieces)).unwrap(), pieces);
+    }
+
+    #[test]
+    fn hand_round_trip_with_melds() {
+        let source = "23m456p11z (789s) (5555m) [7777z]";
+        let hand = parse_hand(source).unwrap();
+        assert_eq!(hand.live_piece_count(), 7);
+        assert_eq!(hand.meld_count(), 3);
+        assert_eq!(hand.melds[0].meld_type, MeldType::Chi);
+        assert_eq!(hand.melds[1].meld_type, MeldType::Kan);
+        assert_eq!(hand.melds[2].meld_type, MeldType::ConcealedKan);
+        assert!(hand.open);
+        assert_eq!(format_hand(&hand), source);
+        assert_eq!(source.parse::<Hand>().unwrap(), hand);
+    }
+
+    #[test]
+    fn red_fives_stay_out_of_melds() {
+        assert_eq!(
+            parse_hand("11z [406m]"),
+            Err(NotationError::RedFiveInMeld {
+                group: "[406m]".to_string()
+            })
+        );
+
+        let source = "0m11z (456p) (5555s)";
+        let mut hand = parse_hand(source).unwrap();
+        assert_eq!(format_hand(&hand), source);
+        assert_eq!(parse_hand(&format_hand(&hand)).unwrap(), hand);
+
+        // A red start, e.g. from the library, formats as its plain tile
+        hand.melds[1].start = Piece::red_five(Suit::Bamboo);
+        assert_eq!(format_hand(&hand), source);
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        assert_eq!(
+            parse_pieces("12"),
+            Err(NotationError::MissingSuit { position: 1 })
+        );
+        assert_eq!(
+            parse_pieces("8z"),
+            Err(NotationError::InvalidRank {
+                rank: '8',
+                suit: 'z',
+                position: 0
+            })
+        );
+        assert_eq!(
+            parse_pieces("1m x"),
+            Err(NotationError::UnexpectedChar {
+                ch: ' ',
+                position: 2
+            })
+        );
+        assert_eq!(
+            parse_hand("11m (123m"),
+            Err(NotationError::UnterminatedMeld { position: 4 })
+        );
+        assert!(matches!(
+            parse_hand("(124m)"),
+            Err(NotationError::InvalidMeld { .. })
+        ));
+        assert_eq!("".parse::<Piece>(), Err(NotationError::Empty));
+        assert_eq!(
+            "12m".parse::<Piece>(),
+            Err(NotationError::ExpectedSinglePiece { count: 2 })
+        );
+    }
+}
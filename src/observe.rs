@@ -1,5 +1,10 @@
-use crate::ffi::observe::{CHand, CMeld, CMeldType, CObservedGameState, CStateFunctionType};
+use crate::ffi::error::MahjongFFIError;
+use crate::ffi::observe::{
+    CHand, CMeld, CMeldType, CObservedGameState, CPiece, CStateFunctionType,
+};
+use crate::piece::Piece;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MeldType {
     Chi,
@@ -19,8 +24,11 @@ impl From<CMeldType> for MeldType {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum StateFunctionType {
+    /// Reported before the first advance
+    #[default]
     Error,
     GameStart,
     RoundStart,
@@ -68,44 +76,74 @@ impl From<CStateFunctionType> for StateFunctionType {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Meld {
     pub meld_type: MeldType,
-    pub start: i32,
+    pub start: Piece,
 }
 
-impl From<CMeld> for Meld {
-    fn from(c_meld: CMeld) -> Self {
-        Self {
+impl TryFrom<CMeld> for Meld {
+    type Error = MahjongFFIError;
+
+    fn try_from(c_meld: CMeld) -> Result<Self, Self::Error> {
+        Ok(Self {
             meld_type: c_meld.meld_type.into(),
-            start: c_meld.start,
+            start: c_meld.start.try_into()?,
+        })
+    }
+}
+
+impl Meld {
+    /// Get every piece making up the meld, lowest first
+    pub fn pieces(&self) -> Vec<Piece> {
+        match self.meld_type {
+            MeldType::Chi => {
+                let mut pieces = vec![self.start];
+                while pieces.len() < 3 {
+                    match pieces[pieces.len() - 1].next_in_suit() {
+                        Some(next) => pieces.push(next),
+                        None => break,
+                    }
+                }
+                pieces
+            }
+            MeldType::Pon => vec![self.start; 3],
+            MeldType::Kan | MeldType::ConcealedKan => vec![self.start; 4],
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+fn try_pieces(c_pieces: &[CPiece]) -> Result<Vec<Piece>, MahjongFFIError> {
+    c_pieces.iter().map(|&piece| piece.try_into()).collect()
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Hand {
-    pub live_pieces: Vec<i32>,
+    pub live_pieces: Vec<Piece>,
     pub melds: Vec<Meld>,
-    pub discards: Vec<i32>,
+    pub discards: Vec<Piece>,
     pub open: bool,
     pub riichi: bool,
     pub riichi_piece_discard: i32,
     pub riichi_round: i32,
 }
 
-impl From<CHand> for Hand {
-    fn from(c_hand: CHand) -> Self {
-        let live_pieces = c_hand.live_pieces[..c_hand.live_piece_count as usize].to_vec();
+impl TryFrom<CHand> for Hand {
+    type Error = MahjongFFIError;
+
+    fn try_from(c_hand: CHand) -> Result<Self, Self::Error> {
+        let live_pieces = try_pieces(&c_hand.live_pieces[..c_hand.live_piece_count as usize])?;
 
         let melds = c_hand.melds[..c_hand.meld_count as usize]
             .iter()
-            .map(|&meld| meld.into())
-            .collect();
+            .map(|&meld| meld.try_into())
+            .collect::<Result<_, _>>()?;
 
-        let discards = c_hand.discards[..c_hand.discard_count as usize].to_vec();
+        let discards = try_pieces(&c_hand.discards[..c_hand.discard_count as usize])?;
 
-        Self {
+        Ok(Self {
             live_pieces,
             melds,
             discards,
@@ -113,7 +151,7 @@ impl From<CHand> for Hand {
             riichi: c_hand.riichi,
             riichi_piece_discard: c_hand.riichi_piece_discard,
             riichi_round: c_hand.riichi_round,
-        }
+        })
     }
 }
 
@@ -134,7 +172,7 @@ impl Hand {
     }
 
     /// Add a piece to the live pieces (example method showing Vec usage)
-    pub fn add_live_piece(&mut self, piece: i32) {
+    pub fn add_live_piece(&mut self, piece: Piece) {
         self.live_pieces.push(piece);
     }
 
@@ -144,12 +182,13 @@ impl Hand {
     }
 
     /// Add a discard to the hand (example method showing Vec usage)
-    pub fn add_discard(&mut self, piece: i32) {
+    pub fn add_discard(&mut self, piece: Piece) {
         self.discards.push(piece);
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ObservedGameState {
     pub current_player: i32,
     pub turn_num: i32,
@@ -160,7 +199,7 @@ pub struct ObservedGameState {
     pub last_caller: i32,
     pub concealed_kan: bool,
     pub seed: u64,
-    pub pending_piece: i32,
+    pub pending_piece: Option<Piece>,
     pub scores: [i32; 4],
     pub points: [i32; 4],
     pub has_ronned: [bool; 4],
@@ -170,16 +209,24 @@ pub struct ObservedGameState {
     pub next_state: StateFunctionType,
 }
 
-impl From<CObservedGameState> for ObservedGameState {
-    fn from(c_state: CObservedGameState) -> Self {
+impl TryFrom<CObservedGameState> for ObservedGameState {
+    type Error = MahjongFFIError;
+
+    fn try_from(c_state: CObservedGameState) -> Result<Self, Self::Error> {
         let hands = [
-            c_state.hands[0].into(),
-            c_state.hands[1].into(),
-            c_state.hands[2].into(),
-            c_state.hands[3].into(),
+            c_state.hands[0].try_into()?,
+            c_state.hands[1].try_into()?,
+            c_state.hands[2].try_into()?,
+            c_state.hands[3].try_into()?,
         ];
 
-        Self {
+        // No piece is pending outside of call decisions, which the C API reports as 0
+        let pending_piece = match c_state.pending_piece {
+            0 => None,
+            piece => Some(piece.try_into()?),
+        };
+
+        Ok(Self {
             current_player: c_state.current_player,
             turn_num: c_state.turn_num,
             round_num: c_state.round_num,
@@ -189,7 +236,7 @@ impl From<CObservedGameState> for ObservedGameState {
             last_caller: c_state.last_caller,
             concealed_kan: c_state.concealed_kan,
             seed: c_state.seed,
-            pending_piece: c_state.pending_piece,
+            pending_piece,
             scores: c_state.scores,
             points: c_state.points,
             has_ronned: c_state.has_ronned,
@@ -197,7 +244,7 @@ impl From<CObservedGameState> for ObservedGameState {
             prev_state: c_state.prev_state.into(),
             curr_state: c_state.curr_state.into(),
             next_state: c_state.next_state.into(),
-        }
+        })
     }
 }
 
@@ -233,7 +280,7 @@ impl ObservedGameState {
     }
 
     /// Get the current pending piece
-    pub fn pending_piece(&self) -> i32 {
+    pub fn pending_piece(&self) -> Option<Piece> {
         self.pending_piece
     }
 
@@ -0,0 +1,423 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::ffi::{error::MahjongFFIError, observe::CPiece};
+
+/// Bit layout of libmahjong's `Piece` encoding.
+///
+/// ```text
+///  7        6 5      4         3 2 1 0
+/// [terminal][suit  ][red five][  rank  ]
+/// ```
+///
+/// Honors use suit `0` and carry the terminal bit, so the terminal bit is
+/// effectively "terminal or honor".
+const RANK_MASK: u8 = 0b0000_1111;
+const RED_FIVE_BIT: u8 = 1 << 4;
+const SUIT_MASK: u8 = 0b0110_0000;
+const TERMINAL_BIT: u8 = 1 << 7;
+
+const HONOR_SUIT_BITS: u8 = 0;
+const BAMBOO_SUIT_BITS: u8 = 1 << 5;
+const PIN_SUIT_BITS: u8 = 2 << 5;
+const CHARACTER_SUIT_BITS: u8 = 3 << 5;
+
+/// Number of distinct tile kinds (ignoring red fives)
+pub const TILE_KINDS: usize = 34;
+
+/// Number of tiles in a full set
+pub const TILE_COUNT: usize = 136;
+
+/// Pieces set aside from the live wall at the start of a round
+pub const DEAD_WALL_SIZE: usize = 14;
+
+/// One of the three numbered suits
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Suit {
+    Character,
+    Pin,
+    Bamboo,
+}
+
+impl Suit {
+    /// All suits in sort order
+    pub const ALL: [Suit; 3] = [Suit::Character, Suit::Pin, Suit::Bamboo];
+
+    fn bits(self) -> u8 {
+        match self {
+            Suit::Character => CHARACTER_SUIT_BITS,
+            Suit::Pin => PIN_SUIT_BITS,
+            Suit::Bamboo => BAMBOO_SUIT_BITS,
+        }
+    }
+
+    fn offset(self) -> usize {
+        match self {
+            Suit::Character => 0,
+            Suit::Pin => 9,
+            Suit::Bamboo => 18,
+        }
+    }
+}
+
+/// Wind and dragon tiles
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Honor {
+    East,
+    South,
+    West,
+    North,
+    White,
+    Green,
+    Red,
+}
+
+impl Honor {
+    /// All honors in sort order
+    pub const ALL: [Honor; 7] = [
+        Honor::East,
+        Honor::South,
+        Honor::West,
+        Honor::North,
+        Honor::White,
+        Honor::Green,
+        Honor::Red,
+    ];
+
+    /// libmahjong numbers dragons before winds
+    fn rank_bits(self) -> u8 {
+        match self {
+            Honor::Red => 1,
+            Honor::White => 2,
+            Honor::Green => 3,
+            Honor::East => 4,
+            Honor::South => 5,
+            Honor::West => 6,
+            Honor::North => 7,
+        }
+    }
+
+    fn from_rank_bits(bits: u8) -> Option<Self> {
+        match bits {
+            1 => Some(Honor::Red),
+            2 => Some(Honor::White),
+            3 => Some(Honor::Green),
+            4 => Some(Honor::East),
+            5 => Some(Honor::South),
+            6 => Some(Honor::West),
+            7 => Some(Honor::North),
+            _ => None,
+        }
+    }
+
+    /// Check if the honor is one of the four winds
+    pub fn is_wind(self) -> bool {
+        matches!(
+            self,
+            Honor::East | Honor::South | Honor::West | Honor::North
+        )
+    }
+
+    /// Check if the honor is one of the three dragons
+    pub fn is_dragon(self) -> bool {
+        !self.is_wind()
+    }
+
+    /// Get the wind for a seat offset from the dealer (0 = East)
+    pub fn wind_for_seat(offset: usize) -> Self {
+        Honor::ALL[offset % 4]
+    }
+}
+
+/// A single mahjong tile, stored in libmahjong's native encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Piece(u8);
+
+impl Piece {
+    /// Create a numbered tile. Returns `None` if the rank is outside `1..=9`.
+    pub fn suited(suit: Suit, rank: u8) -> Option<Self> {
+        if !(1..=9).contains(&rank) {
+            return None;
+        }
+        let terminal = if rank == 1 || rank == 9 {
+            TERMINAL_BIT
+        } else {
+            0
+        };
+        Some(Self(terminal | suit.bits() | rank))
+    }
+
+    /// Create a red five of the given suit
+    pub fn red_five(suit: Suit) -> Self {
+        Self(suit.bits() | RED_FIVE_BIT | 5)
+    }
+
+    /// Create an honor tile
+    pub fn honor(honor: Honor) -> Self {
+        Self(TERMINAL_BIT | HONOR_SUIT_BITS | honor.rank_bits())
+    }
+
+    /// Build a tile from its kind index (`0..34`, see [`Piece::index`]).
+    /// The result is never a red five.
+    pub fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0..=26 => Self::suited(Suit::ALL[index / 9], (index % 9) as u8 + 1),
+            27..=33 => Some(Self::honor(Honor::ALL[index - 27])),
+            _ => None,
+        }
+    }
+
+    /// Get the raw libmahjong encoding
+    pub fn raw(self) -> u8 {
+        self.0
+    }
+
+    /// Get the suit, or `None` for honors
+    pub fn suit(self) -> Option<Suit> {
+        match self.0 & SUIT_MASK {
+            CHARACTER_SUIT_BITS => Some(Suit::Character),
+            PIN_SUIT_BITS => Some(Suit::Pin),
+            BAMBOO_SUIT_BITS => Some(Suit::Bamboo),
+            _ => None,
+        }
+    }
+
+    /// Get the honor, or `None` for numbered tiles
+    pub fn honor_type(self) -> Option<Honor> {
+        if self.is_honor() {
+            Honor::from_rank_bits(self.0 & RANK_MASK)
+        } else {
+            None
+        }
+    }
+
+    /// Get the rank (`1..=9`) of a numbered tile, or `None` for honors
+    pub fn rank(self) -> Option<u8> {
+        self.suit().map(|_| self.0 & RANK_MASK)
+    }
+
+    /// Check if the tile is a wind or dragon
+    pub fn is_honor(self) -> bool {
+        self.0 & SUIT_MASK == HONOR_SUIT_BITS
+    }
+
+    /// Check if the tile is a 1 or 9 of a numbered suit
+    pub fn is_terminal(self) -> bool {
+        !self.is_honor() && self.0 & TERMINAL_BIT != 0
+    }
+
+    /// Check if the tile is a terminal or an honor
+    pub fn is_terminal_or_honor(self) -> bool {
+        self.0 & TERMINAL_BIT != 0
+    }
+
+    /// Check if the tile is a numbered tile from 2 to 8
+    pub fn is_simple(self) -> bool {
+        !self.is_terminal_or_honor()
+    }
+
+    /// Check if the tile is a red five
+    pub fn is_red_five(self) -> bool {
+        self.0 & RED_FIVE_BIT != 0
+    }
+
+    /// Get the same tile with the red five flag cleared
+    pub fn normalized(self) -> Self {
+        Self(self.0 & !RED_FIVE_BIT)
+    }
+
+    /// Get the tile kind index: characters `0..9`, pins `9..18`,
+    /// bamboo `18..27`, then East, South, West, North, White, Green, Red.
+    /// Red fives share the index of their plain counterpart.
+    pub fn index(self) -> usize {
+        match (self.suit(), self.honor_type()) {
+            (Some(suit), _) => suit.offset() + (self.0 & RANK_MASK) as usize - 1,
+            (None, Some(honor)) => 27 + honor as usize,
+            (None, None) => unreachable!("Piece always holds a valid encoding"),
+        }
+    }
+
+    /// Get the next tile in the same suit, used for sequences.
+    /// Returns `None` for honors and nines.
+    pub fn next_in_suit(self) -> Option<Self> {
+        Self::suited(self.suit()?, self.rank()? + 1)
+    }
+
+    /// Get the dora indicated by this tile when it is a dora indicator
+    pub fn dora_from_indicator(self) -> Self {
+        match (self.suit(), self.honor_type()) {
+            (Some(suit), _) => {
+                let rank = self.rank().unwrap_or(1);
+                Self::suited(suit, rank % 9 + 1).unwrap_or(self)
+            }
+            (None, Some(honor)) => {
+                let next = if honor.is_wind() {
+                    Honor::ALL[(honor as usize + 1) % 4]
+                } else {
+                    Honor::ALL[4 + (honor as usize - 4 + 1) % 3]
+                };
+                Self::honor(next)
+            }
+            (None, None) => self,
+        }
+    }
+}
+
+impl Ord for Piece {
+    /// Tiles sort by kind index, with a red five immediately after its
+    /// plain counterpart
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.index()
+            .cmp(&other.index())
+            .then(self.is_red_five().cmp(&other.is_red_five()))
+    }
+}
+
+impl PartialOrd for Piece {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl TryFrom<CPiece> for Piece {
+    type Error = MahjongFFIError;
+
+    fn try_from(value: CPiece) -> Result<Self, Self::Error> {
+        let raw = u8::try_from(value).map_err(|_| MahjongFFIError::InvalidPiece(value))?;
+        let rank = raw & RANK_MASK;
+        let red = raw & RED_FIVE_BIT != 0;
+        let terminal = raw & TERMINAL_BIT != 0;
+
+        let valid = match raw & SUIT_MASK {
+            HONOR_SUIT_BITS => terminal && !red && (1..=7).contains(&rank),
+            _ if red => rank == 5 && !terminal,
+            _ => (1..=9).contains(&rank) && terminal == (rank == 1 || rank == 9),
+        };
+
+        if valid {
+            Ok(Self(raw))
+        } else {
+            Err(MahjongFFIError::InvalidPiece(value))
+        }
+    }
+}
+
+impl From<Piece> for CPiece {
+    fn from(piece: Piece) -> Self {
+        piece.0 as CPiece
+    }
+}
+
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.suit(), self.honor_type()) {
+            (Some(suit), _) => {
+                let rank = if self.is_red_five() {
+                    0
+                } else {
+                    self.0 & RANK_MASK
+                };
+                let suffix = match suit {
+                    Suit::Character => 'm',
+                    Suit::Pin => 'p',
+                    Suit::Bamboo => 's',
+                };
+                write!(f, "{}{}", rank, suffix)
+            }
+            (None, Some(honor)) => write!(f, "{}z", honor as u8 + 1),
+            (None, None) => write!(f, "?"),
+        }
+    }
+}
+
+/// Pieces serialize as their compact notation, e.g. `"5m"` or `"0p"`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Piece {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Piece {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let notation = String::deserialize(deserializer)?;
+        notation.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_index_round_trips() {
+        for index in 0..TILE_KINDS {
+            let piece = Piece::from_index(index).unwrap();
+            assert_eq!(piece.index(), index);
+            assert_eq!(Piece::try_from(CPiece::from(piece)).unwrap(), piece);
+        }
+        assert_eq!(Piece::from_index(TILE_KINDS), None);
+    }
+
+    #[test]
+    fn decodes_tile_properties() {
+        let one_pin = Piece::suited(Suit::Pin, 1).unwrap();
+        assert!(one_pin.is_terminal());
+        assert!(!one_pin.is_honor());
+        assert_eq!(one_pin.rank(), Some(1));
+        assert_eq!(one_pin.suit(), Some(Suit::Pin));
+
+        let red = Piece::red_five(Suit::Bamboo);
+        assert!(red.is_red_five());
+        assert!(red.is_simple());
+        assert_eq!(red.rank(), Some(5));
+        assert_eq!(red.normalized(), Piece::suited(Suit::Bamboo, 5).unwrap());
+        assert_eq!(red.index(), red.normalized().index());
+
+        let north = Piece::honor(Honor::North);
+        assert!(north.is_honor());
+        assert!(!north.is_terminal());
+        assert!(north.is_terminal_or_honor());
+        assert_eq!(north.rank(), None);
+        assert_eq!(north.honor_type(), Some(Honor::North));
+    }
+
+    #[test]
+    fn rejects_invalid_encodings() {
+        for raw in [0, -1, 256, 10, 0x80, 0x88, 0x21, 0x36, 0xB5, 0x95] {
+            assert!(
+                Piece::try_from(raw).is_err(),
+                "{:#x} should be rejected",
+                raw
+            );
+        }
+    }
+
+    #[test]
+    fn orders_by_suit_then_rank() {
+        let mut pieces = [
+            Piece::honor(Honor::East),
+            Piece::red_five(Suit::Character),
+            Piece::suited(Suit::Bamboo, 1).unwrap(),
+            Piece::suited(Suit::Character, 5).unwrap(),
+            Piece::suited(Suit::Character, 9).unwrap(),
+        ];
+        pieces.sort();
+        let names: Vec<_> = pieces.iter().map(ToString::to_string).collect();
+        assert_eq!(names, ["5m", "0m", "9m", "1s", "1z"]);
+    }
+
+    #[test]
+    fn dora_wraps_within_group() {
+        let dora = |p: Piece| p.dora_from_indicator();
+        assert_eq!(
+            dora(Piece::suited(Suit::Pin, 9).unwrap()),
+            Piece::suited(Suit::Pin, 1).unwrap()
+        );
+        assert_eq!(dora(Piece::honor(Honor::North)), Piece::honor(Honor::East));
+        assert_eq!(dora(Piece::honor(Honor::Red)), Piece::honor(Honor::White));
+    }
+}
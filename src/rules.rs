@@ -0,0 +1,187 @@
+//! Game rules carried by [`GameSettings`](crate::settings::GameSettings).
+//!
+//! Rules are validated on the Rust side, then handed to `InitGameState` in
+//! [`CGameSettings`](crate::ffi::gamesettings::CGameSettings) or played by
+//! the pure-Rust engine. Presets approximate the published rule sets.
+
+/// Errors produced when validating a [`RuleSet`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RuleError {
+    #[error("Starting points must be a positive multiple of 100, got {0}")]
+    InvalidStartingPoints(i32),
+    #[error("Starting points {0} cannot cover a riichi deposit")]
+    StartingPointsBelowRiichiDeposit(i32),
+    #[error("Unknown rule preset '{0}'")]
+    UnknownPreset(String),
+}
+
+/// Number of prevailing winds played
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameLength {
+    /// East round only
+    Tonpuusen,
+    /// East and South rounds
+    Hanchan,
+}
+
+/// What happens to riichi deposits still on the table when the game ends
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeftoverRiichi {
+    /// Awarded to the player in first place
+    ToFirstPlace,
+    /// Removed from play
+    Forfeited,
+}
+
+/// Points a riichi declaration puts on the table
+pub const RIICHI_DEPOSIT: i32 = 1000;
+
+/// Names accepted by [`RuleSet::preset`]
+pub const PRESETS: [&str; 3] = ["WRC", "EMA", "Tenhou ranked"];
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSet {
+    pub length: GameLength,
+    pub starting_points: i32,
+    /// One red five per suit
+    pub red_fives: bool,
+    /// Allow tanyao with an open hand
+    pub open_tanyao: bool,
+    /// Allow more than one player to ron the same discard; otherwise the
+    /// first player in turn order wins (head bump)
+    pub double_ron: bool,
+    /// End the game when a player drops below zero points
+    pub tobi: bool,
+    pub leftover_riichi: LeftoverRiichi,
+}
+
+/// Tenhou ranked rules, for settings that pick none. This is the crate's
+/// choice of preset, not a description of what libmahjong plays on its own.
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::tenhou_ranked()
+    }
+}
+
+impl RuleSet {
+    /// World Riichi Championship rules
+    pub fn wrc() -> Self {
+        Self {
+            length: GameLength::Hanchan,
+            starting_points: 30000,
+            red_fives: false,
+            open_tanyao: true,
+            double_ron: false,
+            tobi: false,
+            leftover_riichi: LeftoverRiichi::Forfeited,
+        }
+    }
+
+    /// European Mahjong Association riichi rules
+    pub fn ema() -> Self {
+        Self {
+            length: GameLength::Hanchan,
+            starting_points: 30000,
+            red_fives: false,
+            open_tanyao: true,
+            double_ron: true,
+            tobi: false,
+            leftover_riichi: LeftoverRiichi::ToFirstPlace,
+        }
+    }
+
+    /// Tenhou ranked hanchan rules
+    pub fn tenhou_ranked() -> Self {
+        Self {
+            length: GameLength::Hanchan,
+            starting_points: 25000,
+            red_fives: true,
+            open_tanyao: true,
+            double_ron: true,
+            tobi: true,
+            leftover_riichi: LeftoverRiichi::ToFirstPlace,
+        }
+    }
+
+    /// Look up a preset by name, ignoring case (see [`PRESETS`])
+    pub fn preset(name: &str) -> Result<Self, RuleError> {
+        match name.to_ascii_lowercase().as_str() {
+            "wrc" => Ok(Self::wrc()),
+            "ema" => Ok(Self::ema()),
+            "tenhou ranked" | "tenhou" => Ok(Self::tenhou_ranked()),
+            _ => Err(RuleError::UnknownPreset(name.to_string())),
+        }
+    }
+
+    /// Play only the East round
+    pub fn tonpuusen(mut self) -> Self {
+        self.length = GameLength::Tonpuusen;
+        self
+    }
+
+    /// Check that the starting points are playable
+    pub fn validate(&self) -> Result<(), RuleError> {
+        if self.starting_points <= 0 || self.starting_points % 100 != 0 {
+            return Err(RuleError::InvalidStartingPoints(self.starting_points));
+        }
+        if self.starting_points < RIICHI_DEPOSIT {
+            return Err(RuleError::StartingPointsBelowRiichiDeposit(
+                self.starting_points,
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::{error::MahjongFFIError, gamesettings::OwnedCGameSettings};
+    use crate::settings::GameSettings;
+
+    #[test]
+    fn presets_are_valid() {
+        for name in PRESETS {
+            let rules = RuleSet::preset(name).unwrap();
+            assert_eq!(rules.validate(), Ok(()));
+        }
+        assert_eq!(RuleSet::preset("tenhou RANKED"), Ok(RuleSet::default()));
+        assert!(RuleSet::preset("M-League").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_starting_points() {
+        let mut rules = RuleSet::wrc();
+        rules.starting_points = 25050;
+        assert_eq!(
+            rules.validate(),
+            Err(RuleError::InvalidStartingPoints(25050))
+        );
+        rules.starting_points = 500;
+        assert_eq!(
+            rules.validate(),
+            Err(RuleError::StartingPointsBelowRiichiDeposit(500))
+        );
+    }
+
+    #[test]
+    fn settings_are_validated_before_conversion() {
+        let settings = GameSettings {
+            seed: 1,
+            seat_controllers: Default::default(),
+            rules: RuleSet {
+                starting_points: -100,
+                ..RuleSet::ema()
+            },
+        };
+        assert!(matches!(
+            OwnedCGameSettings::try_from(settings),
+            Err(MahjongFFIError::InvalidRules(
+                RuleError::InvalidStartingPoints(-100)
+            ))
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+//! Drive a [`GameState`] to completion.
+//!
+//! [`GameRunner`] yields every observed state, starting with the freshly
+//! initialised one, and stops after the `GameEnd` state or when the native
+//! library reports that the game has ended. States that cannot follow the
+//! previous one according to [`StateFunctionType::can_transition_to`] are
+//! recorded, or stop the runner when strict transitions are enabled.
+
+use crate::ffi::{error::MahjongFFIError, gamesettings::OwnedCGameSettings, gamestate::GameState};
+use crate::observe::{ObservedGameState, StateFunctionType};
+
+/// Default number of advances before a game is considered stuck
+pub const DEFAULT_STEP_LIMIT: usize = 10_000;
+
+/// Final outcome of a game driven by [`GameRunner`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSummary {
+    /// Number of times the game state was advanced
+    pub steps: usize,
+    /// Number of rounds that reached `RoundEnd`
+    pub rounds: usize,
+    /// Whether the `GameEnd` state was observed
+    pub reached_game_end: bool,
+    /// The last state observed
+    pub final_state: ObservedGameState,
+    /// States that could not follow the state before them
+    pub illegal_transitions: Vec<IllegalTransition>,
+}
+
+impl GameSummary {
+    /// Get the final points for all players
+    pub fn points(&self) -> &[i32; 4] {
+        &self.final_state.points
+    }
+
+    /// Get the final placement (0 = first) of each seat. Ties go to the
+    /// lower seat.
+    pub fn placements(&self) -> [usize; 4] {
+        placements(&self.final_state.points)
+    }
+}
+
+/// A state that could not follow the state before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IllegalTransition {
+    /// Number of advances when the state was observed
+    pub step: usize,
+    pub from: StateFunctionType,
+    pub to: StateFunctionType,
+}
+
+impl std::fmt::Display for IllegalTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "step {}: {:?} -> {:?}", self.step, self.from, self.to)
+    }
+}
+
+/// Rank seats by points, breaking ties in favour of the lower seat
+pub fn placements(points: &[i32; 4]) -> [usize; 4] {
+    let mut order = [0, 1, 2, 3];
+    order.sort_by_key(|&seat| (std::cmp::Reverse(points[seat]), seat));
+
+    let mut placements = [0; 4];
+    for (place, seat) in order.into_iter().enumerate() {
+        placements[seat] = place;
+    }
+    placements
+}
+
+/// Iterator over the observed states of a game
+pub struct GameRunner {
+    state: Option<GameState>,
+    step_limit: usize,
+    strict_transitions: bool,
+    steps: usize,
+    rounds: usize,
+    started: bool,
+    finished: bool,
+    reached_game_end: bool,
+    last: Option<ObservedGameState>,
+    illegal_transitions: Vec<IllegalTransition>,
+    error: Option<MahjongFFIError>,
+}
+
+impl GameRunner {
+    /// Create a runner over an existing game state
+    pub fn new(state: GameState) -> Self {
+        Self {
+            state: Some(state),
+            step_limit: DEFAULT_STEP_LIMIT,
+            strict_transitions: false,
+            steps: 0,
+            rounds: 0,
+            started: false,
+            finished: false,
+            reached_game_end: false,
+            last: None,
+            illegal_transitions: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Create a new game from settings and a runner over it
+    pub fn from_settings<S: TryInto<OwnedCGameSettings, Error = MahjongFFIError>>(
+        settings: S,
+    ) -> Result<Self, MahjongFFIError> {
+        Ok(Self::new(GameState::new(settings)?))
+    }
+
+    /// Set the maximum number of advances before the runner gives up
+    pub fn with_step_limit(mut self, step_limit: usize) -> Self {
+        self.step_limit = step_limit;
+        self
+    }
+
+    /// Stop with [`MahjongFFIError::IllegalTransition`] on the first illegal
+    /// transition instead of recording it
+    pub fn with_strict_transitions(mut self, strict: bool) -> Self {
+        self.strict_transitions = strict;
+        self
+    }
+
+    /// Get the number of advances performed so far
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Get the error that stopped the runner, if any
+    pub fn error(&self) -> Option<&MahjongFFIError> {
+        self.error.as_ref()
+    }
+
+    /// Get the illegal transitions seen so far
+    pub fn illegal_transitions(&self) -> &[IllegalTransition] {
+        &self.illegal_transitions
+    }
+
+    /// Get the most recently observed state
+    pub fn last_observed(&self) -> Option<&ObservedGameState> {
+        self.last.as_ref()
+    }
+
+    fn fail(&mut self, error: MahjongFFIError) -> Option<ObservedGameState> {
+        self.error = Some(error);
+        self.finished = true;
+        None
+    }
+
+    /// Drive the game to completion and summarise the result
+    pub fn run(mut self) -> Result<GameSummary, MahjongFFIError> {
+        self.by_ref().for_each(drop);
+        self.summary()
+    }
+
+    /// Consume the runner and summarise the game so far. Fails with the
+    /// error that stopped the runner, if any.
+    pub fn summary(self) -> Result<GameSummary, MahjongFFIError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let final_state = self.last.ok_or(MahjongFFIError::GameStateConsumed)?;
+
+        Ok(GameSummary {
+            steps: self.steps,
+            rounds: self.rounds,
+            reached_game_end: self.reached_game_end,
+            final_state,
+            illegal_transitions: self.illegal_transitions,
+        })
+    }
+}
+
+impl Iterator for GameRunner {
+    type Item = ObservedGameState;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        if self.started {
+            if self.steps >= self.step_limit {
+                return self.fail(MahjongFFIError::StepLimitExceeded(self.step_limit));
+            }
+
+            let state = self.state.take()?;
+            match state.advance() {
+                Ok(next) => self.state = Some(next),
+                Err(MahjongFFIError::GameEnded) => {
+                    self.finished = true;
+                    return None;
+                }
+                Err(error) => return self.fail(error),
+            }
+            self.steps += 1;
+        }
+        self.started = true;
+
+        let observed = match self.state.as_ref()?.try_observe() {
+            Ok(observed) => observed,
+            Err(error) => return self.fail(error),
+        };
+
+        if let Some(last) = &self.last {
+            let (from, to) = (last.curr_state, observed.curr_state);
+            if !from.can_transition_to(to) {
+                if self.strict_transitions {
+                    return self.fail(MahjongFFIError::IllegalTransition { from, to });
+                }
+                self.illegal_transitions.push(IllegalTransition {
+                    step: self.steps,
+                    from,
+                    to,
+                });
+            }
+        }
+
+        match observed.curr_state {
+            StateFunctionType::RoundEnd => self.rounds += 1,
+            StateFunctionType::GameEnd => {
+                self.reached_game_end = true;
+                self.finished = true;
+            }
+            _ => {}
+        }
+
+        self.last = Some(observed.clone());
+        Some(observed)
+    }
+}
+
+impl IntoIterator for GameState {
+    type Item = ObservedGameState;
+    type IntoIter = GameRunner;
+
+    fn into_iter(self) -> Self::IntoIter {
+        GameRunner::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engine_games_follow_the_transition_table() {
+        use crate::ffi::gamestate::{Backend, GameState};
+        use crate::settings::GameSettings;
+
+        let settings = GameSettings::builder()
+            .seed(11)
+            .all_seats("ThriceBot")
+            .build()
+            .unwrap();
+        let state = GameState::with_backend(settings, Backend::Engine).unwrap();
+        let mut runner = GameRunner::new(state).with_strict_transitions(true);
+        assert_eq!(runner.by_ref().take(300).count(), 300);
+        assert!(runner.error().is_none());
+        assert!(runner.illegal_transitions().is_empty());
+    }
+
+    #[test]
+    fn summary_reports_the_error_that_stopped_the_runner() {
+        use crate::ffi::gamestate::{Backend, GameState};
+        use crate::settings::GameSettings;
+
+        let settings = GameSettings::builder()
+            .seed(11)
+            .all_seats("ThriceBot")
+            .build()
+            .unwrap();
+        let state = GameState::with_backend(settings, Backend::Engine).unwrap();
+        let mut runner = GameRunner::new(state).with_step_limit(10);
+        runner.by_ref().for_each(drop);
+        assert!(matches!(
+            runner.error(),
+            Some(MahjongFFIError::StepLimitExceeded(10))
+        ));
+        assert!(matches!(
+            runner.summary(),
+            Err(MahjongFFIError::StepLimitExceeded(10))
+        ));
+    }
+
+    #[test]
+    fn placements_break_ties_by_seat() {
+        assert_eq!(placements(&[25000, 30000, 25000, 20000]), [1, 0, 2, 3]);
+    }
+}
@@ -0,0 +1,1044 @@
+//! Yaku, han/fu and payment calculation for completed hands.
+//!
+//! [`score`] tries every way of splitting a winning hand into melds and a
+//! pair (and every group the winning tile could have completed) and keeps the
+//! arrangement worth the most points.
+
+use crate::analysis::{tile_counts, TileCounts};
+use crate::observe::{Hand, MeldType};
+use crate::piece::{Honor, Piece, TILE_KINDS};
+
+/// Errors returned when a hand cannot be scored
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ScoringError {
+    #[error("Hand does not contain the winning piece {0}")]
+    MissingWinningPiece(Piece),
+    #[error("Hand has {0} pieces, which cannot form a winning hand")]
+    InvalidPieceCount(usize),
+    #[error("Hand is not a complete winning shape")]
+    NotComplete,
+    #[error("Hand is complete but has no yaku")]
+    NoYaku,
+}
+
+/// Every yaku recognised by the scorer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Yaku {
+    Riichi,
+    DoubleRiichi,
+    Ippatsu,
+    MenzenTsumo,
+    Pinfu,
+    Iipeikou,
+    Tanyao,
+    SeatWind,
+    RoundWind,
+    WhiteDragon,
+    GreenDragon,
+    RedDragon,
+    Haitei,
+    Houtei,
+    Rinshan,
+    Chankan,
+    SanshokuDoujun,
+    Ittsu,
+    Chanta,
+    Chiitoitsu,
+    Toitoi,
+    Sanankou,
+    SanshokuDoukou,
+    Sankantsu,
+    Shousangen,
+    Honroutou,
+    Ryanpeikou,
+    Junchan,
+    Honitsu,
+    Chinitsu,
+    KokushiMusou,
+    Suuankou,
+    Daisangen,
+    Shousuushii,
+    Daisuushii,
+    Tsuuiisou,
+    Chinroutou,
+    Ryuuiisou,
+    ChuurenPoutou,
+    Suukantsu,
+    Tenhou,
+    Chiihou,
+}
+
+impl Yaku {
+    /// Check if the yaku is a yakuman
+    pub fn is_yakuman(self) -> bool {
+        self >= Yaku::KokushiMusou
+    }
+
+    /// Get the han value of the yaku, with the open-hand reduction applied.
+    /// Yakuman report 13.
+    pub fn han(self, open: bool) -> u8 {
+        let reduced = |closed: u8| if open { closed - 1 } else { closed };
+        match self {
+            Yaku::DoubleRiichi | Yaku::Chiitoitsu => 2,
+            Yaku::Toitoi
+            | Yaku::Sanankou
+            | Yaku::SanshokuDoukou
+            | Yaku::Sankantsu
+            | Yaku::Shousangen
+            | Yaku::Honroutou => 2,
+            Yaku::SanshokuDoujun | Yaku::Ittsu | Yaku::Chanta => reduced(2),
+            Yaku::Ryanpeikou => 3,
+            Yaku::Junchan | Yaku::Honitsu => reduced(3),
+            Yaku::Chinitsu => reduced(6),
+            yaku if yaku.is_yakuman() => 13,
+            _ => 1,
+        }
+    }
+}
+
+/// Named score limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Mangan,
+    Haneman,
+    Baiman,
+    Sanbaiman,
+    KazoeYakuman,
+    /// One or more stacked yakuman
+    Yakuman(u8),
+}
+
+/// Shape of the wait the winning piece completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitShape {
+    Ryanmen,
+    Kanchan,
+    Penchan,
+    Shanpon,
+    Tanki,
+}
+
+/// Everything about a win that is not visible in the hand itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WinContext {
+    pub winning_piece: Piece,
+    pub tsumo: bool,
+    pub seat_wind: Honor,
+    pub round_wind: Honor,
+    pub riichi: bool,
+    pub double_riichi: bool,
+    pub ippatsu: bool,
+    /// Won on the last tile of the wall (haitei) or its discard (houtei)
+    pub last_tile: bool,
+    pub rinshan: bool,
+    pub chankan: bool,
+    /// Won on the first uninterrupted draw (tenhou / chiihou)
+    pub first_turn: bool,
+    pub open_tanyao: bool,
+    pub dora_indicators: Vec<Piece>,
+    pub ura_dora_indicators: Vec<Piece>,
+    pub honba: u32,
+    pub riichi_sticks: u32,
+}
+
+impl WinContext {
+    /// Context for a win by discard, with East seat and round and no extras
+    pub fn ron(winning_piece: Piece) -> Self {
+        Self {
+            winning_piece,
+            tsumo: false,
+            seat_wind: Honor::East,
+            round_wind: Honor::East,
+            riichi: false,
+            double_riichi: false,
+            ippatsu: false,
+            last_tile: false,
+            rinshan: false,
+            chankan: false,
+            first_turn: false,
+            open_tanyao: true,
+            dora_indicators: Vec::new(),
+            ura_dora_indicators: Vec::new(),
+            honba: 0,
+            riichi_sticks: 0,
+        }
+    }
+
+    /// Context for a self-drawn win, with East seat and round and no extras
+    pub fn tsumo(winning_piece: Piece) -> Self {
+        Self {
+            tsumo: true,
+            ..Self::ron(winning_piece)
+        }
+    }
+
+    /// Check if the winner is the dealer
+    pub fn is_dealer(&self) -> bool {
+        self.seat_wind == Honor::East
+    }
+}
+
+/// Points paid to the winner, including honba but excluding riichi sticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Payment {
+    /// The discarder pays everything
+    Ron { discarder: u32 },
+    /// The dealer won by tsumo and every other player pays the same
+    DealerTsumo { each: u32 },
+    /// A non-dealer won by tsumo
+    Tsumo { dealer: u32, non_dealer: u32 },
+}
+
+impl Payment {
+    /// Get the total paid by all other players
+    pub fn total(&self) -> u32 {
+        match *self {
+            Payment::Ron { discarder } => discarder,
+            Payment::DealerTsumo { each } => each * 3,
+            Payment::Tsumo { dealer, non_dealer } => dealer + non_dealer * 2,
+        }
+    }
+}
+
+/// Full breakdown of a scored hand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreResult {
+    pub yaku: Vec<(Yaku, u8)>,
+    pub han: u8,
+    pub fu: u8,
+    pub dora: u8,
+    /// Red fives among the concealed pieces. Melds only record their first
+    /// piece as a plain tile, so red fives in melds are not counted.
+    pub aka_dora: u8,
+    pub ura_dora: u8,
+    pub wait: Option<WaitShape>,
+    pub limit: Option<Limit>,
+    pub base_points: u32,
+    pub payment: Payment,
+    pub riichi_sticks: u32,
+}
+
+impl ScoreResult {
+    /// Get the winner's total gain, including the riichi sticks on the table
+    pub fn total_gain(&self) -> u32 {
+        self.payment.total() + self.riichi_sticks * 1000
+    }
+
+    /// Get the point change for every seat. `discarder` is `None` for tsumo.
+    pub fn point_deltas(&self, winner: usize, discarder: Option<usize>, dealer: usize) -> [i32; 4] {
+        let mut deltas = [0; 4];
+        match (self.payment, discarder) {
+            (Payment::Ron { discarder: amount }, Some(from)) => {
+                deltas[from] -= amount as i32;
+            }
+            (Payment::DealerTsumo { each }, _) => {
+                for (seat, delta) in deltas.iter_mut().enumerate() {
+                    if seat != winner {
+                        *delta -= each as i32;
+                    }
+                }
+            }
+            (
+                Payment::Tsumo {
+                    dealer: from_dealer,
+                    non_dealer,
+                },
+                _,
+            ) => {
+                for (seat, delta) in deltas.iter_mut().enumerate() {
+                    if seat == dealer {
+                        *delta -= from_dealer as i32;
+                    } else if seat != winner {
+                        *delta -= non_dealer as i32;
+                    }
+                }
+            }
+            (Payment::Ron { .. }, None) => {}
+        }
+        deltas[winner] += self.total_gain() as i32;
+        deltas
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetKind {
+    Sequence,
+    Triplet,
+    Kan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Set {
+    kind: SetKind,
+    index: usize,
+    concealed: bool,
+}
+
+impl Set {
+    fn indices(&self) -> Vec<usize> {
+        match self.kind {
+            SetKind::Sequence => vec![self.index, self.index + 1, self.index + 2],
+            SetKind::Triplet => vec![self.index; 3],
+            SetKind::Kan => vec![self.index; 4],
+        }
+    }
+
+    fn is_sequence(&self) -> bool {
+        self.kind == SetKind::Sequence
+    }
+
+    fn has_terminal_or_honor(&self) -> bool {
+        self.indices().into_iter().any(is_terminal_or_honor)
+    }
+}
+
+enum Shape {
+    Standard {
+        sets: Vec<Set>,
+        pair: usize,
+        wait: WaitShape,
+    },
+    Chiitoitsu,
+    Kokushi,
+}
+
+fn piece(index: usize) -> Piece {
+    Piece::from_index(index).expect("tile index is always below TILE_KINDS")
+}
+
+fn is_terminal_or_honor(index: usize) -> bool {
+    piece(index).is_terminal_or_honor()
+}
+
+fn is_honor(index: usize) -> bool {
+    index >= 27
+}
+
+fn honor_index(honor: Honor) -> usize {
+    Piece::honor(honor).index()
+}
+
+fn decompose(counts: &mut TileCounts, from: usize, sets: &mut Vec<Set>, out: &mut Vec<Vec<Set>>) {
+    let Some(i) = (from..TILE_KINDS).find(|&i| counts[i] > 0) else {
+        out.push(sets.clone());
+        return;
+    };
+
+    if counts[i] >= 3 {
+        counts[i] -= 3;
+        sets.push(Set {
+            kind: SetKind::Triplet,
+            index: i,
+            concealed: true,
+        });
+        decompose(counts, i, sets, out);
+        sets.pop();
+        counts[i] += 3;
+    }
+
+    if i < 27 && i % 9 <= 6 && counts[i + 1] > 0 && counts[i + 2] > 0 {
+        for count in &mut counts[i..i + 3] {
+            *count -= 1;
+        }
+        sets.push(Set {
+            kind: SetKind::Sequence,
+            index: i,
+            concealed: true,
+        });
+        decompose(counts, i, sets, out);
+        sets.pop();
+        for count in &mut counts[i..i + 3] {
+            *count += 1;
+        }
+    }
+}
+
+fn called_sets(hand: &Hand) -> Vec<Set> {
+    hand.melds
+        .iter()
+        .map(|meld| Set {
+            kind: match meld.meld_type {
+                MeldType::Chi => SetKind::Sequence,
+                MeldType::Pon => SetKind::Triplet,
+                MeldType::Kan | MeldType::ConcealedKan => SetKind::Kan,
+            },
+            index: meld.start.index(),
+            concealed: meld.meld_type == MeldType::ConcealedKan,
+        })
+        .collect()
+}
+
+fn wait_for_sequence(start: usize, win: usize) -> WaitShape {
+    let rank = start % 9;
+    match win - start {
+        1 => WaitShape::Kanchan,
+        0 if rank == 6 => WaitShape::Penchan,
+        2 if rank == 0 => WaitShape::Penchan,
+        _ => WaitShape::Ryanmen,
+    }
+}
+
+fn standard_shapes(counts: &TileCounts, called: &[Set], win: usize, tsumo: bool) -> Vec<Shape> {
+    let mut shapes = Vec::new();
+    let required = 4 - called.len();
+
+    for pair in 0..TILE_KINDS {
+        if counts[pair] < 2 {
+            continue;
+        }
+        let mut rest = *counts;
+        rest[pair] -= 2;
+
+        let mut decompositions = Vec::new();
+        decompose(&mut rest, 0, &mut Vec::new(), &mut decompositions);
+
+        for concealed_sets in decompositions {
+            if concealed_sets.len() != required {
+                continue;
+            }
+
+            if pair == win {
+                let mut sets = called.to_vec();
+                sets.extend(&concealed_sets);
+                shapes.push(Shape::Standard {
+                    sets,
+                    pair,
+                    wait: WaitShape::Tanki,
+                });
+            }
+
+            for (slot, set) in concealed_sets.iter().enumerate() {
+                if !set.indices().contains(&win) {
+                    continue;
+                }
+                let mut won_sets = concealed_sets.clone();
+                let wait = match set.kind {
+                    SetKind::Sequence => wait_for_sequence(set.index, win),
+                    _ => {
+                        // A triplet finished by another player's discard counts as open
+                        won_sets[slot].concealed = tsumo;
+                        WaitShape::Shanpon
+                    }
+                };
+                let mut sets = called.to_vec();
+                sets.extend(won_sets);
+                shapes.push(Shape::Standard { sets, pair, wait });
+            }
+        }
+    }
+
+    shapes
+}
+
+fn is_chiitoitsu(counts: &TileCounts) -> bool {
+    counts.iter().filter(|&&c| c == 2).count() == 7
+}
+
+fn is_kokushi(counts: &TileCounts) -> bool {
+    let orphans: Vec<usize> = (0..TILE_KINDS)
+        .filter(|&i| is_terminal_or_honor(i))
+        .collect();
+    orphans.iter().all(|&i| counts[i] >= 1)
+        && orphans.iter().map(|&i| counts[i] as usize).sum::<usize>() == 14
+}
+
+struct Evaluation {
+    yaku: Vec<Yaku>,
+    fu: u8,
+    wait: Option<WaitShape>,
+}
+
+/// Yaku that only depend on the set of tiles, shared by every shape
+fn tile_yaku(all: &TileCounts, open: bool, ctx: &WinContext, yaku: &mut Vec<Yaku>) {
+    let present: Vec<usize> = (0..TILE_KINDS).filter(|&i| all[i] > 0).collect();
+    let has_honors = present.iter().any(|&i| is_honor(i));
+    let suits: Vec<usize> = {
+        let mut suits: Vec<usize> = present
+            .iter()
+            .filter(|&&i| i < 27)
+            .map(|&i| i / 9)
+            .collect();
+        suits.dedup();
+        suits
+    };
+
+    if present.iter().all(|&i| !is_terminal_or_honor(i)) && (!open || ctx.open_tanyao) {
+        yaku.push(Yaku::Tanyao);
+    }
+    match (suits.len(), has_honors) {
+        (1, true) => yaku.push(Yaku::Honitsu),
+        (1, false) => yaku.push(Yaku::Chinitsu),
+        _ => {}
+    }
+    if present.iter().all(|&i| is_honor(i)) {
+        yaku.push(Yaku::Tsuuiisou);
+    } else if present.iter().all(|&i| is_terminal_or_honor(i)) {
+        if has_honors {
+            yaku.push(Yaku::Honroutou);
+        } else {
+            yaku.push(Yaku::Chinroutou);
+        }
+    }
+
+    let green = [19, 20, 21, 23, 25, honor_index(Honor::Green)];
+    if present.iter().all(|i| green.contains(i)) {
+        yaku.push(Yaku::Ryuuiisou);
+    }
+}
+
+fn situational_yaku(menzen: bool, ctx: &WinContext, yaku: &mut Vec<Yaku>) {
+    if menzen {
+        if ctx.double_riichi {
+            yaku.push(Yaku::DoubleRiichi);
+        } else if ctx.riichi {
+            yaku.push(Yaku::Riichi);
+        }
+        if ctx.ippatsu && (ctx.riichi || ctx.double_riichi) {
+            yaku.push(Yaku::Ippatsu);
+        }
+        if ctx.tsumo {
+            yaku.push(Yaku::MenzenTsumo);
+        }
+    }
+    if ctx.last_tile {
+        yaku.push(if ctx.tsumo {
+            Yaku::Haitei
+        } else {
+            Yaku::Houtei
+        });
+    }
+    if ctx.rinshan {
+        yaku.push(Yaku::Rinshan);
+    }
+    if ctx.chankan {
+        yaku.push(Yaku::Chankan);
+    }
+    if ctx.first_turn && ctx.tsumo && menzen {
+        yaku.push(if ctx.is_dealer() {
+            Yaku::Tenhou
+        } else {
+            Yaku::Chiihou
+        });
+    }
+}
+
+fn evaluate_standard(
+    sets: &[Set],
+    pair: usize,
+    wait: WaitShape,
+    all: &TileCounts,
+    menzen: bool,
+    ctx: &WinContext,
+) -> Evaluation {
+    let open = !menzen;
+    let mut yaku = Vec::new();
+    situational_yaku(menzen, ctx, &mut yaku);
+    tile_yaku(all, open, ctx, &mut yaku);
+
+    let seat = honor_index(ctx.seat_wind);
+    let round = honor_index(ctx.round_wind);
+    let dragons = [
+        (honor_index(Honor::White), Yaku::WhiteDragon),
+        (honor_index(Honor::Green), Yaku::GreenDragon),
+        (honor_index(Honor::Red), Yaku::RedDragon),
+    ];
+    let winds: Vec<usize> = (0..4).map(|i| honor_index(Honor::ALL[i])).collect();
+
+    let sequences: Vec<usize> = sets
+        .iter()
+        .filter(|s| s.is_sequence())
+        .map(|s| s.index)
+        .collect();
+    let triplets: Vec<&Set> = sets.iter().filter(|s| !s.is_sequence()).collect();
+    let has_triplet = |index: usize| triplets.iter().any(|s| s.index == index);
+    let is_yakuhai_pair = pair == seat || pair == round || dragons.iter().any(|&(d, _)| d == pair);
+
+    // Value honors
+    for &(index, dragon) in &dragons {
+        if has_triplet(index) {
+            yaku.push(dragon);
+        }
+    }
+    if has_triplet(seat) {
+        yaku.push(Yaku::SeatWind);
+    }
+    if has_triplet(round) {
+        yaku.push(Yaku::RoundWind);
+    }
+
+    let pinfu = menzen && sequences.len() == 4 && !is_yakuhai_pair && wait == WaitShape::Ryanmen;
+    if pinfu {
+        yaku.push(Yaku::Pinfu);
+    }
+
+    if menzen {
+        let mut sorted = sequences.clone();
+        sorted.sort();
+        let mut identical = 0;
+        let mut i = 0;
+        while i + 1 < sorted.len() {
+            if sorted[i] == sorted[i + 1] {
+                identical += 1;
+                i += 2;
+            } else {
+                i += 1;
+            }
+        }
+        match identical {
+            2 => yaku.push(Yaku::Ryanpeikou),
+            1 => yaku.push(Yaku::Iipeikou),
+            _ => {}
+        }
+    }
+
+    let same_rank_in_all_suits = |starts: &[usize]| {
+        (0..9).any(|rank| (0..3).all(|suit| starts.contains(&(suit * 9 + rank))))
+    };
+    if same_rank_in_all_suits(&sequences) {
+        yaku.push(Yaku::SanshokuDoujun);
+    }
+    if (0..3).any(|suit| {
+        [0, 3, 6]
+            .iter()
+            .all(|r| sequences.contains(&(suit * 9 + r)))
+    }) {
+        yaku.push(Yaku::Ittsu);
+    }
+
+    let triplet_indices: Vec<usize> = triplets.iter().map(|s| s.index).collect();
+    let numbered_triplets: Vec<usize> = triplet_indices
+        .iter()
+        .copied()
+        .filter(|&i| i < 27)
+        .collect();
+    if same_rank_in_all_suits(&numbered_triplets) {
+        yaku.push(Yaku::SanshokuDoukou);
+    }
+
+    let every_group_outside =
+        sets.iter().all(Set::has_terminal_or_honor) && is_terminal_or_honor(pair);
+    if every_group_outside && !sequences.is_empty() {
+        let has_honors = is_honor(pair) || sets.iter().any(|s| is_honor(s.index));
+        yaku.push(if has_honors {
+            Yaku::Chanta
+        } else {
+            Yaku::Junchan
+        });
+    }
+
+    if triplets.len() == 4 {
+        yaku.push(Yaku::Toitoi);
+    }
+
+    let concealed_triplets = triplets.iter().filter(|s| s.concealed).count();
+    if concealed_triplets == 4 {
+        yaku.push(Yaku::Suuankou);
+    } else if concealed_triplets == 3 {
+        yaku.push(Yaku::Sanankou);
+    }
+
+    let kans = triplets.iter().filter(|s| s.kind == SetKind::Kan).count();
+    if kans == 4 {
+        yaku.push(Yaku::Suukantsu);
+    } else if kans == 3 {
+        yaku.push(Yaku::Sankantsu);
+    }
+
+    let dragon_triplets = dragons.iter().filter(|&&(d, _)| has_triplet(d)).count();
+    let dragon_pair = dragons.iter().any(|&(d, _)| d == pair);
+    if dragon_triplets == 3 {
+        yaku.push(Yaku::Daisangen);
+    } else if dragon_triplets == 2 && dragon_pair {
+        yaku.push(Yaku::Shousangen);
+    }
+
+    let wind_triplets = winds.iter().filter(|&&w| has_triplet(w)).count();
+    if wind_triplets == 4 {
+        yaku.push(Yaku::Daisuushii);
+    } else if wind_triplets == 3 && winds.contains(&pair) {
+        yaku.push(Yaku::Shousuushii);
+    }
+
+    if menzen && sets.iter().all(|s| s.concealed) {
+        let suit_start = (0..3).find(|&suit| all[suit * 9..suit * 9 + 9].iter().sum::<u8>() == 14);
+        if let Some(start) = suit_start.map(|suit| suit * 9) {
+            let suit = &all[start..start + 9];
+            if suit[0] >= 3 && suit[8] >= 3 && suit.iter().all(|&c| c >= 1) {
+                yaku.push(Yaku::ChuurenPoutou);
+            }
+        }
+    }
+
+    let fu = if pinfu {
+        if ctx.tsumo {
+            20
+        } else {
+            30
+        }
+    } else {
+        let mut fu = 20u32;
+        if menzen && !ctx.tsumo {
+            fu += 10;
+        }
+        if ctx.tsumo {
+            fu += 2;
+        }
+        for set in &triplets {
+            let mut value = if is_terminal_or_honor(set.index) {
+                4
+            } else {
+                2
+            };
+            if set.concealed {
+                value *= 2;
+            }
+            if set.kind == SetKind::Kan {
+                value *= 4;
+            }
+            fu += value;
+        }
+        if dragons.iter().any(|&(d, _)| d == pair) {
+            fu += 2;
+        }
+        if pair == seat {
+            fu += 2;
+        }
+        if pair == round {
+            fu += 2;
+        }
+        if matches!(
+            wait,
+            WaitShape::Kanchan | WaitShape::Penchan | WaitShape::Tanki
+        ) {
+            fu += 2;
+        }
+        // An open hand with no fu is still worth 30
+        if fu == 20 {
+            fu = 30;
+        }
+        fu.div_ceil(10) * 10
+    };
+
+    Evaluation {
+        yaku,
+        fu: fu as u8,
+        wait: Some(wait),
+    }
+}
+
+fn evaluate_chiitoitsu(all: &TileCounts, ctx: &WinContext) -> Evaluation {
+    let mut yaku = vec![Yaku::Chiitoitsu];
+    situational_yaku(true, ctx, &mut yaku);
+    tile_yaku(all, false, ctx, &mut yaku);
+    Evaluation {
+        yaku,
+        fu: 25,
+        wait: Some(WaitShape::Tanki),
+    }
+}
+
+fn evaluate_kokushi(ctx: &WinContext) -> Evaluation {
+    let mut yaku = vec![Yaku::KokushiMusou];
+    situational_yaku(true, ctx, &mut yaku);
+    Evaluation {
+        yaku,
+        fu: 30,
+        wait: None,
+    }
+}
+
+fn base_points(han: u8, fu: u8, yakuman: u8) -> (u32, Option<Limit>) {
+    if yakuman > 0 {
+        return (8000 * yakuman as u32, Some(Limit::Yakuman(yakuman)));
+    }
+    match han {
+        13.. => (8000, Some(Limit::KazoeYakuman)),
+        11..=12 => (6000, Some(Limit::Sanbaiman)),
+        8..=10 => (4000, Some(Limit::Baiman)),
+        6..=7 => (3000, Some(Limit::Haneman)),
+        5 => (2000, Some(Limit::Mangan)),
+        // Only computed below five han, where it cannot overflow
+        _ => match fu as u32 * 2u32.pow(2 + han as u32) {
+            2000.. => (2000, Some(Limit::Mangan)),
+            base => (base, None),
+        },
+    }
+}
+
+fn round_up_100(points: u32) -> u32 {
+    points.div_ceil(100) * 100
+}
+
+fn payment(base: u32, ctx: &WinContext) -> Payment {
+    match (ctx.tsumo, ctx.is_dealer()) {
+        (false, true) => Payment::Ron {
+            discarder: round_up_100(base * 6) + 300 * ctx.honba,
+        },
+        (false, false) => Payment::Ron {
+            discarder: round_up_100(base * 4) + 300 * ctx.honba,
+        },
+        (true, true) => Payment::DealerTsumo {
+            each: round_up_100(base * 2) + 100 * ctx.honba,
+        },
+        (true, false) => Payment::Tsumo {
+            dealer: round_up_100(base * 2) + 100 * ctx.honba,
+            non_dealer: round_up_100(base) + 100 * ctx.honba,
+        },
+    }
+}
+
+fn count_dora(all: &TileCounts, indicators: &[Piece]) -> u8 {
+    indicators
+        .iter()
+        .map(|indicator| all[indicator.dora_from_indicator().index()])
+        .fold(0, u8::saturating_add)
+}
+
+/// Score a winning hand.
+///
+/// `hand.live_pieces` may either already contain the winning piece (as after
+/// a tsumo draw) or hold one piece fewer (as when winning by ron). Only red
+/// fives among the live pieces count as aka dora, since a [`Meld`] cannot
+/// record one.
+///
+/// [`Meld`]: crate::observe::Meld
+pub fn score(hand: &Hand, ctx: &WinContext) -> Result<ScoreResult, ScoringError> {
+    let mut live_pieces = hand.live_pieces.clone();
+    match live_pieces.len() % 3 {
+        1 => live_pieces.push(ctx.winning_piece),
+        2 if live_pieces
+            .iter()
+            .any(|p| p.index() == ctx.winning_piece.index()) => {}
+        2 => return Err(ScoringError::MissingWinningPiece(ctx.winning_piece)),
+        _ => return Err(ScoringError::InvalidPieceCount(live_pieces.len())),
+    }
+    if live_pieces.len() + 3 * hand.meld_count() != 14 {
+        return Err(ScoringError::InvalidPieceCount(live_pieces.len()));
+    }
+
+    let concealed = tile_counts(&live_pieces);
+    let called = called_sets(hand);
+    let menzen = called.iter().all(|set| set.concealed);
+    let win = ctx.winning_piece.index();
+
+    let mut all = concealed;
+    for set in &called {
+        for index in set.indices() {
+            all[index] += 1;
+        }
+    }
+
+    let mut shapes = standard_shapes(&concealed, &called, win, ctx.tsumo);
+    if called.is_empty() && is_chiitoitsu(&concealed) {
+        shapes.push(Shape::Chiitoitsu);
+    }
+    if called.is_empty() && is_kokushi(&concealed) {
+        shapes.push(Shape::Kokushi);
+    }
+    if shapes.is_empty() {
+        return Err(ScoringError::NotComplete);
+    }
+
+    let dora = count_dora(&all, &ctx.dora_indicators);
+    let ura_dora = if ctx.riichi || ctx.double_riichi {
+        count_dora(&all, &ctx.ura_dora_indicators)
+    } else {
+        0
+    };
+    let aka_dora = live_pieces.iter().filter(|p| p.is_red_five()).count() as u8;
+
+    let mut best: Option<ScoreResult> = None;
+    for shape in shapes {
+        let evaluation = match shape {
+            Shape::Standard { sets, pair, wait } => {
+                evaluate_standard(&sets, pair, wait, &all, menzen, ctx)
+            }
+            Shape::Chiitoitsu => evaluate_chiitoitsu(&all, ctx),
+            Shape::Kokushi => evaluate_kokushi(ctx),
+        };
+
+        let mut yaku = evaluation.yaku;
+        let yakuman = yaku.iter().filter(|y| y.is_yakuman()).count() as u8;
+        if yakuman > 0 {
+            yaku.retain(|y| y.is_yakuman());
+        } else {
+            // Stronger yaku replace the weaker ones they imply
+            if yaku.contains(&Yaku::Ryanpeikou) {
+                yaku.retain(|&y| y != Yaku::Chiitoitsu);
+            }
+            if yaku.contains(&Yaku::Honroutou) {
+                yaku.retain(|&y| y != Yaku::Chanta);
+            }
+        }
+        if yaku.is_empty() {
+            continue;
+        }
+        yaku.sort();
+
+        let yaku: Vec<(Yaku, u8)> = yaku.into_iter().map(|y| (y, y.han(!menzen))).collect();
+        let yaku_han: u8 = yaku.iter().map(|&(_, han)| han).sum();
+        let han = if yakuman > 0 {
+            yaku_han
+        } else {
+            [dora, aka_dora, ura_dora]
+                .into_iter()
+                .fold(yaku_han, u8::saturating_add)
+        };
+        let (base_points, limit) = base_points(han, evaluation.fu, yakuman);
+
+        let candidate = ScoreResult {
+            yaku,
+            han,
+            fu: evaluation.fu,
+            dora,
+            aka_dora,
+            ura_dora,
+            wait: evaluation.wait,
+            limit,
+            base_points,
+            payment: payment(base_points, ctx),
+            riichi_sticks: ctx.riichi_sticks,
+        };
+
+        let better = best.as_ref().is_none_or(|current| {
+            (candidate.base_points, candidate.han, candidate.fu)
+                > (current.base_points, current.han, current.fu)
+        });
+        if better {
+            best = Some(candidate);
+        }
+    }
+
+    best.ok_or(ScoringError::NoYaku)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_hand;
+
+    fn win(piece: &str, tsumo: bool) -> WinContext {
+        let piece = piece.parse().unwrap();
+        if tsumo {
+            WinContext::tsumo(piece)
+        } else {
+            WinContext::ron(piece)
+        }
+    }
+
+    fn yaku_names(result: &ScoreResult) -> Vec<Yaku> {
+        result.yaku.iter().map(|&(yaku, _)| yaku).collect()
+    }
+
+    #[test]
+    fn scores_riichi_pinfu_tanyao() {
+        let hand = parse_hand("234m456p678s23s55p").unwrap();
+        let mut ctx = win("4s", false);
+        ctx.seat_wind = Honor::South;
+        ctx.riichi = true;
+
+        let result = score(&hand, &ctx).unwrap();
+        assert_eq!(
+            yaku_names(&result),
+            [Yaku::Riichi, Yaku::Pinfu, Yaku::Tanyao]
+        );
+        assert_eq!((result.han, result.fu), (3, 30));
+        assert_eq!(result.wait, Some(WaitShape::Ryanmen));
+        assert_eq!(result.payment, Payment::Ron { discarder: 3900 });
+    }
+
+    #[test]
+    fn scores_open_yakuhai_with_fu() {
+        let hand = parse_hand("234m678p99s13s (777z)").unwrap();
+        let ctx = win("2s", false);
+
+        let result = score(&hand, &ctx).unwrap();
+        assert_eq!(yaku_names(&result), [Yaku::RedDragon]);
+        // 20 base + 4 open dragon pon + 2 kanchan, rounded up
+        assert_eq!((result.han, result.fu), (1, 30));
+        assert_eq!(result.payment, Payment::Ron { discarder: 1500 });
+    }
+
+    #[test]
+    fn scores_chiitoitsu_tsumo_with_dora() {
+        let hand = parse_hand("1133m2255p4466s77z").unwrap();
+        let mut ctx = win("7z", true);
+        ctx.seat_wind = Honor::West;
+        ctx.dora_indicators = vec!["3s".parse().unwrap()];
+
+        let result = score(&hand, &ctx).unwrap();
+        assert_eq!(yaku_names(&result), [Yaku::MenzenTsumo, Yaku::Chiitoitsu]);
+        assert_eq!((result.han, result.fu, result.dora), (5, 25, 2));
+        assert_eq!(result.limit, Some(Limit::Mangan));
+        assert_eq!(
+            result.payment,
+            Payment::Tsumo {
+                dealer: 4000,
+                non_dealer: 2000
+            }
+        );
+    }
+
+    #[test]
+    fn scores_yakuman_and_deltas() {
+        let hand = parse_hand("555666777z11m23p").unwrap();
+        let mut ctx = win("1p", false);
+        ctx.honba = 1;
+        ctx.riichi_sticks = 1;
+
+        let result = score(&hand, &ctx).unwrap();
+        assert_eq!(yaku_names(&result), [Yaku::Daisangen]);
+        assert_eq!(result.limit, Some(Limit::Yakuman(1)));
+        assert_eq!(result.payment, Payment::Ron { discarder: 48300 });
+        assert_eq!(result.point_deltas(0, Some(2), 0), [49300, 0, -48300, 0]);
+    }
+
+    #[test]
+    fn caps_very_high_han_at_kazoe_yakuman() {
+        let hand = parse_hand("234m456p678s23s55p").unwrap();
+        let mut ctx = win("4s", false);
+        ctx.seat_wind = Honor::South;
+        ctx.riichi = true;
+        // Every indicator points at the three 5p in the hand
+        ctx.dora_indicators = vec!["4p".parse().unwrap(); 30];
+
+        let result = score(&hand, &ctx).unwrap();
+        assert_eq!((result.han, result.dora), (93, 90));
+        assert_eq!(result.limit, Some(Limit::KazoeYakuman));
+        assert_eq!(result.payment, Payment::Ron { discarder: 32000 });
+        assert_eq!(base_points(u8::MAX, 110, 0).0, 8000);
+
+        // Long indicator lists saturate instead of overflowing
+        ctx.dora_indicators = vec!["4p".parse().unwrap(); 100];
+        let result = score(&hand, &ctx).unwrap();
+        assert_eq!((result.han, result.dora), (u8::MAX, u8::MAX));
+        assert_eq!(result.limit, Some(Limit::KazoeYakuman));
+    }
+
+    #[test]
+    fn counts_red_fives_in_the_concealed_pieces() {
+        let hand = parse_hand("234m406p678s23s55p").unwrap();
+        let result = score(&hand, &win("4s", false)).unwrap();
+        assert_eq!(result.aka_dora, 1);
+    }
+
+    #[test]
+    fn prefers_highest_scoring_arrangement() {
+        // Readable as three concealed triplets or as three identical runs
+        let hand = parse_hand("111222333m789p5s").unwrap();
+        let result = score(&hand, &win("5s", false)).unwrap();
+        assert!(yaku_names(&result).contains(&Yaku::Sanankou));
+    }
+
+    #[test]
+    fn rejects_hands_without_yaku_or_shape() {
+        let hand = parse_hand("234m678p99s13s (123p)").unwrap();
+        assert_eq!(score(&hand, &win("2s", false)), Err(ScoringError::NoYaku));
+
+        let hand = parse_hand("147m258p369s1234z").unwrap();
+        assert_eq!(
+            score(&hand, &win("5z", false)),
+            Err(ScoringError::NotComplete)
+        );
+    }
+}
@@ -1,5 +1,251 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::controller::available_controllers;
+use crate::rules::{RuleError, RuleSet};
+
+/// Errors produced when building [`GameSettings`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SettingsError {
+    #[error("Seat {0} does not exist, seats are numbered 0 to 3")]
+    SeatOutOfRange(usize),
+    #[error("No controller assigned to seat {seat}")]
+    MissingController { seat: usize },
+    #[error("Unknown controller '{name}' assigned to seat {seat}")]
+    UnknownController { seat: usize, name: String },
+    #[error("Invalid rules: {0}")]
+    InvalidRules(#[from] RuleError),
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
 pub struct GameSettings {
     pub seed: u64,
     pub seat_controllers: [String; 4],
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rules: RuleSet,
+}
+
+impl GameSettings {
+    /// Start building settings seat by seat
+    pub fn builder() -> GameSettingsBuilder {
+        GameSettingsBuilder::default()
+    }
+
+    /// Check every seat against [`available_controllers`]
+    pub fn validate_controllers(&self) -> Result<(), SettingsError> {
+        let known = available_controller_names();
+        for (seat, name) in self.seat_controllers.iter().enumerate() {
+            check_known(seat, name, &known)?;
+        }
+        Ok(())
+    }
+}
+
+fn available_controller_names() -> Vec<String> {
+    available_controllers()
+        .into_iter()
+        .map(|controller| controller.name)
+        .collect()
+}
+
+fn check_known(seat: usize, name: &str, known: &[String]) -> Result<(), SettingsError> {
+    if known.iter().any(|known| known == name) {
+        Ok(())
+    } else {
+        Err(SettingsError::UnknownController {
+            seat,
+            name: name.to_string(),
+        })
+    }
+}
+
+/// Generate a seed from the process' random hasher keys and the clock
+pub fn random_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(nanos);
+    hasher.finish()
+}
+
+/// Which controller names [`GameSettingsBuilder::build`] accepts
+#[derive(Debug, Clone, Default)]
+enum ControllerCheck {
+    /// Names reported by [`available_controllers`] when building
+    #[default]
+    Available,
+    Known(Vec<String>),
+    Unchecked,
+}
+
+/// Builder for [`GameSettings`] that reports which seat is misconfigured.
+///
+/// Controller names are checked against [`available_controllers`] unless
+/// [`known_controllers`](Self::known_controllers) or
+/// [`allow_unknown_controllers`](Self::allow_unknown_controllers) says
+/// otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct GameSettingsBuilder {
+    seed: Option<u64>,
+    seat_controllers: [Option<String>; 4],
+    rules: RuleSet,
+    controller_check: ControllerCheck,
+    error: Option<SettingsError>,
+}
+
+impl GameSettingsBuilder {
+    /// Use a fixed seed. Without one, [`random_seed`] is used.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Pick a random seed now
+    pub fn random_seed(self) -> Self {
+        self.seed(random_seed())
+    }
+
+    /// Assign a controller to a seat (`0..4`)
+    pub fn seat(mut self, seat: usize, controller: impl Into<String>) -> Self {
+        match self.seat_controllers.get_mut(seat) {
+            Some(slot) => *slot = Some(controller.into()),
+            None => {
+                self.error
+                    .get_or_insert(SettingsError::SeatOutOfRange(seat));
+            }
+        }
+        self
+    }
+
+    /// Assign the same controller to every seat
+    pub fn all_seats(mut self, controller: impl Into<String>) -> Self {
+        let controller = controller.into();
+        self.seat_controllers = std::array::from_fn(|_| Some(controller.clone()));
+        self
+    }
+
+    pub fn rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Only accept controllers from this list
+    pub fn known_controllers<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.controller_check = ControllerCheck::Known(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Accept any controller name, leaving it to the backend to reject
+    /// unknown ones when the game is created
+    pub fn allow_unknown_controllers(mut self) -> Self {
+        self.controller_check = ControllerCheck::Unchecked;
+        self
+    }
+
+    /// Validate the seats and rules and build the settings
+    pub fn build(self) -> Result<GameSettings, SettingsError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.rules.validate()?;
+
+        let known = match self.controller_check {
+            ControllerCheck::Available => Some(available_controller_names()),
+            ControllerCheck::Known(names) => Some(names),
+            ControllerCheck::Unchecked => None,
+        };
+        let mut seat_controllers: [String; 4] = Default::default();
+        for (seat, (slot, controller)) in seat_controllers
+            .iter_mut()
+            .zip(self.seat_controllers)
+            .enumerate()
+        {
+            let name = controller
+                .filter(|name| !name.is_empty())
+                .ok_or(SettingsError::MissingController { seat })?;
+            if let Some(known) = &known {
+                check_known(seat, &name, known)?;
+            }
+            *slot = name;
+        }
+
+        Ok(GameSettings {
+            seed: self.seed.unwrap_or_else(random_seed),
+            seat_controllers,
+            rules: self.rules,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_settings_seat_by_seat() {
+        let settings = GameSettings::builder()
+            .seed(7)
+            .all_seats("AlphabeticalBot")
+            .seat(2, "AngryDiscardoBot")
+            .rules(RuleSet::wrc())
+            .known_controllers(["AlphabeticalBot", "AngryDiscardoBot"])
+            .build()
+            .unwrap();
+
+        assert_eq!(settings.seed, 7);
+        assert_eq!(settings.seat_controllers[2], "AngryDiscardoBot");
+        assert_eq!(settings.seat_controllers[3], "AlphabeticalBot");
+        assert_eq!(settings.rules, RuleSet::wrc());
+    }
+
+    #[test]
+    fn reports_the_rejected_seat() {
+        let builder = GameSettings::builder()
+            .seat(0, "AlphabeticalBot")
+            .seat(1, "AlphabeticalBot")
+            .seat(3, "AlphabeticalBot");
+        assert_eq!(
+            builder.clone().build().unwrap_err(),
+            SettingsError::MissingController { seat: 2 }
+        );
+
+        let error = builder
+            .seat(2, "AlphabeticBot")
+            .known_controllers(["AlphabeticalBot"])
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            SettingsError::UnknownController {
+                seat: 2,
+                name: "AlphabeticBot".to_string()
+            }
+        );
+
+        let error = GameSettings::builder().seat(4, "AlphabeticalBot").build();
+        assert_eq!(error.unwrap_err(), SettingsError::SeatOutOfRange(4));
+    }
+
+    #[test]
+    fn checks_available_controllers_by_default() {
+        let builder = GameSettings::builder().all_seats("NoSuchBot");
+        assert_eq!(
+            builder.clone().build().unwrap_err(),
+            SettingsError::UnknownController {
+                seat: 0,
+                name: "NoSuchBot".to_string()
+            }
+        );
+
+        let settings = builder.allow_unknown_controllers().build().unwrap();
+        assert_eq!(settings.seat_controllers[3], "NoSuchBot");
+    }
 }
@@ -0,0 +1,384 @@
+//! Play batches of seeded games across a pool of threads.
+//!
+//! [`Simulation`] plays one game per seed with the same seat controllers and
+//! rules. Games finish in any order, but results are always returned in seed
+//! order so two runs over the same range can be compared line by line.
+
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::events::{EventDiffer, GameEvent};
+use crate::ffi::error::MahjongFFIError;
+use crate::observe::ObservedGameState;
+use crate::rules::RuleSet;
+use crate::runner::{GameRunner, GameSummary, DEFAULT_STEP_LIMIT};
+use crate::settings::GameSettings;
+
+/// A game in the batch that could not be played to completion
+#[derive(Debug, thiserror::Error)]
+#[error("Game with seed {seed} failed: {source}")]
+pub struct SimulationError {
+    pub seed: u64,
+    #[source]
+    pub source: MahjongFFIError,
+}
+
+/// A hand won during a simulated game
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Win {
+    pub round: i32,
+    pub winner: usize,
+    /// Seat whose discard was ronned, `None` for a tsumo
+    pub dealt_in: Option<usize>,
+}
+
+impl Win {
+    pub fn is_tsumo(&self) -> bool {
+        self.dealt_in.is_none()
+    }
+}
+
+/// Outcome of one simulated game
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameResult {
+    pub seed: u64,
+    pub points: [i32; 4],
+    /// Placement (0 = first) of each seat
+    pub placements: [usize; 4],
+    pub rounds: usize,
+    pub steps: usize,
+    pub reached_game_end: bool,
+    /// Every win in the order it happened
+    pub wins: Vec<Win>,
+}
+
+impl GameResult {
+    /// Build a result from a runner's summary and the wins seen on the way
+    pub fn from_summary(seed: u64, summary: &GameSummary, wins: Vec<Win>) -> Self {
+        Self {
+            seed,
+            points: *summary.points(),
+            placements: summary.placements(),
+            rounds: summary.rounds,
+            steps: summary.steps,
+            reached_game_end: summary.reached_game_end,
+            wins,
+        }
+    }
+
+    /// Count the wins of each seat
+    pub fn wins_by_seat(&self) -> [usize; 4] {
+        let mut counts = [0; 4];
+        for win in &self.wins {
+            counts[win.winner] += 1;
+        }
+        counts
+    }
+
+    /// Count how often each seat dealt into another player's ron
+    pub fn deal_ins_by_seat(&self) -> [usize; 4] {
+        let mut counts = [0; 4];
+        for seat in self.wins.iter().filter_map(|win| win.dealt_in) {
+            counts[seat] += 1;
+        }
+        counts
+    }
+}
+
+/// Snapshot of a running batch, reported after every finished game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub completed: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    /// Get the finished share of the batch, between 0 and 1
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.completed as f64 / self.total as f64
+        }
+    }
+}
+
+pub(crate) type ProgressCallback = Box<dyn Fn(Progress) + Send + Sync>;
+
+/// A batch of games, one per seed
+pub struct Simulation {
+    seeds: Range<u64>,
+    seat_controllers: [String; 4],
+    rules: RuleSet,
+    threads: usize,
+    step_limit: usize,
+    progress: Option<ProgressCallback>,
+}
+
+impl Simulation {
+    /// Play every seed in the range with the given seat controllers
+    pub fn new(seeds: Range<u64>, seat_controllers: [String; 4]) -> Self {
+        Self {
+            seeds,
+            seat_controllers,
+            rules: RuleSet::default(),
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            step_limit: DEFAULT_STEP_LIMIT,
+            progress: None,
+        }
+    }
+
+    /// Use the controllers and rules of existing settings. Their seed is
+    /// ignored.
+    pub fn from_settings(seeds: Range<u64>, settings: &GameSettings) -> Self {
+        Self::new(seeds, settings.seat_controllers.clone()).with_rules(settings.rules.clone())
+    }
+
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Set the number of worker threads. Defaults to the available
+    /// parallelism.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Set the maximum number of advances per game
+    pub fn with_step_limit(mut self, step_limit: usize) -> Self {
+        self.step_limit = step_limit;
+        self
+    }
+
+    /// Call `callback` from the worker threads whenever a game finishes
+    pub fn on_progress(mut self, callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Get the number of games in the batch
+    pub fn len(&self) -> usize {
+        seed_count(&self.seeds)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Play every game and return the results in seed order
+    pub fn run(&self) -> Vec<Result<GameResult, SimulationError>> {
+        let start = self.seeds.start;
+        run_parallel(
+            self.len(),
+            self.threads,
+            self.progress.as_deref(),
+            |index| {
+                let settings = GameSettings {
+                    seed: start + index as u64,
+                    seat_controllers: self.seat_controllers.clone(),
+                    rules: self.rules.clone(),
+                };
+                play_game(settings, self.step_limit)
+            },
+        )
+    }
+}
+
+pub(crate) fn seed_count(seeds: &Range<u64>) -> usize {
+    seeds.end.saturating_sub(seeds.start) as usize
+}
+
+/// Play one game to completion, collecting its wins on the way
+pub(crate) fn play_game(
+    settings: GameSettings,
+    step_limit: usize,
+) -> Result<GameResult, SimulationError> {
+    let seed = settings.seed;
+    let play = || {
+        let mut runner = GameRunner::from_settings(settings)?.with_step_limit(step_limit);
+
+        let mut differ = EventDiffer::new();
+        let mut wins = Vec::new();
+        for state in runner.by_ref() {
+            record_wins(&mut differ, &state, &mut wins);
+        }
+        Ok(GameResult::from_summary(seed, &runner.summary()?, wins))
+    };
+    play().map_err(|source| SimulationError { seed, source })
+}
+
+fn record_wins(differ: &mut EventDiffer, state: &ObservedGameState, wins: &mut Vec<Win>) {
+    for event in differ.push(state) {
+        let (winner, dealt_in) = match event {
+            GameEvent::Ron { seat, from, .. } => (seat, from),
+            GameEvent::Tsumo { seat, .. } => (seat, None),
+            _ => continue,
+        };
+        wins.push(Win {
+            round: state.round_num,
+            winner,
+            dealt_in,
+        });
+    }
+}
+
+/// Run `play` for every index below `total` on `threads` workers, returning
+/// results in index order
+pub(crate) fn run_parallel<T, E, F>(
+    total: usize,
+    threads: usize,
+    progress: Option<&(dyn Fn(Progress) + Send + Sync)>,
+    play: F,
+) -> Vec<Result<T, E>>
+where
+    T: Send,
+    E: Send,
+    F: Fn(usize) -> Result<T, E> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let counts = Mutex::new((0, 0));
+    let mut finished = Vec::with_capacity(total);
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.clamp(1, total.max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= total {
+                            break results;
+                        }
+                        let result = play(index);
+
+                        // Report under the lock so callbacks see counts in order
+                        let mut counts = counts.lock().unwrap_or_else(|e| e.into_inner());
+                        counts.0 += 1;
+                        counts.1 += usize::from(result.is_err());
+                        if let Some(progress) = progress {
+                            progress(Progress {
+                                completed: counts.0,
+                                failed: counts.1,
+                                total,
+                            });
+                        }
+                        drop(counts);
+
+                        results.push((index, result));
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            match worker.join() {
+                Ok(results) => finished.extend(results),
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        }
+    });
+
+    finished.sort_unstable_by_key(|&(index, _)| index);
+    finished.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_hand;
+    use crate::observe::StateFunctionType;
+    use std::time::Duration;
+
+    #[test]
+    fn results_come_back_in_seed_order() {
+        let reports = Mutex::new(Vec::new());
+        let report = |progress: Progress| reports.lock().unwrap().push(progress);
+
+        let results = run_parallel(32, 4, Some(&report), |index| {
+            let seed = 100 + index as u64;
+            // Make early seeds finish last
+            thread::sleep(Duration::from_millis(132 - seed));
+            if seed % 10 == 0 {
+                Err(seed)
+            } else {
+                Ok(seed * 2)
+            }
+        });
+
+        assert_eq!(results.len(), 32);
+        for (seed, result) in (100..132).zip(&results) {
+            match result {
+                Ok(doubled) => assert_eq!(*doubled, seed * 2),
+                Err(failed) => assert_eq!(*failed, seed),
+            }
+        }
+
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(reports.len(), 32);
+        assert!(reports.windows(2).all(|w| w[0].completed < w[1].completed));
+        assert_eq!(
+            reports.last(),
+            Some(&Progress {
+                completed: 32,
+                failed: 4,
+                total: 32
+            })
+        );
+    }
+
+    #[test]
+    fn records_wins_and_deal_ins() {
+        let state = |curr_state, current_player, has_ronned| ObservedGameState {
+            current_player,
+            turn_num: 0,
+            round_num: 3,
+            riichi_sticks: 0,
+            counters: 0,
+            last_call: 0,
+            last_caller: 0,
+            concealed_kan: false,
+            seed: 9,
+            pending_piece: None,
+            scores: [0; 4],
+            points: [31000, 18000, 26000, 25000],
+            has_ronned,
+            hands: ["1m", "2m", "3m", "4m"].map(|h| parse_hand(h).unwrap()),
+            prev_state: StateFunctionType::Error,
+            curr_state,
+            next_state: StateFunctionType::Error,
+        };
+        let states = [
+            state(StateFunctionType::Ron, 1, [true, false, false, true]),
+            state(StateFunctionType::Tsumo, 2, [false; 4]),
+            state(StateFunctionType::GameEnd, 0, [false; 4]),
+        ];
+
+        let mut differ = EventDiffer::new();
+        let mut wins = Vec::new();
+        for state in &states {
+            record_wins(&mut differ, state, &mut wins);
+        }
+        let summary = GameSummary {
+            steps: 2,
+            rounds: 1,
+            reached_game_end: true,
+            final_state: states[2].clone(),
+            illegal_transitions: Vec::new(),
+        };
+        let result = GameResult::from_summary(9, &summary, wins);
+
+        assert_eq!(result.placements, [0, 3, 1, 2]);
+        assert_eq!(result.wins_by_seat(), [1, 0, 1, 1]);
+        assert_eq!(result.deal_ins_by_seat(), [0, 2, 0, 0]);
+        assert!(result.wins[2].is_tsumo());
+        assert_eq!(result.wins[0].round, 3);
+    }
+}
@@ -0,0 +1,154 @@
+//! Versioned JSON snapshots of settings and observed states.
+//!
+//! Every snapshot is wrapped in an envelope carrying the schema version:
+//!
+//! ```json
+//! {
+//!   "schema_version": 2,
+//!   "data": {
+//!     "current_player": 0,
+//!     "pending_piece": "5m",
+//!     "hands": [
+//!       {
+//!         "live_pieces": ["1m", "0p", "7z"],
+//!         "melds": [{ "meld_type": "Pon", "start": "3s" }],
+//!         "discards": [],
+//!         "open": true,
+//!         "riichi": false,
+//!         "riichi_piece_discard": 0,
+//!         "riichi_round": 0
+//!       }
+//!     ],
+//!     "curr_state": "Discard"
+//!   }
+//! }
+//! ```
+//!
+//! Field names match the Rust field names. Pieces are written in compact
+//! notation (see [`crate::notation`]), `pending_piece` is `null` when no
+//! piece is pending, and enums are written as their variant names.
+//!
+//! Version history:
+//! - `1`: initial schema.
+//! - `2`: `GameSettings` gained `rules`; version 1 settings play
+//!   `RuleSet::default()`.
+//!
+//! Snapshots written with an older schema version are accepted; fields added
+//! in later versions fall back to their defaults. Snapshots from a newer
+//! schema version are rejected.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Current snapshot schema version
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Errors produced when reading or writing snapshots
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("Snapshot schema version {0} is newer than the supported version {SCHEMA_VERSION}")]
+    UnsupportedSchemaVersion(u32),
+    #[error("Invalid snapshot JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// A value tagged with the schema version it was written with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot<T> {
+    pub schema_version: u32,
+    pub data: T,
+}
+
+impl<T> Snapshot<T> {
+    /// Wrap a value with the current schema version
+    pub fn new(data: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            data,
+        }
+    }
+}
+
+/// Serialize a value into a versioned JSON snapshot
+pub fn to_json<T: Serialize>(data: &T) -> Result<String, SnapshotError> {
+    Ok(serde_json::to_string(&Snapshot::new(data))?)
+}
+
+/// Read a versioned JSON snapshot, rejecting newer schema versions
+pub fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, SnapshotError> {
+    #[derive(Deserialize)]
+    struct Version {
+        schema_version: u32,
+    }
+
+    let Version { schema_version } = serde_json::from_str(json)?;
+    if schema_version > SCHEMA_VERSION {
+        return Err(SnapshotError::UnsupportedSchemaVersion(schema_version));
+    }
+
+    let snapshot: Snapshot<T> = serde_json::from_str(json)?;
+    Ok(snapshot.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_hand;
+    use crate::observe::{ObservedGameState, StateFunctionType};
+    use crate::rules::RuleSet;
+    use crate::settings::GameSettings;
+
+    #[test]
+    fn observed_state_round_trips() {
+        let hand = parse_hand("123m0p77z (333s)").unwrap();
+        let state = ObservedGameState {
+            current_player: 1,
+            turn_num: 4,
+            round_num: 0,
+            riichi_sticks: 1,
+            counters: 0,
+            last_call: 0,
+            last_caller: 0,
+            concealed_kan: false,
+            seed: 7,
+            pending_piece: Some("5m".parse().unwrap()),
+            scores: [0; 4],
+            points: [25000, 24000, 25000, 25000],
+            has_ronned: [false; 4],
+            hands: [hand.clone(), hand.clone(), hand.clone(), hand],
+            prev_state: StateFunctionType::Draw,
+            curr_state: StateFunctionType::PlayerHand,
+            next_state: StateFunctionType::Discard,
+        };
+
+        let json = to_json(&state).unwrap();
+        assert!(json.starts_with(r#"{"schema_version":2,"data":{"#));
+        assert!(json.contains(r#""live_pieces":["1m","2m","3m","0p","7z","7z"]"#));
+        assert_eq!(from_json::<ObservedGameState>(&json).unwrap(), state);
+    }
+
+    #[test]
+    fn rejects_newer_schema_versions() {
+        let settings = GameSettings {
+            seed: 1,
+            seat_controllers: Default::default(),
+            rules: Default::default(),
+        };
+        let json = to_json(&settings)
+            .unwrap()
+            .replace(r#""schema_version":2"#, r#""schema_version":99"#);
+
+        assert!(matches!(
+            from_json::<GameSettings>(&json),
+            Err(SnapshotError::UnsupportedSchemaVersion(99))
+        ));
+    }
+
+    #[test]
+    fn version_1_settings_use_the_default_rules() {
+        let json = r#"{"schema_version":1,"data":{"seed":3,"seat_controllers":["A","B","C","D"]}}"#;
+        let settings = from_json::<GameSettings>(json).unwrap();
+        assert_eq!(settings.seed, 3);
+        assert_eq!(settings.seat_controllers[3], "D");
+        assert_eq!(settings.rules, RuleSet::default());
+    }
+}
@@ -0,0 +1,170 @@
+//! Legal transitions between [`StateFunctionType`]s.
+//!
+//! The table follows libmahjong's state functions: a turn runs
+//! `Draw → PlayerHand → Discard`, calls on a discard branch off `Discard`,
+//! and every round ends through `Ron`, `Tsumo` or `Exhaust` into `RoundEnd`.
+//! [`to_dot`] and [`to_mermaid`] render the table as a graph.
+
+use std::fmt::Write;
+
+use crate::observe::StateFunctionType;
+
+impl StateFunctionType {
+    /// Every state function, in declaration order
+    pub const ALL: [Self; 19] = [
+        Self::Error,
+        Self::GameStart,
+        Self::RoundStart,
+        Self::Draw,
+        Self::PlayerHand,
+        Self::Pon,
+        Self::Chi,
+        Self::Kan,
+        Self::ConcealedKan,
+        Self::ConvertedKan,
+        Self::KanDiscard,
+        Self::Replacement,
+        Self::Riichi,
+        Self::Discard,
+        Self::Exhaust,
+        Self::Ron,
+        Self::Tsumo,
+        Self::RoundEnd,
+        Self::GameEnd,
+    ];
+
+    /// Get the states that may directly follow this one
+    pub fn successors(self) -> &'static [Self] {
+        use StateFunctionType::*;
+        match self {
+            Error => &[GameStart],
+            GameStart => &[RoundStart],
+            RoundStart => &[Draw],
+            Draw | Replacement | Pon | Chi => &[PlayerHand],
+            PlayerHand => &[Discard, Riichi, Tsumo, ConcealedKan, ConvertedKan],
+            Riichi => &[Discard],
+            Discard => &[Draw, Ron, Pon, Chi, Kan, Exhaust],
+            Kan | ConcealedKan => &[Replacement],
+            ConvertedKan => &[KanDiscard],
+            KanDiscard => &[Ron, Replacement],
+            Ron | Tsumo | Exhaust => &[RoundEnd],
+            RoundEnd => &[RoundStart, GameEnd],
+            GameEnd => &[],
+        }
+    }
+
+    /// Check if `next` may directly follow this state
+    pub fn can_transition_to(self, next: Self) -> bool {
+        self.successors().contains(&next)
+    }
+
+    /// Check if this state applies a pon, chi or kan
+    pub fn is_call(self) -> bool {
+        matches!(
+            self,
+            Self::Pon | Self::Chi | Self::Kan | Self::ConcealedKan | Self::ConvertedKan
+        )
+    }
+
+    /// Check if this state scores a winning hand
+    pub fn is_win(self) -> bool {
+        matches!(self, Self::Ron | Self::Tsumo)
+    }
+
+    /// Check if this state ends the round, leading into `RoundEnd`
+    pub fn is_round_terminal(self) -> bool {
+        matches!(self, Self::Ron | Self::Tsumo | Self::Exhaust)
+    }
+
+    /// Check if the game is over once this state is reached
+    pub fn is_terminal(self) -> bool {
+        self.successors().is_empty()
+    }
+}
+
+/// Every legal transition, grouped by source state
+pub fn transitions() -> impl Iterator<Item = (StateFunctionType, StateFunctionType)> {
+    StateFunctionType::ALL
+        .into_iter()
+        .flat_map(|from| from.successors().iter().map(move |&to| (from, to)))
+}
+
+/// Render the transition table as a Graphviz digraph
+pub fn to_dot() -> String {
+    let mut out = String::from("digraph StateFunctionType {\n");
+    for state in StateFunctionType::ALL {
+        let shape = if state.is_win() || state.is_terminal() {
+            "doublecircle"
+        } else {
+            "box"
+        };
+        let _ = writeln!(out, "    {state:?} [shape={shape}];");
+    }
+    for (from, to) in transitions() {
+        let _ = writeln!(out, "    {from:?} -> {to:?};");
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Render the transition table as a Mermaid state diagram
+pub fn to_mermaid() -> String {
+    let mut out = String::from("stateDiagram-v2\n");
+    let _ = writeln!(out, "    [*] --> {:?}", StateFunctionType::Error);
+    for (from, to) in transitions() {
+        let _ = writeln!(out, "    {from:?} --> {to:?}");
+    }
+    for state in StateFunctionType::ALL
+        .into_iter()
+        .filter(|s| s.is_terminal())
+    {
+        let _ = writeln!(out, "    {state:?} --> [*]");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use StateFunctionType::*;
+
+    #[test]
+    fn every_state_is_reachable_and_can_end() {
+        let mut reachable = vec![Error];
+        let mut i = 0;
+        while let Some(&state) = reachable.get(i) {
+            for &next in state.successors() {
+                if !reachable.contains(&next) {
+                    reachable.push(next);
+                }
+            }
+            i += 1;
+        }
+        assert_eq!(reachable.len(), StateFunctionType::ALL.len());
+
+        for state in StateFunctionType::ALL {
+            assert_eq!(state.is_terminal(), state == GameEnd);
+            if state.is_round_terminal() {
+                assert_eq!(state.successors(), [RoundEnd]);
+            }
+        }
+        assert!(Discard.can_transition_to(Pon));
+        assert!(!Discard.can_transition_to(Tsumo));
+        assert!(Tsumo.is_win() && !Exhaust.is_win() && Exhaust.is_round_terminal());
+        assert!(ConvertedKan.is_call() && !Riichi.is_call());
+    }
+
+    #[test]
+    fn exports_the_graph() {
+        let edges = transitions().count();
+        let dot = to_dot();
+        assert!(dot.starts_with("digraph"));
+        assert!(dot.contains("    Discard -> Ron;\n"));
+        assert_eq!(dot.matches(" -> ").count(), edges);
+
+        let mermaid = to_mermaid();
+        assert!(mermaid.contains("    [*] --> Error\n"));
+        assert!(mermaid.contains("    KanDiscard --> Replacement\n"));
+        assert!(mermaid.contains("    GameEnd --> [*]\n"));
+    }
+}
@@ -0,0 +1,1072 @@
+//! Tenhou JSON replay export and import.
+//!
+//! [`TenhouRecorder`] turns the observed states of a game into a log in the
+//! JSON format read by Tenhou's viewer (`tenhou.net/6`) and compatible tools,
+//! and [`TenhouLog::states`] replays such a log back into a sequence of
+//! [`ObservedGameState`]s.
+//!
+//! Tiles are written as Tenhou codes: `11`-`19` characters, `21`-`29` pins,
+//! `31`-`39` bamboo, `41`-`47` East, South, West, North, White, Green, Red
+//! and `51`-`53` red fives. Discards of the tile just drawn are written as
+//! `60`. Calls use Tenhou's marker strings, e.g. `"c131211"` for a chi or
+//! `"25p2525"` for a pon from the player opposite.
+//!
+//! Dora indicators are not part of the observed state, so exported rounds
+//! have empty dora lists, and round results only carry point changes.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::events::{EventDiffer, GameEvent};
+use crate::ffi::error::MahjongFFIError;
+use crate::observe::{Hand, Meld, MeldType, ObservedGameState, StateFunctionType};
+use crate::piece::{Piece, Suit};
+use crate::rules::RIICHI_DEPOSIT;
+use crate::runner::GameRunner;
+
+/// Code used in discard lists for a discard of the tile just drawn
+pub const TSUMOGIRI: u8 = 60;
+
+const AGARI: &str = "和了";
+const RYUKYOKU: &str = "流局";
+
+/// Errors produced while reading Tenhou logs
+#[derive(Debug, thiserror::Error)]
+pub enum TenhouError {
+    #[error("Invalid Tenhou log JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid Tenhou tile code {0}")]
+    InvalidTile(u8),
+    #[error("Invalid Tenhou call '{0}'")]
+    InvalidCall(String),
+    #[error("Malformed round {round}: {reason}")]
+    MalformedRound { round: usize, reason: String },
+}
+
+/// Convert a piece to its Tenhou tile code
+pub fn tile_code(piece: Piece) -> u8 {
+    if piece.is_red_five() {
+        let suit = match piece.suit() {
+            Some(Suit::Character) => 1,
+            Some(Suit::Pin) => 2,
+            _ => 3,
+        };
+        return 50 + suit;
+    }
+    let index = piece.index() as u8;
+    match index {
+        0..=26 => (index / 9 + 1) * 10 + index % 9 + 1,
+        _ => 41 + index - 27,
+    }
+}
+
+/// Convert a Tenhou tile code to a piece. [`TSUMOGIRI`] is not a tile and
+/// is rejected.
+pub fn tile_from_code(code: u8) -> Result<Piece, TenhouError> {
+    let index = match code {
+        11..=19 | 21..=29 | 31..=39 => (code / 10 - 1) * 9 + code % 10 - 1,
+        41..=47 => 27 + code - 41,
+        51..=53 => return Ok(Piece::red_five(Suit::ALL[(code - 51) as usize])),
+        _ => return Err(TenhouError::InvalidTile(code)),
+    };
+    Piece::from_index(index as usize).ok_or(TenhouError::InvalidTile(code))
+}
+
+/// An entry of a draw or discard list: a tile code or a marker string for
+/// calls, kans and riichi discards
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TenhouEntry {
+    Tile(u8),
+    Call(String),
+}
+
+/// Viewer display settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenhouRule {
+    #[serde(default)]
+    pub disp: String,
+    #[serde(default)]
+    pub aka: u8,
+}
+
+impl Default for TenhouRule {
+    fn default() -> Self {
+        Self {
+            disp: "libmahjong".to_string(),
+            aka: 1,
+        }
+    }
+}
+
+/// Starting hand, draws and discards of one seat for a round
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TenhouPlayer {
+    pub haipai: Vec<u8>,
+    pub takes: Vec<TenhouEntry>,
+    pub discards: Vec<TenhouEntry>,
+}
+
+/// A single winner of a round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenhouAgari {
+    /// Point changes caused by this win, excluding riichi deposits
+    pub deltas: [i32; 4],
+    pub winner: usize,
+    /// The discarder, or the winner for tsumo
+    pub from: usize,
+    /// The player liable for the hand, or the winner if nobody is
+    pub pao: usize,
+    /// Score and yaku descriptions
+    pub summary: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TenhouResult {
+    Agari(Vec<TenhouAgari>),
+    /// Any draw, such as `流局` for an exhaustive draw
+    Ryukyoku {
+        reason: String,
+        deltas: Option<[i32; 4]>,
+    },
+}
+
+/// One round of a Tenhou log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenhouRound {
+    /// Round index, 0 for East 1
+    pub kyoku: i32,
+    pub honba: i32,
+    pub kyotaku: i32,
+    /// Points at the start of the round
+    pub scores: [i32; 4],
+    pub dora: Vec<u8>,
+    pub ura_dora: Vec<u8>,
+    pub players: [TenhouPlayer; 4],
+    pub result: TenhouResult,
+}
+
+fn malformed(round: usize, reason: impl Into<String>) -> TenhouError {
+    TenhouError::MalformedRound {
+        round,
+        reason: reason.into(),
+    }
+}
+
+fn from_value<T: serde::de::DeserializeOwned>(
+    value: &Value,
+    round: usize,
+    what: &str,
+) -> Result<T, TenhouError> {
+    serde_json::from_value(value.clone()).map_err(|e| malformed(round, format!("{what}: {e}")))
+}
+
+impl TenhouRound {
+    fn to_value(&self) -> Value {
+        let mut entries = vec![
+            json!([self.kyoku, self.honba, self.kyotaku]),
+            json!(self.scores),
+            json!(self.dora),
+            json!(self.ura_dora),
+        ];
+        for player in &self.players {
+            entries.push(json!(player.haipai));
+            entries.push(json!(player.takes));
+            entries.push(json!(player.discards));
+        }
+
+        let result = match &self.result {
+            TenhouResult::Agari(agari) => {
+                let mut result = vec![json!(AGARI)];
+                for win in agari {
+                    let mut info = vec![json!(win.winner), json!(win.from), json!(win.pao)];
+                    info.extend(win.summary.iter().map(|s| json!(s)));
+                    result.push(json!(win.deltas));
+                    result.push(Value::Array(info));
+                }
+                result
+            }
+            TenhouResult::Ryukyoku { reason, deltas } => {
+                let mut result = vec![json!(reason)];
+                result.extend(deltas.iter().map(|d| json!(d)));
+                result
+            }
+        };
+        entries.push(Value::Array(result));
+        Value::Array(entries)
+    }
+
+    fn from_value(value: &Value, round: usize) -> Result<Self, TenhouError> {
+        let entries = value
+            .as_array()
+            .filter(|entries| entries.len() == 17)
+            .ok_or_else(|| malformed(round, "expected 17 entries"))?;
+
+        let [kyoku, honba, kyotaku]: [i32; 3] = from_value(&entries[0], round, "header")?;
+        let mut players: [TenhouPlayer; 4] = Default::default();
+        for (seat, player) in players.iter_mut().enumerate() {
+            let base = 4 + seat * 3;
+            player.haipai = from_value(&entries[base], round, "starting hand")?;
+            player.takes = from_value(&entries[base + 1], round, "draws")?;
+            player.discards = from_value(&entries[base + 2], round, "discards")?;
+        }
+
+        let result = entries[16]
+            .as_array()
+            .ok_or_else(|| malformed(round, "missing result"))?;
+        let kind = result
+            .first()
+            .and_then(Value::as_str)
+            .ok_or_else(|| malformed(round, "missing result type"))?;
+        let result = if kind == AGARI {
+            let agari = result[1..]
+                .chunks(2)
+                .map(|win| {
+                    let [deltas, info] = win else {
+                        return Err(malformed(round, "incomplete win"));
+                    };
+                    let info: Vec<Value> = from_value(info, round, "win info")?;
+                    let seat = |i: usize| {
+                        info.get(i)
+                            .and_then(Value::as_u64)
+                            .filter(|&seat| seat < 4)
+                            .map(|seat| seat as usize)
+                            .ok_or_else(|| malformed(round, "invalid win seat"))
+                    };
+                    Ok(TenhouAgari {
+                        deltas: from_value(deltas, round, "win deltas")?,
+                        winner: seat(0)?,
+                        from: seat(1)?,
+                        pao: seat(2)?,
+                        summary: info
+                            .iter()
+                            .skip(3)
+                            .filter_map(|s| s.as_str().map(str::to_string))
+                            .collect(),
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            TenhouResult::Agari(agari)
+        } else {
+            TenhouResult::Ryukyoku {
+                reason: kind.to_string(),
+                deltas: result
+                    .get(1)
+                    .map(|d| from_value(d, round, "draw deltas"))
+                    .transpose()?,
+            }
+        };
+
+        Ok(Self {
+            kyoku,
+            honba,
+            kyotaku,
+            scores: from_value(&entries[1], round, "scores")?,
+            dora: from_value(&entries[2], round, "dora")?,
+            ura_dora: from_value(&entries[3], round, "ura dora")?,
+            players,
+            result,
+        })
+    }
+}
+
+/// A full Tenhou JSON log
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TenhouLog {
+    pub title: Vec<String>,
+    pub names: [String; 4],
+    pub rule: TenhouRule,
+    /// Seed of the recorded game. Not part of Tenhou's format; viewers
+    /// ignore it.
+    pub seed: Option<u64>,
+    pub rounds: Vec<TenhouRound>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawLog {
+    #[serde(default)]
+    title: Vec<String>,
+    #[serde(default)]
+    name: [String; 4],
+    #[serde(default)]
+    rule: TenhouRule,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    log: Vec<Value>,
+}
+
+impl TenhouLog {
+    /// Serialize the log as Tenhou JSON
+    pub fn to_json(&self) -> Result<String, TenhouError> {
+        Ok(serde_json::to_string(&RawLog {
+            title: self.title.clone(),
+            name: self.names.clone(),
+            rule: self.rule.clone(),
+            seed: self.seed,
+            log: self.rounds.iter().map(TenhouRound::to_value).collect(),
+        })?)
+    }
+
+    /// Parse a Tenhou JSON log
+    pub fn from_json(json: &str) -> Result<Self, TenhouError> {
+        let raw: RawLog = serde_json::from_str(json)?;
+        Ok(Self {
+            title: raw.title,
+            names: raw.name,
+            rule: raw.rule,
+            seed: raw.seed,
+            rounds: raw
+                .log
+                .iter()
+                .enumerate()
+                .map(|(i, round)| TenhouRound::from_value(round, i))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Record a log from every observed state of a game.
+    ///
+    /// The log is lossy: observed states carry no dora indicators, so the
+    /// dora and ura dora lists are empty, and wins are summarized by their
+    /// points alone (`"8000点"`) without yaku. Replaying such a log gives
+    /// back the hands, calls, discards and points, not the scoring details.
+    pub fn record<'a>(states: impl IntoIterator<Item = &'a ObservedGameState>) -> Self {
+        let mut recorder = TenhouRecorder::new();
+        for state in states {
+            recorder.push(state);
+        }
+        recorder.finish()
+    }
+
+    /// Drive a game to completion and record it, with the same losses as
+    /// [`record`](Self::record)
+    pub fn record_game(mut runner: GameRunner) -> Result<Self, MahjongFFIError> {
+        let mut recorder = TenhouRecorder::new();
+        for state in runner.by_ref() {
+            recorder.push(&state);
+        }
+        runner.summary()?;
+        Ok(recorder.finish())
+    }
+
+    /// Replay the log into the sequence of observed states it describes,
+    /// one per draw, call, riichi, discard and round result
+    pub fn states(&self) -> Result<Vec<ObservedGameState>, TenhouError> {
+        let mut replay = Replay::new(self.seed.unwrap_or(0));
+        for (i, round) in self.rounds.iter().enumerate() {
+            replay.round(round, i)?;
+        }
+        replay.emit(StateFunctionType::GameEnd, 0);
+
+        let mut states = replay.states;
+        for i in 1..states.len() {
+            states[i - 1].next_state = states[i].curr_state;
+        }
+        Ok(states)
+    }
+}
+
+/// Seat offset of the discarder as seen from the caller
+fn relative(seat: usize, from: usize) -> usize {
+    (from + 4 - seat) % 4
+}
+
+/// Build a marker string with `marker` and the called tile inserted before
+/// the tile at `position`
+fn call_string(marker: char, called: Piece, own: &[Piece], position: usize) -> String {
+    let mut codes: Vec<String> = own.iter().map(|&p| tile_code(p).to_string()).collect();
+    codes.insert(
+        position.min(codes.len()),
+        format!("{marker}{}", tile_code(called)),
+    );
+    codes.concat()
+}
+
+/// Remove one copy of each piece in `taken` from `pieces`, preferring exact
+/// matches so red fives stay where they are
+fn remove_pieces(pieces: &mut Vec<Piece>, taken: &[Piece]) {
+    for piece in taken {
+        let i = pieces
+            .iter()
+            .position(|p| p == piece)
+            .or_else(|| pieces.iter().position(|p| p.index() == piece.index()));
+        if let Some(i) = i {
+            pieces.remove(i);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RoundRecorder {
+    round: Option<TenhouRound>,
+    riichi: [bool; 4],
+    pending_riichi: [bool; 4],
+    discarded_since_draw: [bool; 4],
+    last_draw: [Option<Piece>; 4],
+    last_discard: Option<(usize, Piece)>,
+    /// Tile kind and marker position of every pon, for later upgrades
+    pons: [Vec<(usize, usize)>; 4],
+    winners: Vec<(usize, usize)>,
+}
+
+/// Records observed states into a [`TenhouLog`]
+#[derive(Debug, Default)]
+pub struct TenhouRecorder {
+    differ: EventDiffer,
+    log: TenhouLog,
+    current: RoundRecorder,
+}
+
+impl TenhouRecorder {
+    pub fn new() -> Self {
+        Self {
+            log: TenhouLog {
+                title: vec!["libmahjong".to_string(), String::new()],
+                names: ["Seat 0", "Seat 1", "Seat 2", "Seat 3"].map(str::to_string),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Set the player names shown by viewers
+    pub fn with_names(mut self, names: [String; 4]) -> Self {
+        self.log.names = names;
+        self
+    }
+
+    /// Record the next observed state
+    pub fn push(&mut self, state: &ObservedGameState) {
+        self.log.seed.get_or_insert(state.seed);
+        for event in self.differ.push(state) {
+            self.record(&event, state);
+        }
+    }
+
+    /// Finish recording and return the log. A round that has not ended is
+    /// closed without a result.
+    pub fn finish(mut self) -> TenhouLog {
+        self.close_round(None);
+        self.log
+    }
+
+    fn close_round(&mut self, points: Option<&[i32; 4]>) {
+        let current = std::mem::take(&mut self.current);
+        let Some(mut round) = current.round else {
+            return;
+        };
+
+        let deltas = points.map(|points| {
+            std::array::from_fn(|seat| {
+                points[seat] - round.scores[seat]
+                    + if current.riichi[seat] {
+                        RIICHI_DEPOSIT
+                    } else {
+                        0
+                    }
+            })
+        });
+
+        round.result = match (current.winners.as_slice(), deltas) {
+            ([], deltas) => TenhouResult::Ryukyoku {
+                reason: RYUKYOKU.to_string(),
+                deltas,
+            },
+            (winners, deltas) => {
+                let deltas = deltas.unwrap_or_default();
+                TenhouResult::Agari(
+                    winners
+                        .iter()
+                        .map(|&(winner, from)| {
+                            // Deltas cannot be split between multiple winners,
+                            // so each win only shows its own exchange
+                            let deltas = if winners.len() == 1 {
+                                deltas
+                            } else {
+                                let mut own = [0; 4];
+                                own[winner] = deltas[winner];
+                                own[from] = -deltas[winner];
+                                own
+                            };
+                            let gain = if winner == from {
+                                -deltas.iter().filter(|&&d| d < 0).sum::<i32>()
+                            } else {
+                                -deltas[from]
+                            };
+                            TenhouAgari {
+                                deltas,
+                                winner,
+                                from,
+                                pao: winner,
+                                summary: vec![format!("{gain}点")],
+                            }
+                        })
+                        .collect(),
+                )
+            }
+        };
+        self.log.rounds.push(round);
+    }
+
+    fn record(&mut self, event: &GameEvent, state: &ObservedGameState) {
+        if let GameEvent::Deal { round, hands, .. } = event {
+            self.close_round(None);
+            self.current.round = Some(TenhouRound {
+                kyoku: *round,
+                honba: state.counters,
+                kyotaku: state.riichi_sticks,
+                scores: state.points,
+                dora: Vec::new(),
+                ura_dora: Vec::new(),
+                players: hands.clone().map(|mut hand| {
+                    hand.sort();
+                    TenhouPlayer {
+                        haipai: hand.into_iter().map(tile_code).collect(),
+                        ..Default::default()
+                    }
+                }),
+                result: TenhouResult::Ryukyoku {
+                    reason: RYUKYOKU.to_string(),
+                    deltas: None,
+                },
+            });
+            return;
+        }
+        if let GameEvent::RoundEnd { points, .. } = event {
+            self.close_round(Some(points));
+            return;
+        }
+
+        let current = &mut self.current;
+        let Some(round) = current.round.as_mut() else {
+            return;
+        };
+        let called = current.last_discard.map(|(_, piece)| piece);
+        let last_discarder = current.last_discard.map(|(seat, _)| seat);
+        let discarder = |seat: usize, from: Option<usize>| from.or(last_discarder).unwrap_or(seat);
+
+        match event {
+            GameEvent::Draw { seat, piece } => {
+                round.players[*seat]
+                    .takes
+                    .push(TenhouEntry::Tile(tile_code(*piece)));
+                current.last_draw[*seat] = Some(*piece);
+                current.discarded_since_draw[*seat] = false;
+            }
+            GameEvent::Discard { seat, piece } => {
+                let code = match current.last_draw[*seat].take() {
+                    Some(drawn) if drawn == *piece => TSUMOGIRI,
+                    _ => tile_code(*piece),
+                };
+                let entry = if std::mem::take(&mut current.pending_riichi[*seat]) {
+                    TenhouEntry::Call(format!("r{code}"))
+                } else {
+                    TenhouEntry::Tile(code)
+                };
+                round.players[*seat].discards.push(entry);
+                current.discarded_since_draw[*seat] = true;
+                current.last_discard = Some((*seat, *piece));
+            }
+            GameEvent::Riichi { seat } => {
+                current.riichi[*seat] = true;
+                match round.players[*seat].discards.last_mut() {
+                    // The riichi was observed after its discard
+                    Some(entry) if current.discarded_since_draw[*seat] => {
+                        if let TenhouEntry::Tile(code) = *entry {
+                            *entry = TenhouEntry::Call(format!("r{code}"));
+                        }
+                    }
+                    _ => current.pending_riichi[*seat] = true,
+                }
+            }
+            GameEvent::Chi { seat, meld, .. } => {
+                let called = called.unwrap_or(meld.start);
+                let mut own = meld.pieces();
+                remove_pieces(&mut own, &[called]);
+                round.players[*seat]
+                    .takes
+                    .push(TenhouEntry::Call(call_string('c', called, &own, 0)));
+                current.last_draw[*seat] = None;
+            }
+            GameEvent::Pon {
+                seat, from, meld, ..
+            } => {
+                let called = called.unwrap_or(meld.start);
+                let position = 3 - relative(*seat, discarder(*seat, *from));
+                round.players[*seat]
+                    .takes
+                    .push(TenhouEntry::Call(call_string(
+                        'p',
+                        called,
+                        &[meld.start; 2],
+                        position,
+                    )));
+                current.pons[*seat].push((meld.start.index(), position));
+                current.last_draw[*seat] = None;
+            }
+            GameEvent::Kan {
+                seat, from, meld, ..
+            } => {
+                let called = called.unwrap_or(meld.start);
+                let position = match relative(*seat, discarder(*seat, *from)) {
+                    1 => 3,
+                    relative => 3 - relative,
+                };
+                let player = &mut round.players[*seat];
+                player.takes.push(TenhouEntry::Call(call_string(
+                    'm',
+                    called,
+                    &[meld.start; 3],
+                    position,
+                )));
+                player.discards.push(TenhouEntry::Tile(0));
+                current.last_draw[*seat] = None;
+            }
+            GameEvent::ConcealedKan { seat, meld, .. } => {
+                round.players[*seat]
+                    .discards
+                    .push(TenhouEntry::Call(call_string(
+                        'a',
+                        meld.start,
+                        &[meld.start; 3],
+                        3,
+                    )));
+            }
+            GameEvent::ConvertedKan { seat, meld, .. } => {
+                let added = current.last_draw[*seat].unwrap_or(meld.start);
+                let position = current.pons[*seat]
+                    .iter()
+                    .find(|(index, _)| *index == meld.start.index())
+                    .map_or(0, |&(_, position)| position);
+                round.players[*seat]
+                    .discards
+                    .push(TenhouEntry::Call(call_string(
+                        'k',
+                        added,
+                        &[meld.start; 3],
+                        position,
+                    )));
+            }
+            GameEvent::Ron { seat, from, .. } => {
+                let from = discarder(*seat, *from);
+                current.winners.push((*seat, from));
+            }
+            GameEvent::Tsumo { seat, .. } => current.winners.push((*seat, *seat)),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CallKind {
+    Chi,
+    Pon,
+    Daiminkan,
+    Kakan,
+    Ankan,
+    Riichi,
+}
+
+/// A parsed marker string
+#[derive(Debug)]
+struct ParsedCall {
+    kind: CallKind,
+    /// All codes, including the one following the marker
+    codes: Vec<u8>,
+    /// Index into `codes` of the tile following the marker
+    marker: usize,
+}
+
+impl ParsedCall {
+    fn parse(call: &str) -> Result<Self, TenhouError> {
+        let invalid = || TenhouError::InvalidCall(call.to_string());
+        let position = call
+            .find(|c: char| c.is_ascii_alphabetic())
+            .ok_or_else(invalid)?;
+        let kind = match &call[position..position + 1] {
+            "c" => CallKind::Chi,
+            "p" => CallKind::Pon,
+            "m" => CallKind::Daiminkan,
+            "k" => CallKind::Kakan,
+            "a" => CallKind::Ankan,
+            "r" => CallKind::Riichi,
+            _ => return Err(invalid()),
+        };
+
+        let digits = format!("{}{}", &call[..position], &call[position + 1..]);
+        if position % 2 != 0 || digits.len() % 2 != 0 {
+            return Err(invalid());
+        }
+        let codes = digits
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                std::str::from_utf8(pair)
+                    .ok()
+                    .and_then(|code| code.parse().ok())
+                    .ok_or_else(invalid)
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        let expected = match kind {
+            CallKind::Riichi => 1,
+            CallKind::Chi | CallKind::Pon => 3,
+            _ => 4,
+        };
+        if codes.len() != expected {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            kind,
+            codes,
+            marker: position / 2,
+        })
+    }
+
+    fn called(&self) -> u8 {
+        self.codes[self.marker]
+    }
+
+    fn pieces(&self) -> Result<Vec<Piece>, TenhouError> {
+        self.codes
+            .iter()
+            .map(|&code| tile_from_code(code))
+            .collect()
+    }
+
+    /// Pieces that came from the caller's hand
+    fn own(&self) -> Result<Vec<Piece>, TenhouError> {
+        let mut pieces = self.pieces()?;
+        if self.kind != CallKind::Ankan {
+            pieces.remove(self.marker);
+        }
+        Ok(pieces)
+    }
+
+    /// Seat offset of the discarder for calls on discards
+    fn relative(&self) -> Option<usize> {
+        match (self.kind, self.marker) {
+            (CallKind::Chi, _) => Some(3),
+            (CallKind::Pon | CallKind::Daiminkan, 0) => Some(3),
+            (CallKind::Pon | CallKind::Daiminkan, 1) => Some(2),
+            (CallKind::Pon, 2) | (CallKind::Daiminkan, 3) => Some(1),
+            _ => None,
+        }
+    }
+}
+
+fn empty_hand() -> Hand {
+    Hand {
+        live_pieces: Vec::new(),
+        melds: Vec::new(),
+        discards: Vec::new(),
+        open: false,
+        riichi: false,
+        riichi_piece_discard: 0,
+        riichi_round: 0,
+    }
+}
+
+struct Replay {
+    state: ObservedGameState,
+    states: Vec<ObservedGameState>,
+}
+
+impl Replay {
+    fn new(seed: u64) -> Self {
+        Self {
+            state: ObservedGameState {
+                current_player: 0,
+                turn_num: 0,
+                round_num: 0,
+                riichi_sticks: 0,
+                counters: 0,
+                last_call: 0,
+                last_caller: 0,
+                concealed_kan: false,
+                seed,
+                pending_piece: None,
+                scores: [0; 4],
+                points: [0; 4],
+                has_ronned: [false; 4],
+                hands: [empty_hand(), empty_hand(), empty_hand(), empty_hand()],
+                prev_state: StateFunctionType::Error,
+                curr_state: StateFunctionType::GameStart,
+                next_state: StateFunctionType::Error,
+            },
+            states: Vec::new(),
+        }
+    }
+
+    fn emit(&mut self, state: StateFunctionType, player: usize) {
+        self.state.prev_state = self.state.curr_state;
+        self.state.curr_state = state;
+        self.state.current_player = player as i32;
+        self.states.push(self.state.clone());
+        self.state.concealed_kan = false;
+    }
+
+    fn round(&mut self, round: &TenhouRound, index: usize) -> Result<(), TenhouError> {
+        let dealer = round.kyoku.rem_euclid(4) as usize;
+        let state = &mut self.state;
+        state.round_num = round.kyoku;
+        state.counters = round.honba;
+        state.riichi_sticks = round.kyotaku;
+        state.points = round.scores;
+        state.turn_num = 0;
+        state.pending_piece = None;
+        state.has_ronned = [false; 4];
+        for (hand, player) in state.hands.iter_mut().zip(&round.players) {
+            *hand = empty_hand();
+            hand.live_pieces = player
+                .haipai
+                .iter()
+                .map(|&code| tile_from_code(code))
+                .collect::<Result<_, _>>()?;
+        }
+        self.emit(StateFunctionType::RoundStart, dealer);
+
+        let mut take = [0; 4];
+        let mut discard = [0; 4];
+        let mut drawn = None;
+        let mut seat = dealer;
+        'turns: while let Some(entry) = round.players[seat].takes.get(take[seat]) {
+            take[seat] += 1;
+            match entry {
+                TenhouEntry::Tile(code) => {
+                    let piece = tile_from_code(*code)?;
+                    self.state.pending_piece = None;
+                    self.state.turn_num += 1;
+                    self.state.hands[seat].live_pieces.push(piece);
+                    drawn = Some(piece);
+                    self.emit(StateFunctionType::Draw, seat);
+                }
+                TenhouEntry::Call(call) => {
+                    let call = ParsedCall::parse(call)?;
+                    let pieces = call.pieces()?;
+                    let (meld_type, state) = match call.kind {
+                        CallKind::Chi => (MeldType::Chi, StateFunctionType::Chi),
+                        CallKind::Pon => (MeldType::Pon, StateFunctionType::Pon),
+                        CallKind::Daiminkan => (MeldType::Kan, StateFunctionType::Kan),
+                        _ => return Err(malformed(index, "unexpected call in draws")),
+                    };
+                    let hand = &mut self.state.hands[seat];
+                    remove_pieces(&mut hand.live_pieces, &call.own()?);
+                    hand.melds.push(Meld {
+                        meld_type,
+                        start: pieces
+                            .iter()
+                            .min()
+                            .copied()
+                            .unwrap_or(pieces[0])
+                            .normalized(),
+                    });
+                    hand.open = true;
+                    self.state.pending_piece = None;
+                    self.state.last_call = meld_type as i32;
+                    self.state.last_caller = seat as i32;
+                    self.emit(state, seat);
+
+                    if call.kind == CallKind::Daiminkan {
+                        // Skip the placeholder discard; the replacement draw follows
+                        discard[seat] += 1;
+                        continue;
+                    }
+                }
+            }
+
+            let Some(entry) = round.players[seat].discards.get(discard[seat]) else {
+                break;
+            };
+            discard[seat] += 1;
+
+            let (code, riichi) = match entry {
+                TenhouEntry::Tile(code) => (*code, false),
+                TenhouEntry::Call(call) => {
+                    let call = ParsedCall::parse(call)?;
+                    let hand = &mut self.state.hands[seat];
+                    match call.kind {
+                        CallKind::Riichi => (call.called(), true),
+                        CallKind::Ankan => {
+                            let pieces = call.pieces()?;
+                            remove_pieces(&mut hand.live_pieces, &pieces);
+                            hand.melds.push(Meld {
+                                meld_type: MeldType::ConcealedKan,
+                                start: pieces[0].normalized(),
+                            });
+                            self.state.concealed_kan = true;
+                            self.emit(StateFunctionType::ConcealedKan, seat);
+                            continue 'turns;
+                        }
+                        CallKind::Kakan => {
+                            let added = tile_from_code(call.called())?;
+                            remove_pieces(&mut hand.live_pieces, &[added]);
+                            let pon = hand.melds.iter_mut().find(|meld| {
+                                meld.meld_type == MeldType::Pon
+                                    && meld.start.index() == added.index()
+                            });
+                            match pon {
+                                Some(meld) => meld.meld_type = MeldType::Kan,
+                                None => return Err(malformed(index, "upgrade without a pon")),
+                            }
+                            self.emit(StateFunctionType::ConvertedKan, seat);
+                            continue 'turns;
+                        }
+                        _ => return Err(malformed(index, "unexpected call in discards")),
+                    }
+                }
+            };
+
+            let piece = match code {
+                TSUMOGIRI => drawn.ok_or_else(|| malformed(index, "tsumogiri without a draw"))?,
+                code => tile_from_code(code)?,
+            };
+            if riichi {
+                let state = &mut self.state;
+                state.hands[seat].riichi = true;
+                state.hands[seat].riichi_piece_discard = state.hands[seat].discards.len() as i32;
+                state.hands[seat].riichi_round = state.turn_num;
+                state.riichi_sticks += 1;
+                state.points[seat] -= RIICHI_DEPOSIT;
+                self.emit(StateFunctionType::Riichi, seat);
+            }
+
+            let hand = &mut self.state.hands[seat];
+            remove_pieces(&mut hand.live_pieces, &[piece]);
+            hand.discards.push(piece);
+            self.state.pending_piece = Some(piece);
+            drawn = None;
+            self.emit(StateFunctionType::Discard, seat);
+
+            // A call on this discard shows up as the next draw entry of the
+            // caller; pon and kan take priority over chi
+            let caller = (1..4)
+                .map(|offset| (seat + offset) % 4)
+                .filter_map(|other| {
+                    let TenhouEntry::Call(call) = round.players[other].takes.get(take[other])?
+                    else {
+                        return None;
+                    };
+                    let call = ParsedCall::parse(call).ok()?;
+                    let matches = call.relative() == Some(relative(other, seat))
+                        && tile_from_code(call.called()).ok()?.index() == piece.index();
+                    matches.then_some((call.kind == CallKind::Chi, other))
+                })
+                .min();
+            seat = caller.map_or((seat + 1) % 4, |(_, caller)| caller);
+        }
+
+        self.result(round)
+    }
+
+    fn result(&mut self, round: &TenhouRound) -> Result<(), TenhouError> {
+        match &round.result {
+            TenhouResult::Agari(agari) => {
+                let Some(first) = agari.first() else {
+                    return Ok(());
+                };
+                if first.winner == first.from {
+                    self.emit(StateFunctionType::Tsumo, first.winner);
+                } else {
+                    for win in agari {
+                        self.state.has_ronned[win.winner] = true;
+                    }
+                    self.emit(StateFunctionType::Ron, first.from);
+                }
+                for win in agari {
+                    for (points, delta) in self.state.points.iter_mut().zip(win.deltas) {
+                        *points += delta;
+                    }
+                }
+                self.state.riichi_sticks = 0;
+                self.emit(StateFunctionType::RoundEnd, first.winner);
+            }
+            TenhouResult::Ryukyoku { deltas, .. } => {
+                let seat = self.state.current_player as usize;
+                self.emit(StateFunctionType::Exhaust, seat);
+                for (points, delta) in self.state.points.iter_mut().zip(deltas.unwrap_or_default())
+                {
+                    *points += delta;
+                }
+                self.emit(StateFunctionType::RoundEnd, seat);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::event_log;
+    use crate::notation::parse_pieces;
+
+    const LOG: &str = r#"{
+        "title": ["libmahjong", ""],
+        "name": ["A", "B", "C", "D"],
+        "rule": {"disp": "libmahjong", "aka": 1},
+        "seed": 42,
+        "log": [[
+            [0, 0, 0], [25000, 25000, 25000, 25000], [], [],
+            [12, 13, 15, 16, 17, 22, 23, 24, 32, 33, 34, 41, 42], [11, 14], [60, 60],
+            [11, 18, 19, 21, 52, 26, 27, 31, 35, 36, 37, 43, 47], [12], [47],
+            [12, 13, 14, 21, 22, 23, 28, 29, 38, 39, 44, 47, 47], ["p474747"], [21],
+            [14, 16, 17, 18, 24, 25, 26, 34, 35, 36, 45, 45, 45], [13], ["r60"],
+            ["和了", [-8000, 0, 0, 9000], [3, 0, 3, "8000点"]]
+        ]]
+    }"#;
+
+    #[test]
+    fn tile_codes_round_trip() {
+        for piece in parse_pieces("19m5p0p9s1234567z").unwrap() {
+            assert_eq!(tile_from_code(tile_code(piece)).unwrap(), piece);
+        }
+        assert_eq!(tile_code("0s".parse().unwrap()), 53);
+        assert_eq!(tile_code("5z".parse().unwrap()), 45);
+        assert!(tile_from_code(TSUMOGIRI).is_err());
+        assert!(tile_from_code(40).is_err());
+    }
+
+    #[test]
+    fn parses_call_markers() {
+        let pon = ParsedCall::parse("25p2525").unwrap();
+        assert_eq!(
+            (pon.kind, pon.marker, pon.relative()),
+            (CallKind::Pon, 1, Some(2))
+        );
+        let kan = ParsedCall::parse("393939m39").unwrap();
+        assert_eq!(kan.relative(), Some(1));
+        assert_eq!(
+            ParsedCall::parse("424242a42").unwrap().own().unwrap().len(),
+            4
+        );
+        assert!(ParsedCall::parse("c2526").is_err());
+        assert!(ParsedCall::parse("x111111").is_err());
+    }
+
+    #[test]
+    fn imports_states_and_exports_them_back() {
+        let log = TenhouLog::from_json(LOG).unwrap();
+        let states = log.states().unwrap();
+
+        let events = event_log(&states);
+        assert!(matches!(events[0], GameEvent::Deal { round: 0, .. }));
+        assert!(events.contains(&GameEvent::Riichi { seat: 3 }));
+        assert!(events.contains(&GameEvent::Ron {
+            seat: 3,
+            from: Some(0),
+            piece: Some("4m".parse().unwrap()),
+        }));
+        let last = states.last().unwrap();
+        assert_eq!(last.curr_state, StateFunctionType::GameEnd);
+        assert_eq!(last.points, [17000, 25000, 25000, 33000]);
+
+        let mut exported = TenhouLog::record(&states);
+        exported.names = log.names.clone();
+        assert_eq!(exported, log);
+
+        let json: Value = serde_json::from_str(&exported.to_json().unwrap()).unwrap();
+        assert_eq!(json, serde_json::from_str::<Value>(LOG).unwrap());
+    }
+}
@@ -0,0 +1,502 @@
+//! Duplicate-style tournaments between controllers.
+//!
+//! Every seed is played by every table of four entrants, once for each of the
+//! 24 seat orders of the table by default, so no entrant benefits from a
+//! lucky seat or from who it follows. Games are
+//! played with [`simulate`](crate::simulate) and folded into [`Standings`] in
+//! schedule order, which keeps ratings reproducible.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::rules::RuleSet;
+use crate::runner::DEFAULT_STEP_LIMIT;
+use crate::settings::GameSettings;
+use crate::simulate::{
+    play_game, run_parallel, seed_count, GameResult, Progress, ProgressCallback, SimulationError,
+};
+
+/// Rating every entrant starts with
+pub const INITIAL_RATING: f64 = 1500.0;
+
+/// Default Elo K-factor, split between the three opponents at the table
+pub const DEFAULT_K_FACTOR: f64 = 32.0;
+
+/// Errors produced when scheduling a [`Tournament`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TournamentError {
+    #[error("A tournament needs at least 4 entrants, got {0}")]
+    NotEnoughEntrants(usize),
+}
+
+/// Which seat orders each table plays a seed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// The 4 cyclic shifts, so every entrant sits in every seat once
+    Cyclic,
+    /// All 24 seat orders
+    AllPermutations,
+}
+
+impl Rotation {
+    /// Get the seat orders as indices into a table of four
+    pub fn orders(&self) -> Vec<[usize; 4]> {
+        match self {
+            Rotation::Cyclic => (0..4)
+                .map(|shift| std::array::from_fn(|seat| (seat + shift) % 4))
+                .collect(),
+            Rotation::AllPermutations => {
+                let mut orders = Vec::with_capacity(24);
+                for a in 0..4 {
+                    for b in (0..4).filter(|&b| b != a) {
+                        for c in (0..4).filter(|&c| c != a && c != b) {
+                            orders.push([a, b, c, 6 - a - b - c]);
+                        }
+                    }
+                }
+                orders
+            }
+        }
+    }
+}
+
+/// One game of the schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledGame {
+    pub seed: u64,
+    /// Entrant index sitting in each seat
+    pub lineup: [usize; 4],
+}
+
+/// A finished game of the schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TournamentGame {
+    pub lineup: [usize; 4],
+    pub result: GameResult,
+}
+
+/// A set of controllers playing every seed against each other
+pub struct Tournament {
+    entrants: Vec<String>,
+    seeds: Range<u64>,
+    rotation: Rotation,
+    rules: RuleSet,
+    threads: usize,
+    step_limit: usize,
+    k_factor: f64,
+    progress: Option<ProgressCallback>,
+}
+
+impl Tournament {
+    /// Schedule the entrants over the seeds. With more than four entrants
+    /// every combination of four forms a table, league style. Each table
+    /// plays every seat order unless [`with_rotation`](Self::with_rotation)
+    /// picks fewer.
+    pub fn new<I, S>(entrants: I, seeds: Range<u64>) -> Result<Self, TournamentError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let entrants: Vec<String> = entrants.into_iter().map(Into::into).collect();
+        if entrants.len() < 4 {
+            return Err(TournamentError::NotEnoughEntrants(entrants.len()));
+        }
+
+        Ok(Self {
+            entrants,
+            seeds,
+            rotation: Rotation::AllPermutations,
+            rules: RuleSet::default(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            step_limit: DEFAULT_STEP_LIMIT,
+            k_factor: DEFAULT_K_FACTOR,
+            progress: None,
+        })
+    }
+
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Set the number of worker threads
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Set the maximum number of advances per game
+    pub fn with_step_limit(mut self, step_limit: usize) -> Self {
+        self.step_limit = step_limit;
+        self
+    }
+
+    /// Set how far a single game can move a rating
+    pub fn with_k_factor(mut self, k_factor: f64) -> Self {
+        self.k_factor = k_factor;
+        self
+    }
+
+    /// Call `callback` from the worker threads whenever a game finishes
+    pub fn on_progress(mut self, callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    pub fn entrants(&self) -> &[String] {
+        &self.entrants
+    }
+
+    /// Get every table of four entrants, in lexicographic order
+    pub fn tables(&self) -> Vec<[usize; 4]> {
+        let n = self.entrants.len();
+        let mut tables = Vec::new();
+        for a in 0..n {
+            for b in a + 1..n {
+                for c in b + 1..n {
+                    for d in c + 1..n {
+                        tables.push([a, b, c, d]);
+                    }
+                }
+            }
+        }
+        tables
+    }
+
+    /// List the games in the order they are rated: by seed, then table,
+    /// then rotation
+    pub fn schedule(&self) -> Vec<ScheduledGame> {
+        let tables = self.tables();
+        let orders = self.rotation.orders();
+        let mut schedule =
+            Vec::with_capacity(seed_count(&self.seeds) * tables.len() * orders.len());
+        for seed in self.seeds.clone() {
+            for table in &tables {
+                for order in &orders {
+                    schedule.push(ScheduledGame {
+                        seed,
+                        lineup: order.map(|i| table[i]),
+                    });
+                }
+            }
+        }
+        schedule
+    }
+
+    /// Play the schedule and compute the standings
+    pub fn run(&self) -> TournamentResult {
+        let schedule = self.schedule();
+        let results = run_parallel(
+            schedule.len(),
+            self.threads,
+            self.progress.as_deref(),
+            |index| {
+                let game = schedule[index];
+                let settings = GameSettings {
+                    seed: game.seed,
+                    seat_controllers: game.lineup.map(|i| self.entrants[i].clone()),
+                    rules: self.rules.clone(),
+                };
+                play_game(settings, self.step_limit)
+            },
+        );
+
+        let mut games = Vec::with_capacity(results.len());
+        let mut failures = Vec::new();
+        for (game, result) in schedule.iter().zip(results) {
+            match result {
+                Ok(result) => games.push(TournamentGame {
+                    lineup: game.lineup,
+                    result,
+                }),
+                Err(error) => failures.push(error),
+            }
+        }
+
+        TournamentResult {
+            standings: Standings::from_games(&self.entrants, &games, self.k_factor),
+            games,
+            failures,
+        }
+    }
+}
+
+/// Everything a finished [`Tournament`] produced
+#[derive(Debug)]
+pub struct TournamentResult {
+    pub standings: Standings,
+    /// Games that finished, in schedule order
+    pub games: Vec<TournamentGame>,
+    /// Games that could not be played
+    pub failures: Vec<SimulationError>,
+}
+
+/// Aggregated results of one entrant
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntrantStats {
+    pub name: String,
+    pub games: usize,
+    /// Number of games finished in each place, first place first
+    pub placements: [usize; 4],
+    pub total_points: i64,
+    /// Rounds played, used as the denominator of the win and deal-in rates
+    pub rounds: usize,
+    pub wins: usize,
+    pub deal_ins: usize,
+    pub rating: f64,
+}
+
+impl EntrantStats {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            games: 0,
+            placements: [0; 4],
+            total_points: 0,
+            rounds: 0,
+            wins: 0,
+            deal_ins: 0,
+            rating: INITIAL_RATING,
+        }
+    }
+
+    /// Get the average placement, counting first place as 1
+    pub fn average_placement(&self) -> f64 {
+        let total: usize = (1..).zip(self.placements).map(|(place, n)| place * n).sum();
+        ratio(total as f64, self.games)
+    }
+
+    pub fn average_points(&self) -> f64 {
+        ratio(self.total_points as f64, self.games)
+    }
+
+    /// Get the share of rounds this entrant won
+    pub fn win_rate(&self) -> f64 {
+        ratio(self.wins as f64, self.rounds)
+    }
+
+    /// Get the share of rounds this entrant dealt into a ron
+    pub fn deal_in_rate(&self) -> f64 {
+        ratio(self.deal_ins as f64, self.rounds)
+    }
+}
+
+fn ratio(numerator: f64, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator / denominator as f64
+    }
+}
+
+/// Entrants ranked by rating
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standings {
+    pub entries: Vec<EntrantStats>,
+}
+
+impl Standings {
+    /// Aggregate games in the given order. Ratings are updated after every
+    /// game as four players' worth of pairwise Elo matches.
+    pub fn from_games(entrants: &[String], games: &[TournamentGame], k_factor: f64) -> Self {
+        let mut entries: Vec<_> = entrants.iter().cloned().map(EntrantStats::new).collect();
+
+        for game in games {
+            let result = &game.result;
+            let (wins, deal_ins) = (result.wins_by_seat(), result.deal_ins_by_seat());
+            for seat in 0..4 {
+                let stats = &mut entries[game.lineup[seat]];
+                stats.games += 1;
+                stats.placements[result.placements[seat]] += 1;
+                stats.total_points += i64::from(result.points[seat]);
+                stats.rounds += result.rounds;
+                stats.wins += wins[seat];
+                stats.deal_ins += deal_ins[seat];
+            }
+
+            let ratings = game.lineup.map(|entrant| entries[entrant].rating);
+            for seat in 0..4 {
+                let delta: f64 = (0..4)
+                    .filter(|&other| other != seat)
+                    .map(|other| {
+                        let expected =
+                            1.0 / (1.0 + 10f64.powf((ratings[other] - ratings[seat]) / 400.0));
+                        let actual = if result.placements[seat] < result.placements[other] {
+                            1.0
+                        } else {
+                            0.0
+                        };
+                        actual - expected
+                    })
+                    .sum();
+                entries[game.lineup[seat]].rating += k_factor / 3.0 * delta;
+            }
+        }
+
+        entries.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+        Self { entries }
+    }
+}
+
+impl fmt::Display for Standings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .entries
+            .iter()
+            .map(|entry| entry.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("Entrant".len());
+
+        writeln!(
+            f,
+            "{:>4}  {:<width$}  {:>6}  {:>9}  {:>10}  {:>6}  {:>9}  {:>7}",
+            "Rank", "Entrant", "Games", "Avg place", "Avg points", "Win %", "Deal-in %", "Rating"
+        )?;
+        for (rank, entry) in self.entries.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>4}  {:<width$}  {:>6}  {:>9.2}  {:>10.0}  {:>6.1}  {:>9.1}  {:>7.0}",
+                rank + 1,
+                entry.name,
+                entry.games,
+                entry.average_placement(),
+                entry.average_points(),
+                entry.win_rate() * 100.0,
+                entry.deal_in_rate() * 100.0,
+                entry.rating,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate::Win;
+
+    fn entrants() -> Vec<&'static str> {
+        vec![
+            "AlphabeticalBot",
+            "AngryDiscardoBot",
+            "FastTanyao",
+            "TotoBot",
+            "ThriceBot",
+        ]
+    }
+
+    #[test]
+    fn rotations_seat_every_entrant_everywhere() {
+        let tournament = Tournament::new(entrants(), 0..2).unwrap();
+        assert_eq!(tournament.tables().len(), 5);
+
+        let seated = |schedule: &[ScheduledGame], entrant: usize, seat: usize| {
+            schedule
+                .iter()
+                .filter(|game| game.lineup[seat] == entrant)
+                .count()
+        };
+
+        // 24 lineups per table and seed
+        let schedule = tournament.schedule();
+        assert_eq!(schedule.len(), 2 * 5 * 24);
+        for seed in 0..2 {
+            for table in tournament.tables() {
+                let lineups = schedule
+                    .iter()
+                    .filter(|game| {
+                        let mut seated = game.lineup;
+                        seated.sort();
+                        game.seed == seed && seated == table
+                    })
+                    .count();
+                assert_eq!(lineups, 24);
+            }
+        }
+        for entrant in 0..5 {
+            for seat in 0..4 {
+                // Each entrant sits at 4 of the 5 tables, in each seat for 6
+                // of the 24 orders
+                assert_eq!(seated(&schedule, entrant, seat), 2 * 4 * 6);
+            }
+        }
+
+        let cyclic = tournament.with_rotation(Rotation::Cyclic).schedule();
+        assert_eq!(cyclic.len(), 2 * 5 * 4);
+        for entrant in 0..5 {
+            for seat in 0..4 {
+                assert_eq!(seated(&cyclic, entrant, seat), 2 * 4);
+            }
+        }
+
+        let orders = Rotation::AllPermutations.orders();
+        assert_eq!(orders.len(), 24);
+        assert!(orders.iter().all(|order| {
+            let mut sorted = *order;
+            sorted.sort();
+            sorted == [0, 1, 2, 3]
+        }));
+
+        assert_eq!(
+            Tournament::new(["AlphabeticalBot"; 3], 0..1).err(),
+            Some(TournamentError::NotEnoughEntrants(3))
+        );
+    }
+
+    #[test]
+    fn aggregates_standings() {
+        let entrants: Vec<String> = entrants()[..4].iter().map(|e| e.to_string()).collect();
+        let result = |seed, points: [i32; 4], wins| GameResult {
+            seed,
+            points,
+            placements: crate::runner::placements(&points),
+            rounds: 8,
+            steps: 0,
+            reached_game_end: true,
+            wins,
+        };
+        let games = vec![
+            TournamentGame {
+                lineup: [0, 1, 2, 3],
+                result: result(
+                    0,
+                    [40000, 30000, 20000, 10000],
+                    vec![Win {
+                        round: 0,
+                        winner: 0,
+                        dealt_in: Some(3),
+                    }],
+                ),
+            },
+            TournamentGame {
+                lineup: [1, 2, 3, 0],
+                result: result(0, [10000, 20000, 30000, 40000], vec![]),
+            },
+        ];
+
+        let standings = Standings::from_games(&entrants, &games, DEFAULT_K_FACTOR);
+        let first = &standings.entries[0];
+        assert_eq!(first.name, "AlphabeticalBot");
+        assert_eq!(first.placements, [2, 0, 0, 0]);
+        assert_eq!(first.average_placement(), 1.0);
+        assert_eq!(first.average_points(), 40000.0);
+        assert_eq!(first.win_rate(), 1.0 / 16.0);
+        assert!(first.rating > INITIAL_RATING);
+
+        let total: f64 = standings.entries.iter().map(|e| e.rating).sum();
+        assert!((total - 4.0 * INITIAL_RATING).abs() < 1e-9);
+
+        let deal_ins: usize = standings.entries.iter().map(|e| e.deal_ins).sum();
+        assert_eq!(deal_ins, 1);
+
+        let table = standings.to_string();
+        assert_eq!(table.lines().count(), 5);
+        assert!(table.lines().nth(1).unwrap().contains("AlphabeticalBot"));
+    }
+}
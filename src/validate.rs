@@ -0,0 +1,374 @@
+//! Check that observed states are physically possible.
+//!
+//! [`validate_state`] looks at a single state: tiles in play, copies per
+//! tile, red fives, hand sizes and the point total. The wall is not
+//! observed, so tiles in play are only checked against an upper bound; a
+//! state that loses tiles still passes. [`validate_transition`] checks that
+//! one state can follow another. [`Validator`] and [`validate_game`] apply
+//! both to a whole game.
+
+use crate::observe::{ObservedGameState, StateFunctionType};
+use crate::piece::{Piece, DEAD_WALL_SIZE, TILE_COUNT, TILE_KINDS};
+use crate::rules::{RuleSet, RIICHI_DEPOSIT};
+
+/// Most tiles that can be outside the dead wall
+pub const MAX_TILES_IN_PLAY: usize = TILE_COUNT - DEAD_WALL_SIZE;
+
+/// Something an observed state or transition should never show
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Violation {
+    /// More tiles are in hands, melds and discards than can leave the wall.
+    /// This is an upper bound, not a check that all 136 tiles are accounted
+    /// for.
+    #[error("{count} tiles are in play, at most {MAX_TILES_IN_PLAY} can leave the wall")]
+    TooManyTiles { count: usize },
+    #[error("{count} copies of {piece} are in play")]
+    TooManyCopies { piece: Piece, count: usize },
+    #[error("{count} copies of red five {piece} are in play")]
+    TooManyRedFives { piece: Piece, count: usize },
+    #[error("red five {piece} is in play, but the rules have no red fives")]
+    RedFiveNotAllowed { piece: Piece },
+    #[error("seat {seat} holds {size} tiles counting melds as three in {state:?}")]
+    HandSize {
+        seat: usize,
+        size: usize,
+        state: StateFunctionType,
+    },
+    #[error("points and riichi sticks total {total}, expected {expected}")]
+    PointTotal { total: i32, expected: i32 },
+    #[error("{from:?} cannot be followed by {to:?}")]
+    IllegalTransition {
+        from: StateFunctionType,
+        to: StateFunctionType,
+    },
+    #[error("state reports {reported:?} as its previous state but followed {actual:?}")]
+    PreviousStateMismatch {
+        reported: StateFunctionType,
+        actual: StateFunctionType,
+    },
+    #[error("state announced {announced:?} as the next state but moved to {actual:?}")]
+    NextStateMismatch {
+        announced: StateFunctionType,
+        actual: StateFunctionType,
+    },
+    #[error("seed changed from {from} to {to}")]
+    SeedChanged { from: u64, to: u64 },
+}
+
+/// A violation and the index of the state it was found in
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("step {step}: {violation}")]
+pub struct StepViolation {
+    pub step: usize,
+    pub violation: Violation,
+}
+
+/// States in which the current player may hold a drawn or called piece
+fn holds_extra_piece(state: StateFunctionType) -> bool {
+    use StateFunctionType::*;
+    matches!(
+        state,
+        Draw | Replacement | PlayerHand | Riichi | Pon | Chi | Tsumo | RoundEnd | GameEnd
+    )
+}
+
+/// Check a single state against the rules it was played with
+pub fn validate_state(state: &ObservedGameState, rules: &RuleSet) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    let mut copies = [0usize; TILE_KINDS];
+    let mut red_fives = [0usize; TILE_KINDS];
+    let mut in_play = 0;
+    for hand in &state.hands {
+        let melded = hand.melds.iter().flat_map(|meld| meld.pieces());
+        for piece in hand
+            .live_pieces
+            .iter()
+            .chain(&hand.discards)
+            .copied()
+            .chain(melded)
+        {
+            copies[piece.index()] += 1;
+            red_fives[piece.index()] += piece.is_red_five() as usize;
+            in_play += 1;
+        }
+    }
+    if in_play > MAX_TILES_IN_PLAY {
+        violations.push(Violation::TooManyTiles { count: in_play });
+    }
+    for index in 0..TILE_KINDS {
+        let Some(piece) = Piece::from_index(index) else {
+            continue;
+        };
+        if copies[index] > 4 {
+            violations.push(Violation::TooManyCopies {
+                piece,
+                count: copies[index],
+            });
+        }
+        if red_fives[index] > 1 {
+            violations.push(Violation::TooManyRedFives {
+                piece,
+                count: red_fives[index],
+            });
+        }
+        if red_fives[index] > 0 && !rules.red_fives {
+            violations.push(Violation::RedFiveNotAllowed { piece });
+        }
+    }
+
+    // Hands are empty until the first deal
+    let dealt = state.hands.iter().any(|hand| hand.live_piece_count() > 0);
+    if dealt {
+        for (seat, hand) in state.hands.iter().enumerate() {
+            let size = hand.live_piece_count() + 3 * hand.meld_count();
+            let extra_allowed =
+                seat as i32 == state.current_player && holds_extra_piece(state.curr_state);
+            if !(size == 13 || size == 14 && extra_allowed) {
+                violations.push(Violation::HandSize {
+                    seat,
+                    size,
+                    state: state.curr_state,
+                });
+            }
+        }
+    }
+
+    // Points are only handed out at GameStart
+    if state.curr_state != StateFunctionType::Error {
+        let total = state.points.iter().sum::<i32>() + state.riichi_sticks * RIICHI_DEPOSIT;
+        let expected = 4 * rules.starting_points;
+        if total != expected {
+            violations.push(Violation::PointTotal { total, expected });
+        }
+    }
+
+    violations
+}
+
+/// Check that `next` can directly follow `prev`
+pub fn validate_transition(prev: &ObservedGameState, next: &ObservedGameState) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    if !prev.curr_state.can_transition_to(next.curr_state) {
+        violations.push(Violation::IllegalTransition {
+            from: prev.curr_state,
+            to: next.curr_state,
+        });
+    }
+    if next.prev_state != prev.curr_state {
+        violations.push(Violation::PreviousStateMismatch {
+            reported: next.prev_state,
+            actual: prev.curr_state,
+        });
+    }
+    if prev.next_state != next.curr_state {
+        violations.push(Violation::NextStateMismatch {
+            announced: prev.next_state,
+            actual: next.curr_state,
+        });
+    }
+    if prev.seed != next.seed {
+        violations.push(Violation::SeedChanged {
+            from: prev.seed,
+            to: next.seed,
+        });
+    }
+    violations
+}
+
+/// Checks each state of a game as it is observed
+#[derive(Debug, Clone)]
+pub struct Validator {
+    rules: RuleSet,
+    previous: Option<ObservedGameState>,
+}
+
+impl Validator {
+    pub fn new(rules: RuleSet) -> Self {
+        Self {
+            rules,
+            previous: None,
+        }
+    }
+
+    /// Validate the next state of the game and the transition into it
+    pub fn check(&mut self, state: &ObservedGameState) -> Vec<Violation> {
+        let mut violations = validate_state(state, &self.rules);
+        if let Some(previous) = &self.previous {
+            violations.extend(validate_transition(previous, state));
+        }
+        self.previous = Some(state.clone());
+        violations
+    }
+}
+
+/// Validate every state of a game, in order
+pub fn validate_game<I>(states: I, rules: &RuleSet) -> Vec<StepViolation>
+where
+    I: IntoIterator<Item = ObservedGameState>,
+{
+    let mut validator = Validator::new(rules.clone());
+    states
+        .into_iter()
+        .enumerate()
+        .flat_map(|(step, state)| {
+            validator
+                .check(&state)
+                .into_iter()
+                .map(move |violation| StepViolation { step, violation })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::gamestate::{Backend, GameState};
+    use crate::settings::GameSettings;
+    use proptest::prelude::*;
+
+    /// Enough states to cover a few rounds without playing whole games
+    const STATES_PER_GAME: usize = 250;
+
+    fn engine_game(seed: u64, controller: &str, rules: &RuleSet) -> GameState {
+        let settings = GameSettings::builder()
+            .seed(seed)
+            .all_seats(controller)
+            .rules(rules.clone())
+            .build()
+            .unwrap();
+        GameState::with_backend(settings, Backend::Engine).unwrap()
+    }
+
+    fn engine_states(seed: u64, controller: &str, rules: &RuleSet) -> Vec<ObservedGameState> {
+        engine_game(seed, controller, rules)
+            .into_iter()
+            .take(STATES_PER_GAME)
+            .collect()
+    }
+
+    fn controllers() -> impl Strategy<Value = &'static str> {
+        prop::sample::select(vec!["TotoBot", "ThriceBot", "FastTanyao"])
+    }
+
+    fn rule_sets() -> impl Strategy<Value = RuleSet> {
+        prop::sample::select(vec![RuleSet::default(), RuleSet::wrc(), RuleSet::ema()])
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(6))]
+
+        #[test]
+        fn engine_states_are_valid(
+            seed in any::<u64>(),
+            controller in controllers(),
+            rules in rule_sets(),
+        ) {
+            let states = engine_states(seed, controller, &rules);
+            let violations = validate_game(states, &rules);
+            prop_assert!(violations.is_empty(), "{violations:#?}");
+        }
+
+        #[test]
+        fn detects_extra_copies(seed in 0u64..1000, step in 3usize..STATES_PER_GAME) {
+            let rules = RuleSet::default();
+            let mut state = engine_states(seed, "TotoBot", &rules).swap_remove(step);
+            let piece = state.hands[0].live_pieces[0];
+            state.hands[1].discards.extend([piece; 4]);
+
+            let detected = validate_state(&state, &rules).iter().any(|v| matches!(
+                v,
+                Violation::TooManyCopies { piece: p, .. } if p.index() == piece.index()
+            ));
+            prop_assert!(detected);
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        /// Whole games over many seeds, too slow for debug builds. Run with
+        /// `cargo test --release -- --ignored`.
+        #[test]
+        #[ignore]
+        fn engine_games_are_valid_over_many_seeds(
+            seed in any::<u64>(),
+            controller in controllers(),
+            rules in rule_sets(),
+        ) {
+            let violations = validate_game(engine_game(seed, controller, &rules), &rules);
+            prop_assert!(violations.is_empty(), "{violations:#?}");
+        }
+    }
+
+    #[cfg(not(feature = "native"))]
+    #[test]
+    fn mock_states_are_valid() {
+        let rules = RuleSet::default();
+        for seed in 0..20 {
+            let settings = GameSettings::builder()
+                .seed(seed)
+                .all_seats("AlphabeticalBot")
+                .build()
+                .unwrap();
+            let violations = validate_game(GameState::new(settings).unwrap(), &rules);
+            assert!(violations.is_empty(), "seed {seed}: {violations:#?}");
+        }
+    }
+
+    #[test]
+    fn detects_red_fives_the_rules_leave_out() {
+        let rules = RuleSet::wrc();
+        let mut state = engine_states(2, "TotoBot", &rules).swap_remove(10);
+        assert!(validate_state(&state, &rules).is_empty());
+
+        let plain = state.hands[0].live_pieces[0];
+        let red = Piece::red_five(crate::piece::Suit::Pin);
+        state.hands[0].live_pieces[0] = red;
+        let violations = validate_state(&state, &rules);
+        assert!(violations.contains(&Violation::RedFiveNotAllowed {
+            piece: red.normalized()
+        }));
+
+        state.hands[0].live_pieces[0] = plain;
+        assert!(validate_state(&state, &rules).is_empty());
+    }
+
+    #[test]
+    fn detects_bad_transitions_and_points() {
+        let rules = RuleSet::default();
+        let states = engine_states(1, "TotoBot", &rules);
+        let (hand, discard) = states
+            .windows(2)
+            .find(|pair| pair[1].curr_state == StateFunctionType::Discard)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .unwrap();
+        assert!(validate_transition(&hand, &discard).is_empty());
+
+        let violations = validate_transition(&discard, &hand);
+        assert!(violations.contains(&Violation::IllegalTransition {
+            from: StateFunctionType::Discard,
+            to: StateFunctionType::PlayerHand,
+        }));
+        assert!(violations
+            .iter()
+            .any(|v| matches!(v, Violation::PreviousStateMismatch { .. })));
+
+        let mut state = discard;
+        state.points[0] += 1000;
+        assert_eq!(
+            validate_state(&state, &rules),
+            [Violation::PointTotal {
+                total: 101_000,
+                expected: 100_000
+            }]
+        );
+        state.points[0] -= 1000;
+        let seat = state.current_player as usize;
+        state.hands[seat].live_pieces.pop();
+        assert!(matches!(
+            validate_state(&state, &rules)[..],
+            [Violation::HandSize { size: 12, .. }]
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+//! Hidden-information projection of [`ObservedGameState`] for one seat.
+//!
+//! The observed state exposes every hand, which is fine for spectating but
+//! not for bots or training code. A [`PlayerView`] keeps only what the seat
+//! could see at the table: its own hand, and for every seat the discards,
+//! open melds, riichi state and the number of concealed tiles. The game seed
+//! is dropped as well, since it determines the wall.
+
+use crate::observe::{Hand, Meld, MeldType, ObservedGameState, StateFunctionType};
+use crate::piece::Piece;
+
+/// A meld as seen by a player. Opponents' concealed kans have no visible
+/// piece.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisibleMeld {
+    pub meld_type: MeldType,
+    pub start: Option<Piece>,
+}
+
+impl VisibleMeld {
+    /// Get the visible pieces of the meld, empty if it is hidden
+    pub fn pieces(&self) -> Vec<Piece> {
+        self.start
+            .map(|start| {
+                Meld {
+                    meld_type: self.meld_type,
+                    start,
+                }
+                .pieces()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The public part of a seat's hand
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeatView {
+    /// Number of concealed pieces in the hand
+    pub concealed_count: usize,
+    pub melds: Vec<VisibleMeld>,
+    pub discards: Vec<Piece>,
+    pub open: bool,
+    pub riichi: bool,
+    pub riichi_piece_discard: i32,
+    pub riichi_round: i32,
+}
+
+impl SeatView {
+    fn project(hand: &Hand, own: bool) -> Self {
+        Self {
+            concealed_count: hand.live_piece_count(),
+            melds: hand
+                .melds
+                .iter()
+                .map(|meld| VisibleMeld {
+                    meld_type: meld.meld_type,
+                    start: (own || meld.meld_type != MeldType::ConcealedKan).then_some(meld.start),
+                })
+                .collect(),
+            discards: hand.discards.clone(),
+            open: hand.open,
+            riichi: hand.riichi,
+            riichi_piece_discard: hand.riichi_piece_discard,
+            riichi_round: hand.riichi_round,
+        }
+    }
+}
+
+/// What a single seat is allowed to know about the game
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerView {
+    /// The seat this view belongs to
+    pub seat: usize,
+    /// The seat's own hand, including concealed pieces
+    pub hand: Hand,
+    /// Public information for every seat, including this one
+    pub seats: [SeatView; 4],
+    pub current_player: i32,
+    pub turn_num: i32,
+    pub round_num: i32,
+    pub riichi_sticks: i32,
+    pub counters: i32,
+    pub last_call: i32,
+    pub last_caller: i32,
+    pub concealed_kan: bool,
+    /// The piece in play, hidden from other seats unless it was just
+    /// discarded or added to a kan
+    pub pending_piece: Option<Piece>,
+    pub scores: [i32; 4],
+    pub points: [i32; 4],
+    pub has_ronned: [bool; 4],
+    pub prev_state: StateFunctionType,
+    pub curr_state: StateFunctionType,
+    pub next_state: StateFunctionType,
+}
+
+impl PlayerView {
+    /// Check if it is this seat's turn
+    pub fn is_own_turn(&self) -> bool {
+        usize::try_from(self.current_player).is_ok_and(|player| player == self.seat)
+    }
+
+    /// Get the seat offset from this one, e.g. 1 for the next player
+    pub fn relative_seat(&self, seat: usize) -> usize {
+        (seat + 4 - self.seat % 4) % 4
+    }
+}
+
+impl ObservedGameState {
+    /// Project the state down to what `seat` may see. Seats outside `0..4`
+    /// are wrapped.
+    pub fn view_for(&self, seat: usize) -> PlayerView {
+        let seat = seat % 4;
+        PlayerView {
+            seat,
+            hand: self.hands[seat].clone(),
+            seats: std::array::from_fn(|i| SeatView::project(&self.hands[i], i == seat)),
+            current_player: self.current_player,
+            turn_num: self.turn_num,
+            round_num: self.round_num,
+            riichi_sticks: self.riichi_sticks,
+            counters: self.counters,
+            last_call: self.last_call,
+            last_caller: self.last_caller,
+            concealed_kan: self.concealed_kan,
+            pending_piece: self
+                .pending_piece
+                .filter(|_| self.pending_piece_visible_to(seat)),
+            scores: self.scores,
+            points: self.points,
+            has_ronned: self.has_ronned,
+            prev_state: self.prev_state,
+            curr_state: self.curr_state,
+            next_state: self.next_state,
+        }
+    }
+
+    /// Check if `seat` may see the pending piece. It can hold the current
+    /// player's draw, so other seats only see it once it has been discarded
+    /// or added to a kan.
+    fn pending_piece_visible_to(&self, seat: usize) -> bool {
+        usize::try_from(self.current_player).is_ok_and(|player| player == seat)
+            || matches!(
+                self.curr_state,
+                StateFunctionType::Discard
+                    | StateFunctionType::ConvertedKan
+                    | StateFunctionType::KanDiscard
+            )
+    }
+
+    /// Project the state for every seat
+    pub fn views(&self) -> [PlayerView; 4] {
+        std::array::from_fn(|seat| self.view_for(seat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_hand;
+
+    #[test]
+    fn masks_opponent_hands_and_concealed_kans() {
+        let hand = parse_hand("123m456p789s1z [2222z] (333z)").unwrap();
+        let state = ObservedGameState {
+            current_player: 2,
+            turn_num: 5,
+            round_num: 0,
+            riichi_sticks: 0,
+            counters: 0,
+            last_call: 0,
+            last_caller: 0,
+            concealed_kan: false,
+            seed: 1234,
+            pending_piece: None,
+            scores: [0; 4],
+            points: [25000; 4],
+            has_ronned: [false; 4],
+            hands: [hand.clone(), hand.clone(), hand.clone(), hand.clone()],
+            prev_state: StateFunctionType::Draw,
+            curr_state: StateFunctionType::PlayerHand,
+            next_state: StateFunctionType::Discard,
+        };
+
+        let view = state.view_for(1);
+        assert_eq!(view.hand, hand);
+        assert_eq!(view.relative_seat(0), 3);
+        assert!(!view.is_own_turn());
+
+        let own = &view.seats[1];
+        assert_eq!(own.concealed_count, 10);
+        assert_eq!(own.melds[0].pieces().len(), 4);
+
+        let opponent = &view.seats[2];
+        assert_eq!(opponent.concealed_count, 10);
+        assert_eq!(opponent.melds[0].start, None);
+        assert!(opponent.melds[0].pieces().is_empty());
+        assert_eq!(opponent.melds[1].start, Some("3z".parse().unwrap()));
+        assert!(state.views()[2].is_own_turn());
+    }
+
+    #[test]
+    fn masks_the_drawn_piece_from_opponents() {
+        let mut state = ObservedGameState {
+            current_player: 2,
+            pending_piece: Some("5m".parse().unwrap()),
+            curr_state: StateFunctionType::Draw,
+            ..Default::default()
+        };
+
+        let views = state.views();
+        assert_eq!(views[2].pending_piece, state.pending_piece);
+        for seat in [0, 1, 3] {
+            assert_eq!(views[seat].pending_piece, None);
+        }
+
+        state.curr_state = StateFunctionType::Discard;
+        assert!(state
+            .views()
+            .iter()
+            .all(|view| view.pending_piece == state.pending_piece));
+    }
+}
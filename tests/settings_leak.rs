@@ -0,0 +1,61 @@
+//! Lives in its own test binary because it replaces the global allocator,
+//! which would otherwise count the allocations of every other test.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use libmahjong_rs::ffi::gamestate::GameState;
+use libmahjong_rs::settings::GameSettings;
+
+/// Tracks the bytes allocated and not yet freed by each thread
+struct CountingAllocator;
+
+thread_local! {
+    static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+fn track(delta: isize) {
+    let _ = LIVE_BYTES.try_with(|live| live.set(live.get() + delta));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        track(layout.size() as isize);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        track(-(layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn settings(seed: u64) -> GameSettings {
+    GameSettings {
+        seed,
+        seat_controllers: std::array::from_fn(|_| "AlphabeticalBot".to_string()),
+        rules: Default::default(),
+    }
+}
+
+#[test]
+fn creating_games_does_not_leak_controller_names() {
+    // Warm up anything allocated once per thread or process
+    drop(GameState::new(settings(0)));
+
+    let before = LIVE_BYTES.with(Cell::get);
+    for seed in 0..10_000 {
+        drop(GameState::new(settings(seed)));
+    }
+    let after = LIVE_BYTES.with(Cell::get);
+
+    assert_eq!(
+        after - before,
+        0,
+        "game creation leaked {} bytes",
+        after - before
+    );
+}